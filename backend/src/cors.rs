@@ -0,0 +1,223 @@
+//! Cross-origin requests from the web app, which is served from another origin than the
+//! backend.
+//!
+//! Requests carry the sign-in cookie, so only origins on the allow-list get CORS headers;
+//! browsers refuse other origins the response. Preflight `OPTIONS` requests are answered
+//! here rather than by a route.
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use std::io::Cursor;
+
+/// Which origins may call the API from a browser, and with what, from the `cors` setting.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Cors {
+    /// Exact origins, e.g. `https://planner.example.com`, without a path.
+    allowed_origins: Vec<String>,
+    allowed_methods: Vec<String>,
+    /// Request headers scripts may set, beyond those browsers always allow.
+    allowed_headers: Vec<String>,
+    /// How long, in seconds, browsers may cache a preflight answer.
+    max_age: u32,
+}
+
+/// Allows the frontend as `trunk serve` serves it, on port 8080.
+impl Default for Cors {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![
+                "http://localhost:8080".to_owned(),
+                "http://127.0.0.1:8080".to_owned(),
+            ],
+            allowed_methods: ["GET", "POST", "PUT", "PATCH", "DELETE"]
+                .map(str::to_owned)
+                .to_vec(),
+            allowed_headers: vec!["Content-Type".to_owned(), "Authorization".to_owned()],
+            max_age: 3600,
+        }
+    }
+}
+
+impl Cors {
+    fn allows_origin(&self, origin: &str) -> bool {
+        let origin = origin.trim_end_matches('/');
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed.trim_end_matches('/') == origin)
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(method.trim()))
+    }
+
+    /// Whether every header in a comma separated `Access-Control-Request-Headers` is allowed.
+    fn allows_headers(&self, headers: &str) -> bool {
+        headers
+            .split(',')
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+            .all(|header| {
+                self.allowed_headers
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(header))
+            })
+    }
+
+    /// Answer a preflight, whatever the route would have: no content if the request it
+    /// asks about is allowed, otherwise forbidden and without CORS headers.
+    fn preflight(&self, request: &Request<'_>, response: &mut Response<'_>, method: &str) {
+        response.set_sized_body(0, Cursor::new(""));
+        response.remove_header("Content-Type");
+        let headers = request
+            .headers()
+            .get_one("Access-Control-Request-Headers")
+            .unwrap_or("");
+        let origin = request.headers().get_one("Origin");
+        match origin {
+            Some(origin)
+                if self.allows_origin(origin)
+                    && self.allows_method(method)
+                    && self.allows_headers(headers) =>
+            {
+                response.set_status(Status::NoContent);
+                self.allow(response, origin);
+                response.set_header(Header::new(
+                    "Access-Control-Allow-Methods",
+                    self.allowed_methods.join(", "),
+                ));
+                response.set_header(Header::new(
+                    "Access-Control-Allow-Headers",
+                    self.allowed_headers.join(", "),
+                ));
+                response.set_header(Header::new(
+                    "Access-Control-Max-Age",
+                    self.max_age.to_string(),
+                ));
+            }
+            _ => response.set_status(Status::Forbidden),
+        }
+    }
+
+    fn allow(&self, response: &mut Response<'_>, origin: &str) {
+        response.set_header(Header::new(
+            "Access-Control-Allow-Origin",
+            origin.to_owned(),
+        ));
+        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "Attaching CORS headers to responses",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        // The CORS headers depend on the origin, so caches must not share responses.
+        response.adjoin_header(Header::new("Vary", "Origin"));
+        let preflight = request
+            .headers()
+            .get_one("Access-Control-Request-Method")
+            .filter(|_| request.method() == Method::Options);
+        if let Some(method) = preflight {
+            self.preflight(request, response, method);
+            return;
+        }
+        if let Some(origin) = request.headers().get_one("Origin") {
+            if self.allows_origin(origin) {
+                self.allow(response, origin);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::local::blocking::Client;
+
+    #[get("/")]
+    fn hello() -> &'static str {
+        "Hello"
+    }
+
+    fn client() -> Client {
+        let rocket = rocket::build()
+            .attach(Cors::default())
+            .mount("/", routes![hello]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn only_allowed_origins_get_cors_headers() {
+        let client = client();
+
+        let allowed = client
+            .get("/")
+            .header(Header::new("Origin", "http://localhost:8080"))
+            .dispatch();
+        let other = client
+            .get("/")
+            .header(Header::new("Origin", "https://evil.example"))
+            .dispatch();
+
+        assert_eq!(
+            allowed.headers().get_one("Access-Control-Allow-Origin"),
+            Some("http://localhost:8080")
+        );
+        assert_eq!(allowed.headers().get_one("Vary"), Some("Origin"));
+        assert_eq!(other.status(), Status::Ok);
+        assert_eq!(other.headers().get_one("Access-Control-Allow-Origin"), None);
+    }
+
+    #[test]
+    fn preflights_are_answered_without_a_route() {
+        let client = client();
+        let preflight = |origin: &str, method: &str, headers: &str| {
+            client
+                .options("/course/1")
+                .header(Header::new("Origin", origin.to_owned()))
+                .header(Header::new(
+                    "Access-Control-Request-Method",
+                    method.to_owned(),
+                ))
+                .header(Header::new(
+                    "Access-Control-Request-Headers",
+                    headers.to_owned(),
+                ))
+                .dispatch()
+        };
+
+        let allowed = preflight("http://localhost:8080", "DELETE", "content-type");
+        assert_eq!(allowed.status(), Status::NoContent);
+        assert_eq!(
+            allowed
+                .headers()
+                .get_one("Access-Control-Allow-Credentials"),
+            Some("true")
+        );
+        assert_eq!(
+            preflight("https://evil.example", "DELETE", "").status(),
+            Status::Forbidden
+        );
+        assert_eq!(
+            preflight("http://localhost:8080", "TRACE", "").status(),
+            Status::Forbidden
+        );
+        assert_eq!(
+            preflight("http://localhost:8080", "GET", "X-Secret").status(),
+            Status::Forbidden
+        );
+    }
+}
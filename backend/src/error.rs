@@ -0,0 +1,64 @@
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+
+use common::ApiError;
+
+use crate::repository::RepositoryError;
+
+/// An `ApiError` sent as JSON with its code as the response status.
+#[derive(Debug)]
+pub struct Error(pub ApiError);
+
+pub type ApiResult<T> = Result<T, Error>;
+
+impl Error {
+    pub fn not_found(what: &str, id: impl std::fmt::Display) -> Self {
+        Error(ApiError::not_found(&format!(
+            "no {} with id '{}'",
+            what, id
+        )))
+    }
+}
+
+impl<E: Into<ApiError>> From<E> for Error {
+    fn from(e: E) -> Self {
+        Error(e.into())
+    }
+}
+
+impl From<RepositoryError> for ApiError {
+    fn from(e: RepositoryError) -> Self {
+        // The cause is for the server log; clients cannot do anything with it.
+        log::error!("{}", e);
+        match e.what() {
+            Some(what) => ApiError::internal(&format!("{} storage failed", what)),
+            None => ApiError::internal("storage failed"),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Error {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = Status::from_code(self.0.code()).unwrap_or(Status::InternalServerError);
+        let mut response = Json(self.0).respond_to(request)?;
+        response.set_status(status);
+        Ok(response)
+    }
+}
+
+/// Turn Rocket's own failures, e.g. unmatched routes or malformed JSON, into `ApiError`s.
+#[catch(default)]
+pub fn default_catcher(status: Status, request: &Request<'_>) -> Json<ApiError> {
+    let message = match status.code {
+        400 => "the request was malformed".to_owned(),
+        401 => "sign in first, or send a valid API key".to_owned(),
+        403 => "not allowed with an API key, or not with a read-only one".to_owned(),
+        404 => format!("nothing at {}", request.uri().path()),
+        422 => "the request body could not be understood".to_owned(),
+        500 => "the server failed to handle the request".to_owned(),
+        _ => status.reason_lossy().to_lowercase(),
+    };
+    Json(ApiError::new(status.code, &message))
+}
@@ -0,0 +1,167 @@
+use rocket::fairing::AdHoc;
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+use rocket::tokio::time::{interval, Duration};
+use rocket::Shutdown;
+
+use chrono::{DateTime, Utc};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use common::{ServerEvent, Session, SessionState};
+
+use crate::users::{Owned, User};
+use crate::Config;
+
+/// How many events a slow client may fall behind before it starts missing them.
+const CAPACITY: usize = 256;
+
+/// How long after serving a session is forgotten if it has not been ended. A session
+/// paused for so long is taken to be abandoned too.
+const FORGET_AFTER: chrono::Duration = chrono::Duration::hours(12);
+
+/// Events, each for the user who owns what changed.
+pub fn channel() -> broadcast::Sender<Owned<ServerEvent>> {
+    broadcast::channel(CAPACITY).0
+}
+
+/// Send every event for `user` published from now on to one client, until the server
+/// shuts down.
+pub fn stream(
+    mut events: broadcast::Receiver<Owned<ServerEvent>>,
+    user: User,
+    mut shutdown: Shutdown,
+) -> EventStream![] {
+    EventStream! {
+        loop {
+            let event = select! {
+                event = events.recv() => match event {
+                    Ok(event) if event.is_owned_by(&user) => event.item,
+                    Ok(_) => continue,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(missed)) => {
+                        log::warn!("event stream dropped {} events", missed);
+                        continue;
+                    }
+                },
+                _ = &mut shutdown => break,
+            };
+            yield Event::json(&event);
+        }
+    }
+}
+
+/// Publish `stage_started` and `stage_due` events as session stages begin and end, and
+/// end sessions left long after serving.
+pub fn ticker() -> AdHoc {
+    AdHoc::on_liftoff("Session ticker", |rocket| {
+        Box::pin(async move {
+            let config = rocket.state::<Config>().expect("Config is managed");
+            let sessions = config.sessions.clone();
+            let events = config.events.clone();
+            rocket::tokio::spawn(tick(sessions, events));
+        })
+    })
+}
+
+/// Remove the sessions long past serving, returning a `session_ended` event for each.
+fn forget_old(
+    sessions: &mut HashMap<String, Owned<Session>>,
+    now: DateTime<Utc>,
+) -> Vec<Owned<ServerEvent>> {
+    let mut ended = Vec::new();
+    sessions.retain(|id, session| {
+        if !session.item.is_past_serving(now, FORGET_AFTER) {
+            return true;
+        }
+        log::info!("Forgetting session {}, long past serving", id);
+        ended.push(Owned::new(
+            &session.owner,
+            ServerEvent::SessionEnded { id: id.clone() },
+        ));
+        false
+    });
+    ended
+}
+
+async fn tick(
+    sessions: Arc<Mutex<HashMap<String, Owned<Session>>>>,
+    events: broadcast::Sender<Owned<ServerEvent>>,
+) {
+    let mut clock = interval(Duration::from_secs(1));
+    let mut since = Utc::now();
+    // Each session's state as last seen, so changes made to a session between ticks are
+    // measured from its schedule before the change.
+    let mut seen: HashMap<String, SessionState> = HashMap::new();
+    loop {
+        clock.tick().await;
+        let now = Utc::now();
+        let mut changes;
+        match sessions.lock() {
+            Ok(mut sessions) => {
+                changes = forget_old(&mut sessions, now);
+                let mut last = std::mem::take(&mut seen);
+                for (id, session) in sessions.iter() {
+                    let before = last.remove(id).unwrap_or_else(|| session.item.state(since));
+                    let after = session.item.state(now);
+                    changes.extend(
+                        ServerEvent::stage_changes(&before, &after)
+                            .into_iter()
+                            .map(|change| Owned::new(&session.owner, change)),
+                    );
+                    seen.insert(id.clone(), after);
+                }
+            }
+            Err(_) => {
+                log::error!("session lock poisoned, stopping the session ticker");
+                return;
+            }
+        }
+        for change in changes {
+            // Nobody listening is not an error.
+            let _ = events.send(change);
+        }
+        since = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use common::{Course, CourseDetails};
+
+    #[test]
+    fn sessions_long_past_serving_are_forgotten() {
+        let course = Course::new(&CourseDetails::new("0", "Toast"));
+        let now = Utc::now();
+        let mut sessions = HashMap::from([
+            (
+                "old".to_owned(),
+                Owned::new(
+                    "ann",
+                    Session::new("old", &course, now - FORGET_AFTER * 2).unwrap(),
+                ),
+            ),
+            (
+                "new".to_owned(),
+                Owned::new("ann", Session::new("new", &course, now).unwrap()),
+            ),
+        ]);
+
+        let ended = forget_old(&mut sessions, now);
+
+        assert_eq!(
+            ended,
+            vec![Owned::new(
+                "ann",
+                ServerEvent::SessionEnded {
+                    id: "old".to_owned()
+                }
+            )]
+        );
+        assert_eq!(sessions.keys().collect::<Vec<_>>(), vec!["new"]);
+    }
+}
@@ -1,23 +1,219 @@
 use rocket::data::{Limits, ToByteUnit};
 
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::Header;
+use rocket::http::{ContentType, CookieJar, Header, Status};
+use rocket::request::FromParam;
+use rocket::response::stream::EventStream;
 use rocket::serde::json::Json;
-use rocket::State;
+use rocket::tokio::sync::broadcast;
 use rocket::{Request, Response};
+use rocket::{Shutdown, State};
+
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 #[macro_use]
 extern crate rocket;
 
-use common::{Course, CourseDetails, Stage};
+mod cors;
+mod error;
+mod events;
+mod repository;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod store;
+mod users;
+
+use error::{ApiResult, Error};
+use repository::{
+    CourseRepository, MemoryRecords, MemoryRepository, RecordRepository, RepositoryError,
+};
+use users::{Account, ApiKey, MemoryUsers, Owned, SignedIn, User, UserRepository};
+
+use common::{
+    ApiError, ApiKeyDetails, AttentionPlan, Calendar, Course, CourseDetails, CreatedApiKey,
+    Credentials, Kitchen, KitchenPlan, Meal, MealDetails, Metadata, NewApiKey, NewStage, Plan,
+    Planned, PlannedStage, ScheduledStage, ServerEvent, Session, SessionError, SessionState, Share,
+    SharedView, Stage, StageGraph, StageUpdate, TimelineEntry, UserDetails,
+};
 
-#[derive(Default, Debug)]
 struct Config {
-    courses: Arc<Mutex<HashMap<String, Course>>>,
+    courses: Box<dyn CourseRepository>,
+    users: Box<dyn UserRepository>,
+    meals: Box<dyn RecordRepository<Meal>>,
+    sessions: Arc<Mutex<HashMap<String, Owned<Session>>>>,
+    /// Plans in each calendar feed, keyed by the feed's token.
+    calendars: Box<dyn RecordRepository<Vec<Plan>>>,
+    /// Read-only links to courses and meals, keyed by token.
+    shares: Box<dyn RecordRepository<Share>>,
+    events: broadcast::Sender<Owned<ServerEvent>>,
+}
+
+impl Config {
+    fn new(
+        courses: Box<dyn CourseRepository>,
+        users: Box<dyn UserRepository>,
+        meals: Box<dyn RecordRepository<Meal>>,
+        calendars: Box<dyn RecordRepository<Vec<Plan>>>,
+        shares: Box<dyn RecordRepository<Share>>,
+    ) -> Self {
+        Self {
+            courses,
+            users,
+            meals,
+            sessions: Arc::default(),
+            calendars,
+            shares,
+            events: events::channel(),
+        }
+    }
+
+    /// Tell every client `user` is connected with about a change.
+    fn publish(&self, user: &User, event: ServerEvent) {
+        // Nobody listening is not an error.
+        let _ = self.events.send(Owned::new(user.id(), event));
+    }
+
+    fn course_updated(&self, user: &User, course: &Course) {
+        self.publish(
+            user,
+            ServerEvent::CourseUpdated {
+                course: course.details().to_owned(),
+            },
+        );
+    }
+
+    /// Store a new course for `user` under a fresh id, telling their clients about it.
+    fn create_course(&self, user: &User, mut course: Course) -> ApiResult<CourseDetails> {
+        course.set_id(&uuid::Uuid::new_v4().to_string());
+        let details = course.details().to_owned();
+        self.courses.insert(user.id(), course)?;
+        self.publish(
+            user,
+            ServerEvent::CourseCreated {
+                course: details.clone(),
+            },
+        );
+        Ok(details)
+    }
+
+    /// Fail unless `user` owns the course; other users' courses are as good as missing.
+    fn check_course(&self, user: &User, id: &str) -> ApiResult<()> {
+        match self.courses.owner(id)? {
+            Some(owner) if owner == user.id() => Ok(()),
+            _ => Err(Error::not_found("course", id)),
+        }
+    }
+
+    fn course(&self, user: &User, id: &str) -> ApiResult<Course> {
+        self.check_course(user, id)?;
+        self.courses
+            .get(id)?
+            .ok_or_else(|| Error::not_found("course", id))
+    }
+
+    fn meal(&self, user: &User, id: &str) -> ApiResult<Meal> {
+        self.meals
+            .get(id)?
+            .filter(|meal| meal.is_owned_by(user))
+            .map(|meal| meal.item)
+            .ok_or_else(|| Error::not_found("meal", id))
+    }
+
+    /// Change one of `user`'s meals and save it, returning it as it now is.
+    fn modify_meal(
+        &self,
+        user: &User,
+        id: &str,
+        change: impl FnOnce(&mut Meal),
+    ) -> ApiResult<Meal> {
+        let mut change = Some(change);
+        self.meals
+            .modify(id, &mut |meal| {
+                if !meal.is_owned_by(user) {
+                    return false;
+                }
+                if let Some(change) = change.take() {
+                    change(&mut meal.item);
+                }
+                true
+            })?
+            .filter(|meal| meal.is_owned_by(user))
+            .map(|meal| meal.item)
+            .ok_or_else(|| Error::not_found("meal", id))
+    }
+
+    fn sessions(&self) -> Result<MutexGuard<'_, HashMap<String, Owned<Session>>>, RepositoryError> {
+        self.sessions
+            .lock()
+            .map_err(|_| RepositoryError::Poisoned.storing("session"))
+    }
+
+    /// The plans in a feed, for anyone who knows its token, or only its owner if `user`
+    /// is given.
+    fn plans(&self, user: Option<&User>, token: &str) -> ApiResult<Vec<Plan>> {
+        self.calendars
+            .get(token)?
+            .filter(|plans| user.is_none_or(|user| plans.is_owned_by(user)))
+            .map(|plans| plans.item)
+            .ok_or_else(|| Error::not_found("calendar", token))
+    }
+
+    /// Change the plans in one of `user`'s calendar feeds, saving them only if `change`
+    /// returns `true`.
+    fn modify_plans(
+        &self,
+        user: &User,
+        token: &str,
+        mut change: impl FnMut(&mut Vec<Plan>) -> bool,
+    ) -> ApiResult<Vec<Plan>> {
+        self.calendars
+            .modify(token, &mut |plans| {
+                plans.is_owned_by(user) && change(&mut plans.item)
+            })?
+            .filter(|plans| plans.is_owned_by(user))
+            .map(|plans| plans.item)
+            .ok_or_else(|| Error::not_found("calendar", token))
+    }
+
+    /// Apply `action` to one of `user`'s sessions as of now, returning its new state.
+    fn session_action(
+        &self,
+        user: &User,
+        id: &str,
+        action: impl FnOnce(&mut Session, DateTime<Utc>) -> Result<(), SessionError>,
+    ) -> ApiResult<Json<SessionState>> {
+        let now = Utc::now();
+        let mut sessions = self.sessions()?;
+        let session = sessions
+            .get_mut(id)
+            .filter(|session| session.is_owned_by(user))
+            .ok_or_else(|| Error::not_found("session", id))?;
+        action(&mut session.item, now)?;
+        let session = session.item.state(now);
+        self.publish(
+            user,
+            ServerEvent::SessionUpdated {
+                session: session.clone(),
+            },
+        );
+        Ok(Json(session))
+    }
+
+    /// The courses of a meal which still exist.
+    fn meal_courses(&self, meal: &Meal) -> Result<Vec<Course>, RepositoryError> {
+        let mut courses = Vec::new();
+        for id in meal.courses() {
+            courses.extend(self.courses.get(id)?);
+        }
+        Ok(courses)
+    }
 }
 
 #[get("/")]
@@ -25,54 +221,802 @@ fn hello() -> &'static str {
     "Hello, world!"
 }
 
+/// Make an account and sign in to it.
+#[post("/user", data = "<credentials>")]
+fn register(
+    state: &State<Config>,
+    cookies: &CookieJar<'_>,
+    credentials: Json<Credentials>,
+) -> ApiResult<Json<UserDetails>> {
+    credentials.validate()?;
+    let account = Account::new(&credentials).map_err(|e| {
+        error!("Cannot hash password: {}", e);
+        Error(ApiError::internal("cannot make the account"))
+    })?;
+    if !state.users.create(account.clone())? {
+        return Err(Error(ApiError::new(
+            409,
+            &format!("the name '{}' is taken", account.name()),
+        )));
+    }
+    Ok(Json(User::sign_in(cookies, &account).details().to_owned()))
+}
+
+#[post("/login", data = "<credentials>")]
+fn login(
+    state: &State<Config>,
+    cookies: &CookieJar<'_>,
+    credentials: Json<Credentials>,
+) -> ApiResult<Json<UserDetails>> {
+    match state.users.by_name(credentials.name())? {
+        Some(account) if account.verify(credentials.password()) => {
+            Ok(Json(User::sign_in(cookies, &account).details().to_owned()))
+        }
+        Some(_) => Err(Error(ApiError::unauthorized("wrong name or password"))),
+        None => {
+            Account::verify_nobody(credentials.password());
+            Err(Error(ApiError::unauthorized("wrong name or password")))
+        }
+    }
+}
+
+#[post("/logout")]
+fn logout(cookies: &CookieJar<'_>) -> Status {
+    User::sign_out(cookies);
+    Status::NoContent
+}
+
+#[get("/user")]
+fn current_user(user: User) -> Json<UserDetails> {
+    Json(user.details().to_owned())
+}
+
+/// Make an API key for scripts to use instead of signing in. The response holds the key
+/// itself, which cannot be seen again.
+#[post("/api_key", data = "<new>")]
+fn add_api_key(
+    state: &State<Config>,
+    user: SignedIn,
+    new: Json<NewApiKey>,
+) -> ApiResult<Json<CreatedApiKey>> {
+    if new.name().is_empty() {
+        return Err(Error(ApiError::bad_request("an API key needs a name")));
+    }
+    let (key, secret) = ApiKey::generate(&new, Utc::now());
+    let details = key.details().to_owned();
+    if !state.users.update(user.0.id(), &mut |account| {
+        account.add_key(key.clone());
+        true
+    })? {
+        return Err(Error::not_found("user", user.0.id()));
+    }
+    Ok(Json(CreatedApiKey::new(details, &secret)))
+}
+
+#[get("/api_keys")]
+fn get_api_keys(state: &State<Config>, user: SignedIn) -> ApiResult<Json<Vec<ApiKeyDetails>>> {
+    let account = state
+        .users
+        .by_id(user.0.id())?
+        .ok_or_else(|| Error::not_found("user", user.0.id()))?;
+    Ok(Json(account.api_keys().cloned().collect()))
+}
+
+#[delete("/api_key/<id>")]
+fn revoke_api_key(state: &State<Config>, user: SignedIn, id: &str) -> ApiResult<Status> {
+    if !state
+        .users
+        .update(user.0.id(), &mut |account| account.revoke_key(id))?
+    {
+        return Err(Error::not_found("API key", id));
+    }
+    Ok(Status::NoContent)
+}
+
 #[get("/courses")]
-fn get_courses(state: &State<Config>) -> Json<Vec<CourseDetails>> {
-    let r = state
-        .courses
-        .lock()
-        .unwrap()
-        .iter()
-        .map(|(_k, v)| (v.details().to_owned()))
-        .collect();
-    Json(r)
+fn get_courses(state: &State<Config>, user: User) -> ApiResult<Json<Vec<CourseDetails>>> {
+    Ok(Json(state.courses.list(user.id())?))
 }
 
 #[get("/course/<id>")]
-fn course(state: &State<Config>, id: &str) -> Option<Json<Vec<Stage>>> {
-    state
-        .courses
-        .lock()
-        .unwrap()
-        .get(id)
-        .map(Course::stages)
-        .map(|i| i.collect())
-        .map(Json)
+fn course(state: &State<Config>, user: User, id: &str) -> ApiResult<Json<Vec<Stage>>> {
+    Ok(Json(state.course(&user, id)?.stages().collect()))
+}
+
+#[get("/course/<id>/schedule?<serve_at>&<tz>")]
+fn schedule(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    serve_at: &str,
+    tz: Option<&str>,
+) -> ApiResult<Json<Vec<ScheduledStage>>> {
+    let serve_at = common::schedule::serve_time(serve_at, tz)?;
+    Ok(Json(state.course(&user, id)?.schedule(&serve_at)?))
+}
+
+/// The schedule as an iCalendar file, with a reminder as each stage starts.
+#[get("/course/<id>/schedule.ics?<serve_at>&<tz>")]
+fn schedule_ics(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    serve_at: &str,
+    tz: Option<&str>,
+) -> ApiResult<(ContentType, String)> {
+    let serve_at = common::schedule::serve_time(serve_at, tz)?;
+    let course = state.course(&user, id)?;
+    let mut calendar = Calendar::new(course.details().name(), Utc::now());
+    calendar.add_schedule(
+        &format!("{}-{}", id, serve_at.timestamp()),
+        course.details().name(),
+        &course.schedule(&serve_at)?,
+    );
+    Ok((ContentType::Calendar, calendar.to_string()))
 }
 
 #[post("/course/<id>", data = "<stage>")]
-fn add_stage(state: &State<Config>, id: &str, stage: Json<Stage>) -> Json<Vec<Stage>> {
-    let mut courses = state.courses.lock().unwrap();
-    let stages = match courses.get_mut(id) {
-        Some(course) => {
-            course.add(stage.into_inner());
-            course.stages().collect()
+fn add_stage(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    stage: Json<NewStage>,
+) -> ApiResult<Json<Vec<Stage>>> {
+    state.check_course(&user, id)?;
+    let stage = Stage::try_from(stage.into_inner())?;
+    let course = state
+        .courses
+        .add_stage(id, stage)?
+        .ok_or_else(|| Error::not_found("course", id))?;
+    state.course_updated(&user, &course);
+    Ok(Json(course.stages().collect()))
+}
+
+#[post("/course/<id>/graph", data = "<graph>")]
+fn add_stage_graph(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    graph: Json<StageGraph>,
+) -> ApiResult<Json<Vec<PlannedStage>>> {
+    state.check_course(&user, id)?;
+    let mut plan = Ok(Vec::new());
+    let course = state
+        .courses
+        .modify(id, &mut |course| {
+            plan = course.add_graph(&graph);
+            plan.is_ok()
+        })?
+        .ok_or_else(|| Error::not_found("course", id))?;
+    let plan = plan?;
+    state.course_updated(&user, &course);
+    Ok(Json(plan))
+}
+
+#[patch("/course/<id>/stage/<stage_id>", data = "<update>")]
+fn update_stage(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    stage_id: u32,
+    update: Json<StageUpdate>,
+) -> ApiResult<Json<Vec<Stage>>> {
+    state.check_course(&user, id)?;
+    let mut updated = Ok(false);
+    let course = state
+        .courses
+        .modify(id, &mut |course| {
+            updated = course.update_stage(stage_id, &update);
+            updated == Ok(true)
+        })?
+        .ok_or_else(|| Error::not_found("course", id))?;
+    if !updated? {
+        return Err(Error::not_found("stage", stage_id));
+    }
+    state.course_updated(&user, &course);
+    Ok(Json(course.stages().collect()))
+}
+
+#[delete("/course/<id>/stage/<stage_id>")]
+fn delete_stage(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    stage_id: u32,
+) -> ApiResult<Json<Vec<Stage>>> {
+    state.check_course(&user, id)?;
+    let mut removed = false;
+    let course = state
+        .courses
+        .modify(id, &mut |course| {
+            removed = course.remove_stage(stage_id);
+            removed
+        })?
+        .ok_or_else(|| Error::not_found("course", id))?;
+    if !removed {
+        return Err(Error::not_found("stage", stage_id));
+    }
+    state.course_updated(&user, &course);
+    Ok(Json(course.stages().collect()))
+}
+
+#[patch("/course/<id>", data = "<details>")]
+fn rename_course(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    details: Json<CourseDetails>,
+) -> ApiResult<Json<CourseDetails>> {
+    state.check_course(&user, id)?;
+    let course = state
+        .courses
+        .modify(id, &mut |course| {
+            course.rename(details.name());
+            true
+        })?
+        .ok_or_else(|| Error::not_found("course", id))?;
+    state.course_updated(&user, &course);
+    Ok(Json(course.details().to_owned()))
+}
+
+#[delete("/course/<id>")]
+fn delete_course(state: &State<Config>, user: User, id: &str) -> ApiResult<Status> {
+    state.check_course(&user, id)?;
+    if !state.courses.delete(id)? {
+        return Err(Error::not_found("course", id));
+    }
+    state.meals.modify_all(user.id(), &mut |meal| {
+        let count = meal.courses().len();
+        meal.remove(id);
+        meal.courses().len() != count
+    })?;
+    state.publish(&user, ServerEvent::CourseDeleted { id: id.to_owned() });
+    Ok(Status::NoContent)
+}
+
+#[put("/course", data = "<details>")]
+fn add_course(
+    state: &State<Config>,
+    user: User,
+    details: Json<CourseDetails>,
+) -> ApiResult<Json<CourseDetails>> {
+    Ok(Json(state.create_course(&user, Course::new(&details))?))
+}
+
+/// Create a course from a Cooklang recipe, called `name` unless the recipe has a title.
+#[put("/course/cooklang?<name>", data = "<recipe>")]
+fn import_cooklang(
+    state: &State<Config>,
+    user: User,
+    name: Option<&str>,
+    recipe: &str,
+) -> ApiResult<Json<CourseDetails>> {
+    let course = common::cooklang::parse(name.unwrap_or("Untitled"), recipe)?;
+    Ok(Json(state.create_course(&user, course.validated()?)?))
+}
+
+/// Import every course written in the plain text format, or none if any is invalid.
+#[put("/course/text", data = "<text>")]
+fn import_text(
+    state: &State<Config>,
+    user: User,
+    text: &str,
+) -> ApiResult<Json<Vec<CourseDetails>>> {
+    let courses = common::text::parse(text)?;
+    Ok(Json(
+        courses
+            .into_iter()
+            .map(|course| state.create_course(&user, course))
+            .collect::<ApiResult<_>>()?,
+    ))
+}
+
+/// Propose a course from a saved recipe page or its schema.org JSON-LD, without saving it.
+#[post("/course/schema_org", data = "<page>")]
+fn propose_schema_org(_user: User, page: &str) -> ApiResult<Json<Course>> {
+    Ok(Json(common::schema_org::propose(page)?))
+}
+
+/// Save a whole course, such as a reviewed proposal, under a fresh id, once each of its
+/// stages has been checked as if added one at a time.
+#[put("/course/import", data = "<course>")]
+fn import_course(
+    state: &State<Config>,
+    user: User,
+    course: Json<Course>,
+) -> ApiResult<Json<CourseDetails>> {
+    Ok(Json(state.create_course(&user, course.validated()?)?))
+}
+
+#[get("/course/<id>/cooklang")]
+fn export_cooklang(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+) -> ApiResult<(ContentType, String)> {
+    Ok((
+        ContentType::Plain,
+        common::cooklang::write(&state.course(&user, id)?),
+    ))
+}
+
+#[get("/course/<id>/text")]
+fn export_text(state: &State<Config>, user: User, id: &str) -> ApiResult<(ContentType, String)> {
+    Ok((
+        ContentType::Plain,
+        common::text::write(&state.course(&user, id)?),
+    ))
+}
+
+/// Every course in the plain text format.
+#[get("/courses/text")]
+fn export_all_text(state: &State<Config>, user: User) -> ApiResult<(ContentType, String)> {
+    let courses = state
+        .courses
+        .list(user.id())?
+        .iter()
+        .map(|details| state.course(&user, details.id()))
+        .collect::<ApiResult<Vec<_>>>()?;
+    Ok((ContentType::Plain, common::text::write_all(&courses)))
+}
+
+/// Ingredients and anything else known about a course beyond its stages.
+#[get("/course/<id>/metadata")]
+fn course_metadata(state: &State<Config>, user: User, id: &str) -> ApiResult<Json<Metadata>> {
+    Ok(Json(state.course(&user, id)?.metadata().to_owned()))
+}
+
+/// Start cooking a course now, or so that it is served at `serve_at`.
+#[post("/course/<id>/session?<serve_at>&<tz>")]
+fn start_session(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    serve_at: Option<&str>,
+    tz: Option<&str>,
+) -> ApiResult<Json<SessionState>> {
+    let course = state.course(&user, id)?;
+    let now = Utc::now();
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let session = match serve_at {
+        Some(serve_at) => {
+            let serve_at = common::schedule::serve_time(serve_at, tz)?;
+            Session::new(&session_id, &course, serve_at.with_timezone(&Utc))?
         }
-        None => Vec::new(),
+        None => Session::starting(&session_id, &course, now)?,
     };
-    Json(stages)
+    let session_state = session.state(now);
+    state
+        .sessions()?
+        .insert(session_id, Owned::new(user.id(), session));
+    state.publish(
+        &user,
+        ServerEvent::SessionUpdated {
+            session: session_state.clone(),
+        },
+    );
+    Ok(Json(session_state))
 }
 
-#[put("/course", data = "<details>")]
-fn add_course(state: &State<Config>, details: Json<CourseDetails>) -> Json<CourseDetails> {
+#[get("/sessions")]
+fn get_sessions(state: &State<Config>, user: User) -> ApiResult<Json<Vec<SessionState>>> {
+    let now = Utc::now();
+    Ok(Json(
+        state
+            .sessions()?
+            .values()
+            .filter(|session| session.is_owned_by(&user))
+            .map(|session| session.item.state(now))
+            .collect(),
+    ))
+}
+
+#[get("/session/<id>")]
+fn session(state: &State<Config>, user: User, id: &str) -> ApiResult<Json<SessionState>> {
+    state
+        .sessions()?
+        .get(id)
+        .filter(|session| session.is_owned_by(&user))
+        .map(|session| Json(session.item.state(Utc::now())))
+        .ok_or_else(|| Error::not_found("session", id))
+}
+
+#[post("/session/<id>/pause")]
+fn pause_session(state: &State<Config>, user: User, id: &str) -> ApiResult<Json<SessionState>> {
+    state.session_action(&user, id, Session::pause)
+}
+
+#[post("/session/<id>/resume")]
+fn resume_session(state: &State<Config>, user: User, id: &str) -> ApiResult<Json<SessionState>> {
+    state.session_action(&user, id, Session::resume)
+}
+
+#[post("/session/<id>/skip")]
+fn skip_stage(state: &State<Config>, user: User, id: &str) -> ApiResult<Json<SessionState>> {
+    state.session_action(&user, id, Session::skip)
+}
+
+fn parse_duration(input: &str) -> ApiResult<std::time::Duration> {
+    humantime::parse_duration(input).map_err(|e| {
+        Error(ApiError::bad_request(&format!(
+            "invalid duration '{}': {}",
+            input, e
+        )))
+    })
+}
+
+/// Give the current stage longer, e.g. `?by=5m`.
+#[post("/session/<id>/extend?<by>")]
+fn extend_stage(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    by: &str,
+) -> ApiResult<Json<SessionState>> {
+    let by = parse_duration(by)?;
+    state.session_action(&user, id, |session, now| session.extend(now, by))
+}
+
+#[delete("/session/<id>")]
+fn end_session(state: &State<Config>, user: User, id: &str) -> ApiResult<Status> {
     {
-        let mut courses = state.courses.lock().unwrap();
-        let mut details = details.into_inner();
-        let id = uuid::Uuid::new_v4().to_string();
-        details.set_id(&id);
-        let course = Course::new(&details);
-        courses.insert(id, course);
-        Json(details)
+        let mut sessions = state.sessions()?;
+        if !sessions
+            .get(id)
+            .is_some_and(|session| session.is_owned_by(&user))
+        {
+            return Err(Error::not_found("session", id));
+        }
+        sessions.remove(id);
+    }
+    state.publish(&user, ServerEvent::SessionEnded { id: id.to_owned() });
+    Ok(Status::NoContent)
+}
+
+/// A Server-Sent Events stream of every `ServerEvent` from now on.
+#[get("/events")]
+fn event_stream(state: &State<Config>, user: User, shutdown: Shutdown) -> EventStream![] {
+    events::stream(state.events.subscribe(), user, shutdown)
+}
+
+#[get("/meals")]
+fn get_meals(state: &State<Config>, user: User) -> ApiResult<Json<Vec<MealDetails>>> {
+    let r = state
+        .meals
+        .list(user.id())?
+        .iter()
+        .map(|meal| meal.details().to_owned())
+        .collect();
+    Ok(Json(r))
+}
+
+#[get("/meal/<id>")]
+fn meal(state: &State<Config>, user: User, id: &str) -> ApiResult<Json<Vec<TimelineEntry>>> {
+    let meal = state.meal(&user, id)?;
+    let courses = state.meal_courses(&meal)?;
+    Ok(Json(meal.timeline(&courses)))
+}
+
+#[post("/meal/<id>", data = "<course_id>")]
+fn add_meal_course(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    course_id: Json<String>,
+) -> ApiResult<Json<Vec<TimelineEntry>>> {
+    state.course(&user, &course_id)?;
+    let meal = state.modify_meal(&user, id, |meal| meal.add(&course_id))?;
+    let courses = state.meal_courses(&meal)?;
+    Ok(Json(meal.timeline(&courses)))
+}
+
+/// Serve the meal at `serve_at`, interpreted as for a schedule, or clear its serving time
+/// without one.
+#[put("/meal/<id>/serve_at?<serve_at>&<tz>")]
+fn set_meal_serve_at(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    serve_at: Option<&str>,
+    tz: Option<&str>,
+) -> ApiResult<Json<Vec<TimelineEntry>>> {
+    let serve_at = serve_at
+        .map(|serve_at| common::schedule::serve_time(serve_at, tz))
+        .transpose()?
+        .map(|serve_at| serve_at.with_timezone(&Utc));
+    let meal = state.modify_meal(&user, id, |meal| meal.set_serve_at(serve_at))?;
+    let courses = state.meal_courses(&meal)?;
+    Ok(Json(meal.timeline(&courses)))
+}
+
+#[get("/meal/<id>/attention")]
+fn meal_attention(state: &State<Config>, user: User, id: &str) -> ApiResult<Json<AttentionPlan>> {
+    let courses = state.meal_courses(&state.meal(&user, id)?)?;
+    Ok(Json(common::attention::plan(&courses)))
+}
+
+#[post("/meal/<id>/plan", data = "<kitchen>")]
+fn plan_meal(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    kitchen: Json<Kitchen>,
+) -> ApiResult<Json<KitchenPlan>> {
+    let courses = state.meal_courses(&state.meal(&user, id)?)?;
+    Ok(Json(common::kitchen::plan(&kitchen, &courses)?))
+}
+
+#[put("/meal", data = "<details>")]
+fn add_meal(
+    state: &State<Config>,
+    user: User,
+    details: Json<MealDetails>,
+) -> ApiResult<Json<MealDetails>> {
+    let mut details = details.into_inner();
+    let id = uuid::Uuid::new_v4().to_string();
+    details.set_id(&id);
+    state
+        .meals
+        .insert(&id, Owned::new(user.id(), Meal::new(&details)))?;
+    Ok(Json(details))
+}
+
+/// Start a calendar feed, returning the token which names it.
+#[post("/calendar")]
+fn add_calendar(state: &State<Config>, user: User) -> ApiResult<Json<String>> {
+    let token = uuid::Uuid::new_v4().to_simple().to_string();
+    state
+        .calendars
+        .insert(&token, Owned::new(user.id(), Vec::new()))?;
+    Ok(Json(token))
+}
+
+#[get("/calendar/<token>/plans")]
+fn plans(state: &State<Config>, user: User, token: &str) -> ApiResult<Json<Vec<Plan>>> {
+    Ok(Json(state.plans(Some(&user), token)?))
+}
+
+fn add_plan(
+    state: &State<Config>,
+    user: &User,
+    token: &str,
+    planned: Planned,
+    serve_at: &str,
+    tz: Option<&str>,
+) -> ApiResult<Json<Plan>> {
+    let serve_at = common::schedule::serve_time(serve_at, tz)?.with_timezone(&Utc);
+    if serve_at <= Utc::now() {
+        return Err(Error(ApiError::bad_request(
+            "a plan must be served in the future",
+        )));
+    }
+    let plan = Plan::new(&uuid::Uuid::new_v4().to_string(), planned, serve_at);
+    state.modify_plans(user, token, |plans| {
+        plans.push(plan.clone());
+        true
+    })?;
+    Ok(Json(plan))
+}
+
+#[post("/calendar/<token>/course/<id>?<serve_at>&<tz>")]
+fn plan_course(
+    state: &State<Config>,
+    user: User,
+    token: &str,
+    id: &str,
+    serve_at: &str,
+    tz: Option<&str>,
+) -> ApiResult<Json<Plan>> {
+    state.course(&user, id)?;
+    add_plan(
+        state,
+        &user,
+        token,
+        Planned::Course(id.to_owned()),
+        serve_at,
+        tz,
+    )
+}
+
+#[post("/calendar/<token>/meal/<id>?<serve_at>&<tz>")]
+fn plan_meal_at(
+    state: &State<Config>,
+    user: User,
+    token: &str,
+    id: &str,
+    serve_at: &str,
+    tz: Option<&str>,
+) -> ApiResult<Json<Plan>> {
+    state.meal(&user, id)?;
+    add_plan(
+        state,
+        &user,
+        token,
+        Planned::Meal(id.to_owned()),
+        serve_at,
+        tz,
+    )
+}
+
+#[delete("/calendar/<token>/plan/<id>")]
+fn delete_plan(state: &State<Config>, user: User, token: &str, id: &str) -> ApiResult<Status> {
+    let mut found = false;
+    state.modify_plans(&user, token, |plans| {
+        let count = plans.len();
+        plans.retain(|plan| plan.id() != id);
+        found = plans.len() != count;
+        found
+    })?;
+    if !found {
+        return Err(Error::not_found("plan", id));
+    }
+    Ok(Status::NoContent)
+}
+
+/// The `<token>.ics` file name of a calendar feed.
+struct FeedFile<'r>(&'r str);
+
+impl<'r> FromParam<'r> for FeedFile<'r> {
+    type Error = &'r str;
+
+    fn from_param(param: &'r str) -> Result<Self, Self::Error> {
+        param.strip_suffix(".ics").map(FeedFile).ok_or(param)
+    }
+}
+
+/// Every current plan in a feed as an iCalendar file, for calendar apps to subscribe to.
+///
+/// It is built afresh on each request, so it follows changes to the planned courses.
+#[get("/calendar/<file>")]
+fn calendar_feed(
+    state: &State<Config>,
+    file: Result<FeedFile<'_>, &str>,
+) -> ApiResult<(ContentType, String)> {
+    let token = file.map_err(|file| Error::not_found("calendar", file))?.0;
+    let now = Utc::now();
+    let mut calendar = Calendar::new("Course Planner", now);
+    for plan in state.plans(None, token)? {
+        if !plan.is_current(now) {
+            continue;
+        }
+        // Plans for courses or meals which have since been deleted are left out.
+        let added = match plan.planned() {
+            Planned::Course(id) => {
+                let courses: Vec<Course> = state.courses.get(id)?.into_iter().collect();
+                calendar.add_plan(&plan, None, &courses)
+            }
+            Planned::Meal(id) => match state.meals.get(id)?.map(|meal| meal.item) {
+                Some(meal) => {
+                    let courses = state.meal_courses(&meal)?;
+                    calendar.add_plan(&plan, Some(meal.details().name()), &courses)
+                }
+                None => Ok(()),
+            },
+        };
+        // One plan which can no longer be scheduled, e.g. after its course grew too long,
+        // must not stop calendar apps seeing the rest.
+        if let Err(e) = added {
+            warn!(
+                "Leaving plan {} out of calendar {}: {}",
+                plan.id(),
+                token,
+                e
+            );
+        }
+    }
+    Ok((ContentType::Calendar, calendar.to_string()))
+}
+
+/// The shortest a share may last, so it cannot have expired before it is sent on.
+const MIN_SHARE_LIFETIME: chrono::Duration = chrono::Duration::minutes(1);
+
+/// Share a course or meal read-only, optionally only for a while, e.g. `?expires_in=2d`.
+fn add_share(
+    state: &State<Config>,
+    user: &User,
+    shared: Planned,
+    expires_in: Option<&str>,
+) -> ApiResult<Json<Share>> {
+    let expires_at = expires_in
+        .map(|expires_in| {
+            let lasts = chrono::Duration::from_std(parse_duration(expires_in)?)
+                .map_err(|_| Error(ApiError::bad_request("a share cannot last that long")))?;
+            if lasts < MIN_SHARE_LIFETIME {
+                return Err(Error(ApiError::new(
+                    422,
+                    "a share must last at least a minute",
+                )));
+            }
+            Utc::now()
+                .checked_add_signed(lasts)
+                .ok_or_else(|| Error(ApiError::bad_request("a share cannot last that long")))
+        })
+        .transpose()?;
+    // As unguessable as the 122 random bits of a v4 uuid.
+    let token = uuid::Uuid::new_v4().to_simple().to_string();
+    let share = Share::new(&token, shared, expires_at);
+    state
+        .shares
+        .insert(&token, Owned::new(user.id(), share.clone()))?;
+    Ok(Json(share))
+}
+
+#[post("/course/<id>/share?<expires_in>")]
+fn share_course(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    expires_in: Option<&str>,
+) -> ApiResult<Json<Share>> {
+    state.check_course(&user, id)?;
+    add_share(state, &user, Planned::Course(id.to_owned()), expires_in)
+}
+
+#[post("/meal/<id>/share?<expires_in>")]
+fn share_meal(
+    state: &State<Config>,
+    user: User,
+    id: &str,
+    expires_in: Option<&str>,
+) -> ApiResult<Json<Share>> {
+    state.meal(&user, id)?;
+    add_share(state, &user, Planned::Meal(id.to_owned()), expires_in)
+}
+
+/// Every share link the user has made, including expired ones until they are revoked.
+#[get("/shares")]
+fn get_shares(state: &State<Config>, user: User) -> ApiResult<Json<Vec<Share>>> {
+    Ok(Json(state.shares.list(user.id())?))
+}
+
+#[delete("/share/<token>")]
+fn revoke_share(state: &State<Config>, user: User, token: &str) -> ApiResult<Status> {
+    let owned = state
+        .shares
+        .get(token)?
+        .is_some_and(|share| share.is_owned_by(&user));
+    // Shares never change hands, so one owned here is still the user's to delete.
+    if !owned || !state.shares.delete(token)? {
+        return Err(Error::not_found("share", token));
     }
+    Ok(Status::NoContent)
+}
+
+/// What a share link shows, for anyone with its token and no account needed.
+///
+/// Revoked and expired links, and links to what has since been deleted, are all missing.
+#[get("/shared/<token>")]
+fn shared(state: &State<Config>, token: &str) -> ApiResult<Json<SharedView>> {
+    let missing = || Error::not_found("share", token);
+    let share = state
+        .shares
+        .get(token)?
+        .filter(|share| share.item.is_current(Utc::now()))
+        .ok_or_else(missing)?;
+    let view = match share.item.shared() {
+        Planned::Course(id) => {
+            if state.courses.owner(id)?.as_deref() != Some(&share.owner) {
+                return Err(missing());
+            }
+            let course = state.courses.get(id)?.ok_or_else(missing)?;
+            let name = course.details().name().to_owned();
+            SharedView::new(&name, vec![course], None)
+        }
+        Planned::Meal(id) => {
+            let meal = state
+                .meals
+                .get(id)?
+                .filter(|meal| meal.owner == share.owner)
+                .map(|meal| meal.item)
+                .ok_or_else(missing)?;
+            SharedView::new(
+                meal.details().name(),
+                state.meal_courses(&meal)?,
+                meal.serve_at().copied(),
+            )
+        }
+    };
+    Ok(Json(view))
 }
 
 /*
@@ -110,33 +1054,6 @@ fn test(id: &str) -> Option<Json<Vec<Stage>>> {
 }
 */
 
-#[options("/<_path..>")]
-fn options(_path: PathBuf) -> String {
-    "Ok".to_string()
-}
-
-struct Cors;
-
-#[rocket::async_trait]
-impl Fairing for Cors {
-    fn info(&self) -> Info {
-        Info {
-            name: "Attaching CORS headers to responses",
-            kind: Kind::Response,
-        }
-    }
-
-    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
-        response.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "POST, GET, PATCH, OPTIONS, PUT",
-        ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
-    }
-}
-
 struct NoCache;
 
 #[rocket::async_trait]
@@ -157,29 +1074,269 @@ impl Fairing for NoCache {
     }
 }
 
+fn path(figment: &rocket::figment::Figment, key: &str) -> PathBuf {
+    figment
+        .extract_inner(key)
+        .unwrap_or_else(|e| panic!("{} must be a path: {}", key, e))
+}
+
+fn storage(figment: &rocket::figment::Figment) -> String {
+    figment
+        .extract_inner("storage")
+        .expect("storage must be a string")
+}
+
+/// Pick where courses and accounts are kept from the `storage` setting: `memory`, `file`
+/// or `sqlite`.
+fn repository(
+    figment: &rocket::figment::Figment,
+) -> (Box<dyn CourseRepository>, Box<dyn UserRepository>) {
+    match storage(figment).as_str() {
+        "memory" => (
+            Box::new(MemoryRepository::new()),
+            Box::new(MemoryUsers::new()),
+        ),
+        "file" => (
+            Box::new(
+                MemoryRepository::with_store(store::FileStore::new(&path(figment, "courses_path")))
+                    .expect("cannot load courses"),
+            ),
+            Box::new(
+                MemoryUsers::with_file(&path(figment, "users_path"))
+                    .expect("cannot load user accounts"),
+            ),
+        ),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => {
+            // Each gets its own connection to the one database.
+            let open = || {
+                sqlite::SqliteRepository::open(&path(figment, "database_path"))
+                    .expect("cannot open course database")
+            };
+            (Box::new(open()), Box::new(open()))
+        }
+        other => panic!("unsupported storage '{}'", other),
+    }
+}
+
+/// Pick where records such as calendar feeds are kept, alongside the courses: in the
+/// file at the `<table>_path` setting, or the database's `table`.
+fn records<T>(
+    figment: &rocket::figment::Figment,
+    table: &'static str,
+    what: &'static str,
+) -> Box<dyn RecordRepository<T>>
+where
+    T: Clone + Serialize + DeserializeOwned + Send + 'static,
+{
+    match storage(figment).as_str() {
+        "memory" => Box::new(MemoryRecords::new(what)),
+        "file" => Box::new(
+            MemoryRecords::with_file(what, &path(figment, &format!("{}_path", table)))
+                .unwrap_or_else(|e| panic!("cannot load {}s: {}", what, e)),
+        ),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Box::new(
+            sqlite::SqliteRecords::open(&path(figment, "database_path"), table, what)
+                .unwrap_or_else(|e| panic!("cannot load {}s: {}", what, e)),
+        ),
+        other => panic!("unsupported storage '{}'", other),
+    }
+}
+
+/// Give the courses stored before there were accounts to the account named by the
+/// `adopt_courses` setting, if there is one. This is a step for whoever runs the server,
+/// once that account has been registered.
+fn adopt_courses(
+    figment: &rocket::figment::Figment,
+    courses: &dyn CourseRepository,
+    users: &dyn UserRepository,
+) {
+    let name: String = match figment.extract_inner("adopt_courses") {
+        Ok(name) => name,
+        Err(_) => return,
+    };
+    let account = users
+        .by_name(&name)
+        .expect("cannot read user accounts")
+        .unwrap_or_else(|| panic!("adopt_courses names no account: '{}'", name));
+    let adopted = courses
+        .adopt(account.id())
+        .expect("cannot give courses to their new owner");
+    info!("{} adopted {} courses", name, adopted);
+}
+
 #[launch]
 fn rocket() -> _ {
-    let config = Config::default();
-
     let figment = rocket::Config::figment()
+        .join(("storage", "file"))
+        .join(("courses_path", "courses.json"))
+        .join(("users_path", "users.json"))
+        .join(("meals_path", "meals.json"))
+        .join(("calendars_path", "calendars.json"))
+        .join(("shares_path", "shares.json"))
+        .join(("cors", cors::Cors::default()))
+        .join(("database_path", "courses.db"))
         .merge(("port", 1111))
-        .merge(("limits", Limits::new().limit("json", 2.mebibytes())))
+        .merge(("limits", Limits::new()
+                .limit("json", 2.mebibytes())
+                .limit("string", 8.mebibytes())))
         //.merge(("tls.certs", "backend/certs.pem"))
         //.merge(("tls.key", "backend/key.pem"))
         ;
+    let (courses, users) = repository(&figment);
+    adopt_courses(&figment, &*courses, &*users);
+    let config = Config::new(
+        courses,
+        users,
+        records(&figment, "meals", "meal"),
+        records(&figment, "calendars", "calendar"),
+        records(&figment, "shares", "share"),
+    );
+    let cors: cors::Cors = figment
+        .extract_inner("cors")
+        .unwrap_or_else(|e| panic!("cors must be a table of allowed origins: {}", e));
+
     rocket::custom(figment)
-        .attach(Cors)
+        .attach(cors)
         .attach(NoCache)
+        .attach(events::ticker())
         .mount(
             "/",
             routes![
                 hello, //test,
+                register,
+                login,
+                logout,
+                current_user,
+                add_api_key,
+                get_api_keys,
+                revoke_api_key,
                 get_courses,
                 course,
+                schedule,
+                schedule_ics,
                 add_stage,
+                add_stage_graph,
                 add_course,
-                options,
+                import_cooklang,
+                export_cooklang,
+                import_text,
+                export_text,
+                export_all_text,
+                propose_schema_org,
+                import_course,
+                course_metadata,
+                rename_course,
+                delete_course,
+                update_stage,
+                delete_stage,
+                get_meals,
+                meal,
+                add_meal_course,
+                set_meal_serve_at,
+                meal_attention,
+                plan_meal,
+                add_meal,
+                start_session,
+                get_sessions,
+                session,
+                pause_session,
+                resume_session,
+                skip_stage,
+                extend_stage,
+                end_session,
+                event_stream,
+                add_calendar,
+                plans,
+                plan_course,
+                plan_meal_at,
+                delete_plan,
+                calendar_feed,
+                share_course,
+                share_meal,
+                get_shares,
+                revoke_share,
+                shared,
             ],
         )
+        .register("/", catchers![error::default_catcher])
         .manage(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rocket::local::blocking::Client;
+
+    /// A client signed in as a new user, of a server with only `routes` and everything
+    /// kept in memory.
+    fn client(routes: Vec<rocket::Route>) -> Client {
+        let config = Config::new(
+            Box::new(MemoryRepository::new()),
+            Box::new(MemoryUsers::new()),
+            Box::new(MemoryRecords::new("meal")),
+            Box::new(MemoryRecords::new("calendar")),
+            Box::new(MemoryRecords::new("share")),
+        );
+        let rocket = rocket::build()
+            .manage(config)
+            .mount("/", routes![register])
+            .mount("/", routes);
+        let client = Client::tracked(rocket).unwrap();
+        let status = client
+            .post("/user")
+            .json(&Credentials::new("ann", "correct horse"))
+            .dispatch()
+            .status();
+        assert_eq!(status, Status::Ok);
+        client
+    }
+
+    #[test]
+    fn kitchens_may_leave_out_fields() {
+        let client = client(routes![add_meal, plan_meal]);
+        let meal: MealDetails = client
+            .put("/meal")
+            .json(&MealDetails::new("", "Dinner"))
+            .dispatch()
+            .into_json()
+            .unwrap();
+
+        for kitchen in ["{}", r#"{"ovens": 2}"#] {
+            let planned = client
+                .post(format!("/meal/{}/plan", meal.id()))
+                .header(ContentType::JSON)
+                .body(kitchen)
+                .dispatch();
+            assert_eq!(planned.status(), Status::Ok, "{}", kitchen);
+        }
+    }
+
+    #[test]
+    fn shares_last_at_least_a_minute() {
+        let client = client(routes![add_meal, share_meal]);
+        let meal: MealDetails = client
+            .put("/meal")
+            .json(&MealDetails::new("", "Dinner"))
+            .dispatch()
+            .into_json()
+            .unwrap();
+
+        for (expires_in, status) in [
+            ("0s", Status::UnprocessableEntity),
+            ("59s", Status::UnprocessableEntity),
+            ("1min", Status::Ok),
+        ] {
+            let shared = client
+                .post(format!(
+                    "/meal/{}/share?expires_in={}",
+                    meal.id(),
+                    expires_in
+                ))
+                .dispatch();
+            assert_eq!(shared.status(), status, "{}", expires_in);
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use common::{Course, CourseDetails, Stage};
+
+use crate::store::FileStore;
+use crate::users::Owned;
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// A thread panicked while holding the store, so it may be half updated.
+    Poisoned,
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    /// The error happened while storing what is named, e.g. `"meal"`.
+    Storing(&'static str, Box<RepositoryError>),
+}
+
+impl RepositoryError {
+    /// Record that this happened while storing `what`, unless that is already known.
+    pub fn storing(self, what: &'static str) -> Self {
+        match self {
+            RepositoryError::Storing(..) => self,
+            e => RepositoryError::Storing(what, Box::new(e)),
+        }
+    }
+
+    /// What was being stored, if that is known.
+    pub fn what(&self) -> Option<&'static str> {
+        match self {
+            RepositoryError::Storing(what, _) => Some(what),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryError::Poisoned => write!(f, "storage lock poisoned"),
+            RepositoryError::Io(e) => write!(f, "storage I/O failed: {}", e),
+            RepositoryError::Json(e) => write!(f, "storage JSON invalid: {}", e),
+            #[cfg(feature = "sqlite")]
+            RepositoryError::Sqlite(e) => write!(f, "database failed: {}", e),
+            RepositoryError::Storing(what, e) => write!(f, "{} {}", what, e),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<std::io::Error> for RepositoryError {
+    fn from(e: std::io::Error) -> Self {
+        RepositoryError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RepositoryError {
+    fn from(e: serde_json::Error) -> Self {
+        RepositoryError::Json(e)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for RepositoryError {
+    fn from(e: rusqlite::Error) -> Self {
+        RepositoryError::Sqlite(e)
+    }
+}
+
+/// Where courses live. Every method is atomic with respect to the others.
+///
+/// Each course belongs to a user, whose id is its `owner`; checking that a user may see
+/// or change a course is up to the caller.
+pub trait CourseRepository: Send + Sync {
+    /// The courses belonging to `owner`.
+    fn list(&self, owner: &str) -> Result<Vec<CourseDetails>, RepositoryError>;
+
+    fn get(&self, id: &str) -> Result<Option<Course>, RepositoryError>;
+
+    /// The id of the user a course belongs to, or `None` if there is no such course.
+    fn owner(&self, id: &str) -> Result<Option<String>, RepositoryError>;
+
+    /// Store a new course for `owner` under the id in its details.
+    fn insert(&self, owner: &str, course: Course) -> Result<(), RepositoryError>;
+
+    /// Give every course without an owner, saved before there were accounts, to `owner`.
+    ///
+    /// Returns how many courses were adopted.
+    fn adopt(&self, owner: &str) -> Result<usize, RepositoryError>;
+
+    /// Replace an existing course, returning `false` if there is none with its id.
+    #[allow(dead_code)] // Edits go through `modify`, so nothing else sees a stale course.
+    fn update(&self, course: Course) -> Result<bool, RepositoryError>;
+
+    /// Remove a course, returning `false` if there was none with that id.
+    fn delete(&self, id: &str) -> Result<bool, RepositoryError>;
+
+    /// Add all of `stages` to a course, or none of them, returning the updated course.
+    fn add_stages(&self, id: &str, stages: Vec<Stage>) -> Result<Option<Course>, RepositoryError>;
+
+    fn add_stage(&self, id: &str, stage: Stage) -> Result<Option<Course>, RepositoryError> {
+        self.add_stages(id, vec![stage])
+    }
+
+    /// Change a course in place, saving it only if `change` returns `true`.
+    ///
+    /// Returns the course as it now is, or `None` if there is no course with that id.
+    fn modify(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Course) -> bool,
+    ) -> Result<Option<Course>, RepositoryError>;
+}
+
+/// Courses held in memory, optionally saved to a file after every change.
+#[derive(Debug, Default)]
+pub struct MemoryRepository {
+    courses: Mutex<HashMap<String, Owned<Course>>>,
+    store: Option<FileStore>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Courses kept by `store`, failing if they are there but cannot be read.
+    pub fn with_store(store: FileStore) -> Result<Self, RepositoryError> {
+        Ok(Self {
+            courses: Mutex::new(
+                store
+                    .load()
+                    .map_err(|e| RepositoryError::from(e).storing("course"))?,
+            ),
+            store: Some(store),
+        })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, Owned<Course>>>, RepositoryError> {
+        self.courses
+            .lock()
+            .map_err(|_| RepositoryError::Poisoned.storing("course"))
+    }
+
+    /// Persist the courses; called while still holding the lock, so saves cannot interleave.
+    fn save(&self, courses: &HashMap<String, Owned<Course>>) -> Result<(), RepositoryError> {
+        match &self.store {
+            Some(store) => store
+                .save(courses)
+                .map_err(|e| RepositoryError::from(e).storing("course")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl CourseRepository for MemoryRepository {
+    fn list(&self, owner: &str) -> Result<Vec<CourseDetails>, RepositoryError> {
+        Ok(self
+            .lock()?
+            .values()
+            .filter(|v| v.owner == owner)
+            .map(|v| v.item.details().to_owned())
+            .collect())
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Course>, RepositoryError> {
+        Ok(self.lock()?.get(id).map(|v| v.item.clone()))
+    }
+
+    fn owner(&self, id: &str) -> Result<Option<String>, RepositoryError> {
+        Ok(self.lock()?.get(id).map(|v| v.owner.clone()))
+    }
+
+    fn insert(&self, owner: &str, course: Course) -> Result<(), RepositoryError> {
+        let mut courses = self.lock()?;
+        courses.insert(course.details().id().to_owned(), Owned::new(owner, course));
+        self.save(&courses)
+    }
+
+    fn adopt(&self, owner: &str) -> Result<usize, RepositoryError> {
+        let mut courses = self.lock()?;
+        let mut adopted = 0;
+        for course in courses.values_mut().filter(|v| v.owner.is_empty()) {
+            course.owner = owner.to_owned();
+            adopted += 1;
+        }
+        if adopted > 0 {
+            self.save(&courses)?;
+        }
+        Ok(adopted)
+    }
+
+    fn update(&self, course: Course) -> Result<bool, RepositoryError> {
+        let mut courses = self.lock()?;
+        match courses.get_mut(course.details().id()) {
+            Some(existing) => existing.item = course,
+            None => return Ok(false),
+        }
+        self.save(&courses)?;
+        Ok(true)
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, RepositoryError> {
+        let mut courses = self.lock()?;
+        if courses.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.save(&courses)?;
+        Ok(true)
+    }
+
+    fn add_stages(&self, id: &str, stages: Vec<Stage>) -> Result<Option<Course>, RepositoryError> {
+        let mut courses = self.lock()?;
+        let course = match courses.get_mut(id) {
+            Some(course) => &mut course.item,
+            None => return Ok(None),
+        };
+        for stage in stages {
+            course.add(stage);
+        }
+        let course = course.clone();
+        self.save(&courses)?;
+        Ok(Some(course))
+    }
+
+    fn modify(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Course) -> bool,
+    ) -> Result<Option<Course>, RepositoryError> {
+        let mut courses = self.lock()?;
+        let course = match courses.get_mut(id) {
+            Some(course) => &mut course.item,
+            None => return Ok(None),
+        };
+        if !change(course) {
+            return Ok(Some(course.clone()));
+        }
+        let course = course.clone();
+        self.save(&courses)?;
+        Ok(Some(course))
+    }
+}
+
+/// Where one kind of record belonging to a user lives, such as meals or calendar feeds,
+/// each under an id or token. Every method is atomic with respect to the others.
+pub trait RecordRepository<T>: Send + Sync {
+    /// The records belonging to `owner`.
+    fn list(&self, owner: &str) -> Result<Vec<T>, RepositoryError>;
+
+    fn get(&self, id: &str) -> Result<Option<Owned<T>>, RepositoryError>;
+
+    /// Store a new record under `id`.
+    fn insert(&self, id: &str, record: Owned<T>) -> Result<(), RepositoryError>;
+
+    /// Change a record in place, saving it only if `change` returns `true`.
+    ///
+    /// Returns the record as it now is, or `None` if there is none with that id.
+    fn modify(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Owned<T>) -> bool,
+    ) -> Result<Option<Owned<T>>, RepositoryError>;
+
+    /// Change every record belonging to `owner`, saving those for which `change` returns
+    /// `true`.
+    fn modify_all(
+        &self,
+        owner: &str,
+        change: &mut dyn FnMut(&mut T) -> bool,
+    ) -> Result<(), RepositoryError>;
+
+    /// Remove a record, returning `false` if there was none with that id.
+    fn delete(&self, id: &str) -> Result<bool, RepositoryError>;
+}
+
+/// Records held in memory, optionally saved to a JSON file after every change.
+#[derive(Debug)]
+pub struct MemoryRecords<T> {
+    records: Mutex<HashMap<String, Owned<T>>>,
+    path: Option<PathBuf>,
+    /// What the records are, for errors.
+    what: &'static str,
+}
+
+impl<T> MemoryRecords<T> {
+    pub fn new(what: &'static str) -> Self {
+        Self {
+            records: Mutex::default(),
+            path: None,
+            what,
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, Owned<T>>>, RepositoryError> {
+        self.records
+            .lock()
+            .map_err(|_| RepositoryError::Poisoned.storing(self.what))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> MemoryRecords<T> {
+    /// Records kept in `path`; a missing file is no records yet.
+    pub fn with_file(what: &'static str, path: &Path) -> Result<Self, RepositoryError> {
+        let records: HashMap<String, Owned<T>> = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(RepositoryError::from),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+        .map_err(|e| e.storing(what))?;
+        info!("Loaded {} {}s from {:?}", records.len(), what, path);
+        Ok(Self {
+            records: Mutex::new(records),
+            path: Some(path.to_owned()),
+            what,
+        })
+    }
+
+    /// Persist the records; called while still holding the lock, so saves cannot interleave.
+    fn save(&self, records: &HashMap<String, Owned<T>>) -> Result<(), RepositoryError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        serde_json::to_string_pretty(records)
+            .map_err(RepositoryError::from)
+            .and_then(|json| Ok(crate::store::replace(path, json.as_bytes())?))
+            .map_err(|e| e.storing(self.what))
+    }
+}
+
+impl<T> RecordRepository<T> for MemoryRecords<T>
+where
+    T: Clone + Serialize + DeserializeOwned + Send,
+{
+    fn list(&self, owner: &str) -> Result<Vec<T>, RepositoryError> {
+        Ok(self
+            .lock()?
+            .values()
+            .filter(|record| record.owner == owner)
+            .map(|record| record.item.clone())
+            .collect())
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Owned<T>>, RepositoryError> {
+        Ok(self.lock()?.get(id).cloned())
+    }
+
+    fn insert(&self, id: &str, record: Owned<T>) -> Result<(), RepositoryError> {
+        let mut records = self.lock()?;
+        records.insert(id.to_owned(), record);
+        self.save(&records)
+    }
+
+    fn modify(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Owned<T>) -> bool,
+    ) -> Result<Option<Owned<T>>, RepositoryError> {
+        let mut records = self.lock()?;
+        let record = match records.get_mut(id) {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        if !change(record) {
+            return Ok(Some(record.clone()));
+        }
+        let record = record.clone();
+        self.save(&records)?;
+        Ok(Some(record))
+    }
+
+    fn modify_all(
+        &self,
+        owner: &str,
+        change: &mut dyn FnMut(&mut T) -> bool,
+    ) -> Result<(), RepositoryError> {
+        let mut records = self.lock()?;
+        let mut changed = false;
+        for record in records.values_mut().filter(|record| record.owner == owner) {
+            changed |= change(&mut record.item);
+        }
+        if changed {
+            self.save(&records)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, RepositoryError> {
+        let mut records = self.lock()?;
+        if records.remove(id).is_none() {
+            return Ok(false);
+        }
+        self.save(&records)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_repository_crud() {
+        let repository = MemoryRepository::new();
+        let details = CourseDetails::new("0", "Toast");
+        repository.insert("ann", Course::new(&details)).unwrap();
+
+        let course = repository
+            .add_stage("0", Stage::new("Bread in toaster", "1min"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(course.raw_stages().len(), 2);
+        assert_eq!(repository.list("ann").unwrap(), vec![details]);
+        assert_eq!(repository.list("bob").unwrap(), vec![]);
+        assert_eq!(repository.owner("0").unwrap().as_deref(), Some("ann"));
+
+        let course = repository
+            .modify("0", &mut |course| course.remove_stage(2))
+            .unwrap()
+            .unwrap();
+        assert_eq!(course.raw_stages().len(), 1);
+        assert_eq!(repository.modify("1", &mut |_| true).unwrap(), None);
+
+        assert!(repository.delete("0").unwrap());
+        assert!(!repository.delete("0").unwrap());
+        assert_eq!(repository.get("0").unwrap(), None);
+    }
+
+    #[test]
+    fn unowned_courses_are_adopted() {
+        let repository = MemoryRepository::new();
+        repository
+            .insert("", Course::new(&CourseDetails::new("0", "Toast")))
+            .unwrap();
+        repository
+            .insert("bob", Course::new(&CourseDetails::new("1", "Tea")))
+            .unwrap();
+
+        assert_eq!(repository.adopt("ann").unwrap(), 1);
+        assert_eq!(repository.owner("0").unwrap().as_deref(), Some("ann"));
+        assert_eq!(repository.owner("1").unwrap().as_deref(), Some("bob"));
+        assert_eq!(repository.adopt("ann").unwrap(), 0);
+    }
+
+    #[test]
+    fn errors_name_what_was_being_stored() {
+        let error = RepositoryError::Poisoned.storing("meal").storing("course");
+        assert_eq!(error.what(), Some("meal"));
+        assert_eq!(error.to_string(), "meal storage lock poisoned");
+        assert_eq!(RepositoryError::Poisoned.what(), None);
+    }
+
+    #[test]
+    fn records_are_saved_and_reloaded() {
+        let path = std::env::temp_dir().join(format!("records-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let records = MemoryRecords::with_file("calendar", &path).unwrap();
+        records.insert("t", Owned::new("ann", vec![1])).unwrap();
+
+        let record = records
+            .modify("t", &mut |record| {
+                record.item.push(2);
+                true
+            })
+            .unwrap();
+        assert_eq!(record, Some(Owned::new("ann", vec![1, 2])));
+        assert_eq!(records.modify("u", &mut |_| true).unwrap(), None);
+
+        let reloaded = MemoryRecords::<Vec<u32>>::with_file("calendar", &path).unwrap();
+        assert_eq!(reloaded.get("t").unwrap(), record);
+        assert_eq!(reloaded.get("u").unwrap(), None);
+
+        reloaded.insert("u", Owned::new("bob", vec![3])).unwrap();
+        reloaded
+            .modify_all("ann", &mut |item| {
+                item.clear();
+                true
+            })
+            .unwrap();
+        assert_eq!(reloaded.list("ann").unwrap(), vec![Vec::<u32>::new()]);
+        assert_eq!(reloaded.list("bob").unwrap(), vec![vec![3]]);
+        assert!(reloaded.delete("u").unwrap());
+        assert!(!reloaded.delete("u").unwrap());
+        fs::remove_file(path).unwrap();
+    }
+}
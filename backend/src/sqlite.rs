@@ -0,0 +1,613 @@
+use rusqlite::{params, Connection, OptionalExtension};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Mutex;
+
+use common::{Course, CourseDetails, Stage};
+
+use crate::repository::{CourseRepository, RecordRepository, RepositoryError};
+use crate::users::{Account, Owned, UserRepository};
+
+/// Schema changes, applied in order; `PRAGMA user_version` records how many have run.
+/// Only ever append to this list.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE courses (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE stages (
+        course_id TEXT NOT NULL REFERENCES courses(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        stage TEXT NOT NULL,
+        PRIMARY KEY (course_id, position)
+    );
+",
+    "
+    ALTER TABLE courses ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
+",
+    "
+    ALTER TABLE courses ADD COLUMN owner TEXT NOT NULL DEFAULT '';
+    CREATE INDEX courses_owner ON courses(owner);
+    CREATE TABLE users (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT UNIQUE NOT NULL,
+        account TEXT NOT NULL
+    );
+",
+    "
+    CREATE TABLE calendars (
+        id TEXT PRIMARY KEY NOT NULL,
+        owner TEXT NOT NULL,
+        item TEXT NOT NULL
+    );
+",
+    "
+    CREATE TABLE meals (
+        id TEXT PRIMARY KEY NOT NULL,
+        owner TEXT NOT NULL,
+        item TEXT NOT NULL
+    );
+    CREATE INDEX meals_owner ON meals(owner);
+    CREATE TABLE shares (
+        id TEXT PRIMARY KEY NOT NULL,
+        owner TEXT NOT NULL,
+        item TEXT NOT NULL
+    );
+    CREATE INDEX shares_owner ON shares(owner);
+",
+];
+
+/// Courses and accounts in an embedded SQLite database, each stage, course's metadata and
+/// account stored as JSON.
+pub struct SqliteRepository {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteRepository {
+    pub fn open(path: &Path) -> Result<Self, RepositoryError> {
+        let mut connection = Connection::open(path)?;
+        // WAL lets readers in other processes carry on while one writes; the busy
+        // timeout makes competing writers wait for each other rather than fail.
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        connection.busy_timeout(std::time::Duration::from_secs(5))?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Run `f` on the connection, saying that it was storing `what` if it fails.
+    fn with<T>(
+        &self,
+        what: &'static str,
+        f: impl FnOnce(&mut Connection) -> Result<T, RepositoryError>,
+    ) -> Result<T, RepositoryError> {
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|_| RepositoryError::Poisoned.storing(what))?;
+        f(&mut connection).map_err(|e| e.storing(what))
+    }
+
+    /// The account matching `condition`, where `?1` is `value`.
+    fn account(&self, condition: &str, value: &str) -> Result<Option<Account>, RepositoryError> {
+        self.with("account", |connection| {
+            let account: Option<String> = connection
+                .query_row(
+                    &format!("SELECT account FROM users WHERE {}", condition),
+                    [value],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(account
+                .map(|account| serde_json::from_str(&account))
+                .transpose()?)
+        })
+    }
+}
+
+fn migrate(connection: &mut Connection) -> Result<(), RepositoryError> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (applied, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        log::info!("Applying database migration {}", applied + 1);
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", applied + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn insert_stages(
+    connection: &Connection,
+    id: &str,
+    first: usize,
+    stages: &[Stage],
+) -> Result<(), RepositoryError> {
+    let mut statement = connection
+        .prepare("INSERT INTO stages (course_id, position, stage) VALUES (?1, ?2, ?3)")?;
+    for (position, stage) in (first..).zip(stages) {
+        statement.execute(params![id, position, serde_json::to_string(stage)?])?;
+    }
+    Ok(())
+}
+
+/// Overwrite a stored course, returning `false` if there is none with its id.
+fn store(connection: &Connection, course: &Course) -> Result<bool, RepositoryError> {
+    let details = course.details();
+    let updated = connection.execute(
+        "UPDATE courses SET name = ?2, metadata = ?3 WHERE id = ?1",
+        params![
+            details.id(),
+            details.name(),
+            serde_json::to_string(course.metadata())?
+        ],
+    )?;
+    if updated == 0 {
+        return Ok(false);
+    }
+    connection.execute("DELETE FROM stages WHERE course_id = ?1", [details.id()])?;
+    insert_stages(connection, details.id(), 0, course.raw_stages())?;
+    Ok(true)
+}
+
+fn load(connection: &Connection, id: &str) -> Result<Option<Course>, RepositoryError> {
+    let row: Option<(String, String)> = connection
+        .query_row(
+            "SELECT name, metadata FROM courses WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (name, metadata) = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let mut statement =
+        connection.prepare("SELECT stage FROM stages WHERE course_id = ?1 ORDER BY position")?;
+    let stages = statement
+        .query_map([id], |row| row.get::<_, String>(0))?
+        .map(|stage| Ok(serde_json::from_str(&stage?)?))
+        .collect::<Result<Vec<Stage>, RepositoryError>>()?;
+    let mut course = Course::from_stages(&CourseDetails::new(id, &name), stages);
+    course.set_metadata(serde_json::from_str(&metadata)?);
+    Ok(Some(course))
+}
+
+impl CourseRepository for SqliteRepository {
+    fn list(&self, owner: &str) -> Result<Vec<CourseDetails>, RepositoryError> {
+        self.with("course", |connection| {
+            let mut statement = connection
+                .prepare("SELECT id, name FROM courses WHERE owner = ?1 ORDER BY name")?;
+            let courses = statement
+                .query_map([owner], |row| {
+                    Ok(CourseDetails::new(
+                        &row.get::<_, String>(0)?,
+                        &row.get::<_, String>(1)?,
+                    ))
+                })?
+                .collect::<Result<_, _>>()?;
+            Ok(courses)
+        })
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Course>, RepositoryError> {
+        self.with("course", |connection| load(connection, id))
+    }
+
+    fn owner(&self, id: &str) -> Result<Option<String>, RepositoryError> {
+        self.with("course", |connection| {
+            Ok(connection
+                .query_row("SELECT owner FROM courses WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })
+                .optional()?)
+        })
+    }
+
+    fn insert(&self, owner: &str, course: Course) -> Result<(), RepositoryError> {
+        self.with("course", |connection| {
+            let transaction = connection.transaction()?;
+            let details = course.details();
+            transaction.execute(
+                "INSERT INTO courses (id, name, metadata, owner) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    details.id(),
+                    details.name(),
+                    serde_json::to_string(course.metadata())?,
+                    owner
+                ],
+            )?;
+            insert_stages(&transaction, details.id(), 0, course.raw_stages())?;
+            Ok(transaction.commit()?)
+        })
+    }
+
+    fn adopt(&self, owner: &str) -> Result<usize, RepositoryError> {
+        self.with("course", |connection| {
+            Ok(connection.execute("UPDATE courses SET owner = ?1 WHERE owner = ''", [owner])?)
+        })
+    }
+
+    fn update(&self, course: Course) -> Result<bool, RepositoryError> {
+        self.with("course", |connection| {
+            let transaction = connection.transaction()?;
+            if !store(&transaction, &course)? {
+                return Ok(false);
+            }
+            transaction.commit()?;
+            Ok(true)
+        })
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, RepositoryError> {
+        self.with("course", |connection| {
+            Ok(connection.execute("DELETE FROM courses WHERE id = ?1", [id])? > 0)
+        })
+    }
+
+    fn add_stages(&self, id: &str, stages: Vec<Stage>) -> Result<Option<Course>, RepositoryError> {
+        self.with("course", |connection| {
+            let transaction = connection.transaction()?;
+            let exists = transaction
+                .query_row("SELECT 1 FROM courses WHERE id = ?1", [id], |_| Ok(()))
+                .optional()?
+                .is_some();
+            if !exists {
+                return Ok(None);
+            }
+            let next: usize = transaction.query_row(
+                "SELECT COALESCE(MAX(position) + 1, 0) FROM stages WHERE course_id = ?1",
+                [id],
+                |row| row.get(0),
+            )?;
+            insert_stages(&transaction, id, next, &stages)?;
+            let course = load(&transaction, id)?;
+            transaction.commit()?;
+            Ok(course)
+        })
+    }
+
+    fn modify(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Course) -> bool,
+    ) -> Result<Option<Course>, RepositoryError> {
+        self.with("course", |connection| {
+            let transaction = connection.transaction()?;
+            let mut course = match load(&transaction, id)? {
+                Some(course) => course,
+                None => return Ok(None),
+            };
+            if change(&mut course) {
+                store(&transaction, &course)?;
+                transaction.commit()?;
+            }
+            Ok(Some(course))
+        })
+    }
+}
+
+impl UserRepository for SqliteRepository {
+    fn create(&self, account: Account) -> Result<bool, RepositoryError> {
+        self.with("account", |connection| {
+            let inserted = connection.execute(
+                "INSERT INTO users (id, name, account) VALUES (?1, ?2, ?3) ON CONFLICT DO NOTHING",
+                params![
+                    account.id(),
+                    account.name(),
+                    serde_json::to_string(&account)?
+                ],
+            )?;
+            Ok(inserted > 0)
+        })
+    }
+
+    fn by_id(&self, id: &str) -> Result<Option<Account>, RepositoryError> {
+        self.account("id = ?1", id)
+    }
+
+    fn by_name(&self, name: &str) -> Result<Option<Account>, RepositoryError> {
+        self.account("name = ?1", name)
+    }
+
+    fn update(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Account) -> bool,
+    ) -> Result<bool, RepositoryError> {
+        self.with("account", |connection| {
+            let transaction = connection.transaction()?;
+            let account: Option<String> = transaction
+                .query_row("SELECT account FROM users WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })
+                .optional()?;
+            let mut account: Account = match account {
+                Some(account) => serde_json::from_str(&account)?,
+                None => return Ok(false),
+            };
+            if !change(&mut account) {
+                return Ok(false);
+            }
+            transaction.execute(
+                "UPDATE users SET account = ?2 WHERE id = ?1",
+                params![id, serde_json::to_string(&account)?],
+            )?;
+            transaction.commit()?;
+            Ok(true)
+        })
+    }
+
+    fn by_key(&self, hash: &str) -> Result<Option<Account>, RepositoryError> {
+        self.account(
+            "EXISTS (SELECT 1 FROM json_each(account, '$.api_keys')
+                WHERE json_extract(value, '$.hash') = ?1)",
+            hash,
+        )
+    }
+}
+
+/// Records of one kind, e.g. meals, in a table of the same database with each
+/// record stored as JSON.
+pub struct SqliteRecords<T> {
+    repository: SqliteRepository,
+    table: &'static str,
+    /// What the records are, for errors.
+    what: &'static str,
+    records: PhantomData<fn() -> T>,
+}
+
+impl<T> SqliteRecords<T> {
+    pub fn open(
+        path: &Path,
+        table: &'static str,
+        what: &'static str,
+    ) -> Result<Self, RepositoryError> {
+        Ok(Self {
+            repository: SqliteRepository::open(path)?,
+            table,
+            what,
+            records: PhantomData,
+        })
+    }
+}
+
+fn load_record<T: DeserializeOwned>(
+    connection: &Connection,
+    table: &str,
+    id: &str,
+) -> Result<Option<Owned<T>>, RepositoryError> {
+    let row: Option<(String, String)> = connection
+        .query_row(
+            &format!("SELECT owner, item FROM {} WHERE id = ?1", table),
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    Ok(match row {
+        Some((owner, item)) => Some(Owned::new(&owner, serde_json::from_str(&item)?)),
+        None => None,
+    })
+}
+
+impl<T> RecordRepository<T> for SqliteRecords<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn list(&self, owner: &str) -> Result<Vec<T>, RepositoryError> {
+        self.repository.with(self.what, |connection| {
+            let mut statement = connection.prepare(&format!(
+                "SELECT item FROM {} WHERE owner = ?1 ORDER BY rowid",
+                self.table
+            ))?;
+            let items = statement
+                .query_map([owner], |row| row.get::<_, String>(0))?
+                .map(|item| Ok(serde_json::from_str(&item?)?))
+                .collect::<Result<_, RepositoryError>>()?;
+            Ok(items)
+        })
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Owned<T>>, RepositoryError> {
+        self.repository.with(self.what, |connection| {
+            load_record(connection, self.table, id)
+        })
+    }
+
+    fn insert(&self, id: &str, record: Owned<T>) -> Result<(), RepositoryError> {
+        self.repository.with(self.what, |connection| {
+            connection.execute(
+                &format!(
+                    "INSERT INTO {} (id, owner, item) VALUES (?1, ?2, ?3)",
+                    self.table
+                ),
+                params![id, record.owner, serde_json::to_string(&record.item)?],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn modify(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Owned<T>) -> bool,
+    ) -> Result<Option<Owned<T>>, RepositoryError> {
+        self.repository.with(self.what, |connection| {
+            let transaction = connection.transaction()?;
+            let mut record = match load_record(&transaction, self.table, id)? {
+                Some(record) => record,
+                None => return Ok(None),
+            };
+            if change(&mut record) {
+                transaction.execute(
+                    &format!("UPDATE {} SET item = ?2 WHERE id = ?1", self.table),
+                    params![id, serde_json::to_string(&record.item)?],
+                )?;
+                transaction.commit()?;
+            }
+            Ok(Some(record))
+        })
+    }
+
+    fn modify_all(
+        &self,
+        owner: &str,
+        change: &mut dyn FnMut(&mut T) -> bool,
+    ) -> Result<(), RepositoryError> {
+        self.repository.with(self.what, |connection| {
+            let transaction = connection.transaction()?;
+            let records = {
+                let mut statement = transaction.prepare(&format!(
+                    "SELECT id, item FROM {} WHERE owner = ?1",
+                    self.table
+                ))?;
+                let records = statement
+                    .query_map([owner], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                records
+            };
+            for (id, item) in records {
+                let mut item: T = serde_json::from_str(&item)?;
+                if change(&mut item) {
+                    transaction.execute(
+                        &format!("UPDATE {} SET item = ?2 WHERE id = ?1", self.table),
+                        params![id, serde_json::to_string(&item)?],
+                    )?;
+                }
+            }
+            Ok(transaction.commit()?)
+        })
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, RepositoryError> {
+        self.repository.with(self.what, |connection| {
+            Ok(connection.execute(&format!("DELETE FROM {} WHERE id = ?1", self.table), [id])? > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::users::{hash_key, ApiKey};
+    use chrono::Utc;
+    use common::{Credentials, Ingredient, Metadata, NewApiKey, Scope};
+
+    #[test]
+    fn sqlite_repository_crud() {
+        let repository = SqliteRepository::open(Path::new(":memory:")).unwrap();
+        let details = CourseDetails::new("0", "Toast");
+        let mut course = Course::new(&details);
+        course.set_metadata(Metadata::default().with_ingredient(Ingredient::new("bread")));
+        repository.insert("ann", course).unwrap();
+
+        let course = repository
+            .add_stage("0", Stage::new("Bread in toaster", "1min"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            course.stages().map(|s| s.to_string()).collect::<Vec<_>>(),
+            vec!["1m -- Bread in toaster"]
+        );
+        assert_eq!(repository.get("0").unwrap(), Some(course));
+        assert_eq!(repository.list("ann").unwrap(), vec![details]);
+        assert_eq!(repository.list("bob").unwrap(), vec![]);
+        assert_eq!(repository.owner("0").unwrap().as_deref(), Some("ann"));
+
+        let course = repository
+            .modify("0", &mut |course| {
+                course.rename("Hot buttered toast");
+                true
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(repository.get("0").unwrap(), Some(course));
+        assert_eq!(
+            repository
+                .add_stage("missing", Stage::new("Toast", "1min"))
+                .unwrap(),
+            None
+        );
+
+        assert!(repository.delete("0").unwrap());
+        assert_eq!(repository.get("0").unwrap(), None);
+    }
+
+    #[test]
+    fn sqlite_accounts_have_unique_names() {
+        let repository = SqliteRepository::open(Path::new(":memory:")).unwrap();
+        let ann = Account::new(&Credentials::new("ann", "correct horse")).unwrap();
+
+        assert!(repository.create(ann.clone()).unwrap());
+        assert!(!repository
+            .create(Account::new(&Credentials::new("ann", "other horse")).unwrap())
+            .unwrap());
+        assert_eq!(repository.by_name("ann").unwrap(), Some(ann.clone()));
+        assert_eq!(repository.by_id(ann.id()).unwrap(), Some(ann));
+    }
+
+    #[test]
+    fn sqlite_accounts_are_found_by_api_key() {
+        let repository = SqliteRepository::open(Path::new(":memory:")).unwrap();
+        let ann = Account::new(&Credentials::new("ann", "correct horse")).unwrap();
+        repository.create(ann.clone()).unwrap();
+        let (key, secret) = ApiKey::generate(&NewApiKey::new("oven", Scope::Read), Utc::now());
+
+        assert!(
+            UserRepository::update(&repository, ann.id(), &mut |account| {
+                account.add_key(key.clone());
+                true
+            })
+            .unwrap()
+        );
+
+        let found = repository.by_key(&hash_key(&secret)).unwrap().unwrap();
+        assert_eq!(found.id(), ann.id());
+        assert_eq!(found.key(&hash_key(&secret)), Some(key.details()));
+        assert_eq!(repository.by_key(&hash_key("cpk_guess")).unwrap(), None);
+    }
+
+    #[test]
+    fn sqlite_records_crud() {
+        let path = std::env::temp_dir().join(format!("records-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let records = SqliteRecords::open(&path, "calendars", "calendar").unwrap();
+        records.insert("t", Owned::new("ann", vec![1])).unwrap();
+
+        let record = records
+            .modify("t", &mut |record| {
+                record.item.push(2);
+                true
+            })
+            .unwrap();
+        assert_eq!(record, Some(Owned::new("ann", vec![1, 2])));
+        assert_eq!(records.modify("u", &mut |_| true).unwrap(), None);
+        assert!(records.insert("t", Owned::new("bob", vec![])).is_err());
+
+        let reopened = SqliteRecords::<Vec<u32>>::open(&path, "calendars", "calendar").unwrap();
+        assert_eq!(reopened.get("t").unwrap(), record);
+
+        reopened.insert("u", Owned::new("bob", vec![3])).unwrap();
+        reopened
+            .modify_all("ann", &mut |item| {
+                item.clear();
+                true
+            })
+            .unwrap();
+        assert_eq!(reopened.list("ann").unwrap(), vec![Vec::<u32>::new()]);
+        assert_eq!(reopened.list("bob").unwrap(), vec![vec![3]]);
+        assert!(reopened.delete("u").unwrap());
+        assert!(!reopened.delete("u").unwrap());
+        drop((records, reopened));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use common::{Course, CourseDetails, Metadata, Stage};
+
+use crate::users::Owned;
+
+/// A course as written to disk, keyed by its id in the surrounding map.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredCourse {
+    /// The id of the user the course belongs to; missing from files saved before accounts.
+    #[serde(default)]
+    owner: String,
+    #[serde(default)]
+    name: String,
+    stages: Vec<Stage>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    metadata: Metadata,
+}
+
+/// Keeps every course in a single JSON file, rewritten in full on each change.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+        }
+    }
+
+    /// Read all courses, starting empty if the file is missing or cannot be parsed.
+    ///
+    /// A corrupt file is moved aside rather than overwritten by the next save. Any other
+    /// failure to read it, e.g. for lack of permission, is returned, as saving over it
+    /// would lose every course.
+    pub fn load(&self) -> io::Result<HashMap<String, Owned<Course>>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("No courses at {:?}, starting empty", self.path);
+                return Ok(HashMap::new());
+            }
+            // Not UTF-8, so not JSON either.
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                self.move_aside(&e)?;
+                return Ok(HashMap::new());
+            }
+            Err(e) => {
+                error!("Cannot read courses from {:?}: {}", self.path, e);
+                return Err(e);
+            }
+        };
+        match serde_json::from_str::<HashMap<String, StoredCourse>>(&contents) {
+            Ok(stored) => {
+                info!("Loaded {} courses from {:?}", stored.len(), self.path);
+                Ok(stored
+                    .into_iter()
+                    .map(|(id, stored)| {
+                        let details = CourseDetails::new(&id, &stored.name);
+                        let mut course = Course::from_stages(&details, stored.stages);
+                        course.set_metadata(stored.metadata);
+                        (id, Owned::new(&stored.owner, course))
+                    })
+                    .collect())
+            }
+            Err(e) => {
+                self.move_aside(&e)?;
+                Ok(HashMap::new())
+            }
+        }
+    }
+
+    fn move_aside(&self, reason: &dyn std::fmt::Display) -> io::Result<()> {
+        let backup = self.path.with_extension("json.corrupt");
+        warn!(
+            "Courses file {:?} is corrupt ({}), moving it to {:?}",
+            self.path, reason, backup
+        );
+        fs::rename(&self.path, &backup).inspect_err(|e| {
+            error!("Cannot move {:?} aside: {}", self.path, e);
+        })
+    }
+
+    pub fn save(&self, courses: &HashMap<String, Owned<Course>>) -> io::Result<()> {
+        let stored = courses
+            .iter()
+            .map(
+                |(
+                    id,
+                    Owned {
+                        owner,
+                        item: course,
+                    },
+                )| {
+                    (
+                        id,
+                        StoredCourse {
+                            owner: owner.clone(),
+                            name: course.details().name().to_owned(),
+                            stages: course.raw_stages().to_vec(),
+                            metadata: course.metadata().clone(),
+                        },
+                    )
+                },
+            )
+            .collect::<HashMap<_, _>>();
+        replace(
+            &self.path,
+            serde_json::to_string_pretty(&stored)?.as_bytes(),
+        )
+    }
+}
+
+/// Write `contents` to a temporary file, then rename it over `path`, so that a crash part
+/// way through never leaves a truncated file behind.
+pub fn replace(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp = path.with_extension("json.tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str) -> FileStore {
+        let path = std::env::temp_dir().join(format!("{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        FileStore::new(&path)
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let store = store("round-trip");
+        let mut course = Course::new(&CourseDetails::new("0", "Toast"));
+        course.add(Stage::new("Bread in toaster", "1min"));
+        course.set_metadata(Metadata::default().with_field("servings", "2"));
+        let courses = HashMap::from([("0".to_owned(), Owned::new("ann", course))]);
+
+        store.save(&courses).unwrap();
+
+        assert_eq!(store.load().unwrap(), courses);
+        fs::remove_file(&store.path).unwrap();
+    }
+
+    #[test]
+    fn load_moves_corrupt_file_aside() {
+        let store = store("corrupt");
+        fs::write(&store.path, "{ not json").unwrap();
+
+        assert!(store.load().unwrap().is_empty());
+
+        let backup = store.path.with_extension("json.corrupt");
+        assert!(!store.path.exists());
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "{ not json");
+        fs::remove_file(backup).unwrap();
+    }
+
+    #[test]
+    fn load_moves_binary_file_aside() {
+        let store = store("binary");
+        fs::write(&store.path, [0xff, 0xfe, 0x00]).unwrap();
+
+        assert!(store.load().unwrap().is_empty());
+
+        let backup = store.path.with_extension("json.corrupt");
+        assert!(!store.path.exists());
+        assert_eq!(fs::read(&backup).unwrap(), [0xff, 0xfe, 0x00]);
+        fs::remove_file(backup).unwrap();
+    }
+
+    #[test]
+    fn load_fails_if_the_file_cannot_be_read() {
+        // A directory cannot be read as a file, much like one without permission.
+        let store = FileStore::new(&std::env::temp_dir());
+
+        assert!(store.load().is_err());
+    }
+}
@@ -0,0 +1,472 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use rocket::http::{Cookie, CookieJar, Method, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use common::{ApiKeyDetails, Credentials, NewApiKey, Scope, UserDetails};
+
+use crate::repository::RepositoryError;
+use crate::Config;
+
+/// The private cookie holding the id of the signed in user.
+const COOKIE: &str = "user";
+
+/// What every API key starts with, so that a leaked one is easy to recognise.
+const KEY_PREFIX: &str = "cpk_";
+
+/// How often a key's last use is recorded, so that a busy script does not rewrite its
+/// owner's account on every request.
+const KEY_USE_RESOLUTION: Duration = Duration::minutes(1);
+
+/// Something which belongs to one user: a course, meal, session or calendar feed.
+///
+/// `owner` is empty for courses made before there were accounts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Owned<T> {
+    pub owner: String,
+    pub item: T,
+}
+
+impl<T> Owned<T> {
+    pub fn new(owner: &str, item: T) -> Self {
+        Self {
+            owner: owner.to_owned(),
+            item,
+        }
+    }
+
+    pub fn is_owned_by(&self, user: &User) -> bool {
+        self.owner == user.id()
+    }
+}
+
+/// An account as stored, with a hash of its password rather than the password itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Account {
+    id: String,
+    name: String,
+    /// In PHC string format, so it records its own algorithm, parameters and salt.
+    password_hash: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    api_keys: Vec<ApiKey>,
+}
+
+/// An API key as stored, with a hash of the key rather than the key itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiKey {
+    #[serde(flatten)]
+    details: ApiKeyDetails,
+    hash: String,
+}
+
+impl ApiKey {
+    /// A new random key, returned alongside it as this is the only time it is known.
+    pub fn generate(new: &NewApiKey, now: DateTime<Utc>) -> (Self, String) {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        let key = format!("{}{}", KEY_PREFIX, hex(&secret));
+        let details = ApiKeyDetails::new(
+            &uuid::Uuid::new_v4().to_string(),
+            new.name(),
+            new.scope(),
+            now,
+        );
+        let hash = hash_key(&key);
+        (Self { details, hash }, key)
+    }
+
+    pub fn details(&self) -> &ApiKeyDetails {
+        &self.details
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// How an API key is stored and looked up. Keys are 256 random bits, so unlike passwords
+/// they need no salt or slow hash.
+pub fn hash_key(key: &str) -> String {
+    hex(&Sha256::digest(key.as_bytes()))
+}
+
+impl Account {
+    /// A new account, with its password hashed under a fresh random salt.
+    pub fn new(credentials: &Credentials) -> Result<Self, argon2::password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(credentials.password().as_bytes(), &salt)?
+            .to_string();
+        Ok(Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: credentials.name().to_owned(),
+            password_hash,
+            api_keys: Vec::new(),
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        match PasswordHash::new(&self.password_hash) {
+            Ok(hash) => Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok(),
+            Err(e) => {
+                error!("Unreadable password hash for user {}: {}", self.id, e);
+                false
+            }
+        }
+    }
+
+    /// Check `password` against the hash of a made-up account, taking as long as `verify`
+    /// does, so that signing in as nobody cannot be told from a wrong password by timing.
+    pub fn verify_nobody(password: &str) {
+        static NOBODY: OnceLock<Option<Account>> = OnceLock::new();
+        let nobody = NOBODY.get_or_init(|| {
+            let mut password = [0u8; 16];
+            OsRng.fill_bytes(&mut password);
+            Account::new(&Credentials::new("", &hex(&password))).ok()
+        });
+        if let Some(nobody) = nobody {
+            nobody.verify(password);
+        }
+    }
+
+    pub fn details(&self) -> UserDetails {
+        UserDetails::new(&self.id, &self.name)
+    }
+
+    pub fn api_keys(&self) -> impl Iterator<Item = &ApiKeyDetails> {
+        self.api_keys.iter().map(ApiKey::details)
+    }
+
+    pub fn add_key(&mut self, key: ApiKey) {
+        self.api_keys.push(key);
+    }
+
+    /// Remove the key with `id`, returning whether there was one.
+    pub fn revoke_key(&mut self, id: &str) -> bool {
+        let count = self.api_keys.len();
+        self.api_keys.retain(|key| key.details.id() != id);
+        self.api_keys.len() != count
+    }
+
+    /// The key whose hash is `hash`.
+    pub fn key(&self, hash: &str) -> Option<&ApiKeyDetails> {
+        self.api_keys
+            .iter()
+            .find(|key| key.hash == hash)
+            .map(ApiKey::details)
+    }
+
+    /// Record that the key whose hash is `hash` was used at `at`, returning whether that
+    /// changed the account, which it does at most once a `KEY_USE_RESOLUTION`.
+    pub fn key_used(&mut self, hash: &str, at: DateTime<Utc>) -> bool {
+        match self.api_keys.iter_mut().find(|key| key.hash == hash) {
+            Some(key)
+                if key
+                    .details
+                    .last_used_at()
+                    .is_none_or(|last| at - *last >= KEY_USE_RESOLUTION) =>
+            {
+                key.details.set_last_used_at(at);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Where accounts live. Every method is atomic with respect to the others.
+pub trait UserRepository: Send + Sync {
+    /// Store a new account, returning `false` if its name is already taken.
+    fn create(&self, account: Account) -> Result<bool, RepositoryError>;
+
+    fn by_id(&self, id: &str) -> Result<Option<Account>, RepositoryError>;
+
+    fn by_name(&self, name: &str) -> Result<Option<Account>, RepositoryError>;
+
+    /// Apply `change` to the account `id`, keeping the result if `change` returns `true`.
+    /// Returns whether it did, so `false` if there is no such account.
+    fn update(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Account) -> bool,
+    ) -> Result<bool, RepositoryError>;
+
+    /// The account with an API key whose hash is `hash`.
+    fn by_key(&self, hash: &str) -> Result<Option<Account>, RepositoryError>;
+}
+
+/// Accounts held in memory, optionally saved to a JSON file after every change.
+#[derive(Debug, Default)]
+pub struct MemoryUsers {
+    accounts: Mutex<HashMap<String, Account>>,
+    path: Option<PathBuf>,
+}
+
+impl MemoryUsers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accounts kept in `path`; a missing file is no accounts yet.
+    pub fn with_file(path: &Path) -> Result<Self, RepositoryError> {
+        let accounts: Vec<Account> = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        info!("Loaded {} accounts from {:?}", accounts.len(), path);
+        Ok(Self {
+            accounts: Mutex::new(
+                accounts
+                    .into_iter()
+                    .map(|account| (account.id.clone(), account))
+                    .collect(),
+            ),
+            path: Some(path.to_owned()),
+        })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, Account>>, RepositoryError> {
+        self.accounts
+            .lock()
+            .map_err(|_| RepositoryError::Poisoned.storing("account"))
+    }
+
+    fn save(&self, accounts: &HashMap<String, Account>) -> Result<(), RepositoryError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let all = accounts.values().collect::<Vec<_>>();
+        serde_json::to_string_pretty(&all)
+            .map_err(RepositoryError::from)
+            .and_then(|json| Ok(crate::store::replace(path, json.as_bytes())?))
+            .map_err(|e| e.storing("account"))
+    }
+}
+
+impl UserRepository for MemoryUsers {
+    fn create(&self, account: Account) -> Result<bool, RepositoryError> {
+        let mut accounts = self.lock()?;
+        if accounts.values().any(|other| other.name == account.name) {
+            return Ok(false);
+        }
+        accounts.insert(account.id.clone(), account);
+        self.save(&accounts)?;
+        Ok(true)
+    }
+
+    fn by_id(&self, id: &str) -> Result<Option<Account>, RepositoryError> {
+        Ok(self.lock()?.get(id).cloned())
+    }
+
+    fn by_name(&self, name: &str) -> Result<Option<Account>, RepositoryError> {
+        Ok(self
+            .lock()?
+            .values()
+            .find(|account| account.name == name)
+            .cloned())
+    }
+
+    fn update(
+        &self,
+        id: &str,
+        change: &mut dyn FnMut(&mut Account) -> bool,
+    ) -> Result<bool, RepositoryError> {
+        let mut accounts = self.lock()?;
+        let changed = accounts.get_mut(id).is_some_and(change);
+        if changed {
+            self.save(&accounts)?;
+        }
+        Ok(changed)
+    }
+
+    fn by_key(&self, hash: &str) -> Result<Option<Account>, RepositoryError> {
+        Ok(self
+            .lock()?
+            .values()
+            .find(|account| account.key(hash).is_some())
+            .cloned())
+    }
+}
+
+/// The user a request is from, for routes which need one; others get a 401.
+///
+/// Browsers are recognised by the cookie set when signing in, and scripts by an API key
+/// sent as `Authorization: Bearer <key>`. A read-only key gets a 403 for anything but a
+/// `GET`.
+#[derive(Debug, Clone)]
+pub struct User(UserDetails);
+
+impl User {
+    pub fn id(&self) -> &str {
+        self.0.id()
+    }
+
+    pub fn details(&self) -> &UserDetails {
+        &self.0
+    }
+
+    /// Remember `account` as signed in, in a cookie only the server can read or forge.
+    pub fn sign_in(cookies: &CookieJar<'_>, account: &Account) -> Self {
+        cookies.add_private(Cookie::new(COOKIE, account.id().to_owned()));
+        User(account.details())
+    }
+
+    pub fn sign_out(cookies: &CookieJar<'_>) {
+        cookies.remove_private(COOKIE);
+    }
+}
+
+/// A user signed in with the cookie rather than an API key, for routes which keys must
+/// not reach, such as managing keys.
+#[derive(Debug, Clone)]
+pub struct SignedIn(pub User);
+
+fn config<'r>(request: &'r Request<'_>) -> &'r Config {
+    request
+        .rocket()
+        .state::<Config>()
+        .expect("Config is managed")
+}
+
+/// Pass on a repository failure as a 500, logging the cause.
+fn failed<T>(e: RepositoryError) -> Outcome<T, ()> {
+    error!("{}", e);
+    Outcome::Error((Status::InternalServerError, ()))
+}
+
+fn from_cookie(request: &Request<'_>) -> Outcome<User, ()> {
+    let id = match request.cookies().get_private(COOKIE) {
+        Some(cookie) => cookie.value().to_owned(),
+        None => return Outcome::Error((Status::Unauthorized, ())),
+    };
+    match config(request).users.by_id(&id) {
+        Ok(Some(account)) => Outcome::Success(User(account.details())),
+        // The account has gone, e.g. with an in-memory backend which has restarted.
+        Ok(None) => Outcome::Error((Status::Unauthorized, ())),
+        Err(e) => failed(e),
+    }
+}
+
+fn from_key(request: &Request<'_>, authorization: &str) -> Outcome<User, ()> {
+    let key = match authorization.strip_prefix("Bearer ") {
+        Some(key) => key.trim(),
+        None => return Outcome::Error((Status::Unauthorized, ())),
+    };
+    let hash = hash_key(key);
+    let users = &config(request).users;
+    let (account, scope) = match users.by_key(&hash) {
+        Ok(Some(account)) => match account.key(&hash).map(ApiKeyDetails::scope) {
+            Some(scope) => (account, scope),
+            None => return Outcome::Error((Status::Unauthorized, ())),
+        },
+        Ok(None) => return Outcome::Error((Status::Unauthorized, ())),
+        Err(e) => return failed(e),
+    };
+    if scope == Scope::Read && !matches!(request.method(), Method::Get | Method::Head) {
+        return Outcome::Error((Status::Forbidden, ()));
+    }
+    let now = Utc::now();
+    if let Err(e) = users.update(account.id(), &mut |account| account.key_used(&hash, now)) {
+        // Not worth failing the request over.
+        error!("Cannot record use of an API key: {}", e);
+    }
+    Outcome::Success(User(account.details()))
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for User {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.headers().get_one("Authorization") {
+            Some(authorization) => from_key(request, authorization),
+            None => from_cookie(request),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SignedIn {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if request.headers().contains("Authorization") {
+            return Outcome::Error((Status::Forbidden, ()));
+        }
+        from_cookie(request).map(SignedIn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwords_are_hashed_and_verified() {
+        let account = Account::new(&Credentials::new("ann", "correct horse")).unwrap();
+
+        assert!(!account.password_hash.contains("correct horse"));
+        assert!(account.verify("correct horse"));
+        assert!(!account.verify("wrong horse"));
+    }
+
+    #[test]
+    fn api_keys_are_hashed_and_their_use_recorded_each_minute() {
+        let mut account = Account::new(&Credentials::new("ann", "correct horse")).unwrap();
+        let now = Utc::now();
+        let (key, secret) = ApiKey::generate(&NewApiKey::new("oven", Scope::Write), now);
+        let id = key.details().id().to_owned();
+        account.add_key(key);
+        let hash = hash_key(&secret);
+
+        assert!(secret.starts_with(KEY_PREFIX));
+        assert!(!serde_json::to_string(&account).unwrap().contains(&secret));
+        assert!(account.key_used(&hash, now));
+        assert!(!account.key_used(&hash, now + Duration::seconds(30)));
+        assert!(account.key_used(&hash, now + Duration::seconds(90)));
+        assert!(account.revoke_key(&id));
+        assert_eq!(account.key(&hash), None);
+    }
+
+    #[test]
+    fn names_are_unique_and_accounts_saved() {
+        let path = std::env::temp_dir().join(format!("users-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let users = MemoryUsers::with_file(&path).unwrap();
+        let ann = Account::new(&Credentials::new("ann", "correct horse")).unwrap();
+
+        assert!(users.create(ann.clone()).unwrap());
+        assert!(!users
+            .create(Account::new(&Credentials::new("ann", "other horse")).unwrap())
+            .unwrap());
+
+        let reloaded = MemoryUsers::with_file(&path).unwrap();
+        assert_eq!(reloaded.by_name("ann").unwrap(), Some(ann.clone()));
+        assert_eq!(reloaded.by_id(ann.id()).unwrap(), Some(ann));
+        fs::remove_file(path).unwrap();
+    }
+}
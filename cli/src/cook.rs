@@ -0,0 +1,242 @@
+//! Cook mode in the terminal: a live countdown through a course, for kitchens with a
+//! laptop but no browser.
+//!
+//! The countdown is a `Session` kept in memory, so pausing, skipping and extending work
+//! just as they do for sessions kept by the backend.
+
+use chrono::{DateTime, Local, Utc};
+use common::{Session, SessionState, SessionStatus};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Alignment, Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// How long `+` gives the current stage.
+const EXTEND_BY: Duration = Duration::from_secs(60);
+
+/// How often the countdown is redrawn when no key is pressed.
+const TICK: Duration = Duration::from_millis(250);
+
+/// A countdown as a clock, e.g. `4:05` or `1:04:05`.
+fn clock(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// The stage after the current one, or the first while waiting to start.
+fn next(state: &SessionState) -> Option<usize> {
+    match (state.status(), state.current_index()) {
+        (SessionStatus::Waiting, _) => Some(0),
+        (_, Some(current)) => Some(current + 1),
+        _ => None,
+    }
+}
+
+fn draw(frame: &mut Frame, state: &SessionState, now: DateTime<Utc>, message: Option<&str>) {
+    let [header, countdown, stages, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(4),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let serve_at = state.serve_at().with_timezone(&Local);
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::from(state.course_name()).bold(),
+            Span::from(format!(
+                "  {}, served at {}",
+                state.status(),
+                serve_at.format("%H:%M")
+            )),
+        ]))
+        .block(Block::bordered()),
+        header,
+    );
+
+    let (title, colour) = match (state.status(), state.current()) {
+        (SessionStatus::Finished, _) => ("Served".to_owned(), Color::Green),
+        (SessionStatus::Waiting, _) => ("Starting in".to_owned(), Color::Cyan),
+        (SessionStatus::Paused, Some(stage)) => (format!("{} (paused)", stage.name()), Color::Red),
+        (SessionStatus::Paused, None) => ("Paused".to_owned(), Color::Red),
+        (SessionStatus::Running, stage) => (
+            stage.map_or_else(String::new, |stage| stage.name().to_owned()),
+            Color::Yellow,
+        ),
+    };
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::from(title).bold(),
+            Line::from(clock(state.remaining_at(now))).bold().fg(colour),
+        ])
+        .alignment(Alignment::Center)
+        .block(Block::bordered()),
+        countdown,
+    );
+
+    let next = next(state);
+    let items = state.stages().iter().enumerate().map(|(i, stage)| {
+        let start = stage.start().with_timezone(&Local).format("%H:%M");
+        let (marker, style) = if Some(i) == state.current_index() {
+            (
+                "▶",
+                Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            )
+        } else if Some(i) == next {
+            ("›", Style::new().fg(Color::Cyan))
+        } else if state.current_index().is_some_and(|current| i < current)
+            || state.status() == SessionStatus::Finished
+        {
+            (" ", Style::new().fg(Color::DarkGray))
+        } else {
+            (" ", Style::new())
+        };
+        ListItem::new(format!("{} {}  {}", marker, start, stage.name())).style(style)
+    });
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title("Stages")),
+        stages,
+    );
+
+    frame.render_widget(
+        Paragraph::new(message.unwrap_or("space pause/resume · s skip · + 1 min more · q quit"))
+            .dim(),
+        footer,
+    );
+}
+
+/// Ring the terminal bell.
+fn bell() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()
+}
+
+/// Where the session has got to, which changes only when a stage starts or ends, and not
+/// on pausing or resuming.
+fn progress(state: &SessionState) -> (Option<usize>, bool) {
+    (
+        state.current_index(),
+        state.status() == SessionStatus::Finished,
+    )
+}
+
+fn cook(terminal: &mut DefaultTerminal, session: &mut Session) -> io::Result<()> {
+    let mut last = None;
+    let mut message = None;
+    loop {
+        let now = Utc::now();
+        let state = session.state(now);
+        let position = progress(&state);
+        if last.is_some_and(|last| last != position) {
+            bell()?;
+        }
+        last = Some(position);
+        terminal.draw(|frame| draw(frame, &state, now, message.as_deref()))?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        let now = Utc::now();
+        let result = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char(' ') | KeyCode::Char('p') if state.status() == SessionStatus::Paused => {
+                session.resume(now)
+            }
+            KeyCode::Char(' ') | KeyCode::Char('p') => session.pause(now),
+            KeyCode::Char('s') | KeyCode::Right => session.skip(now),
+            KeyCode::Char('+') => session.extend(now, EXTEND_BY),
+            _ => Ok(()),
+        };
+        message = result.err().map(|e| e.to_string());
+    }
+}
+
+/// Count down through the session's course until the cook quits.
+pub fn run(mut session: Session) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = cook(&mut terminal, &mut session);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use common::{Course, CourseDetails, Stage};
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn clock_shows_hours_only_when_needed() {
+        assert_eq!(clock(Duration::from_secs(245)), "4:05");
+        assert_eq!(clock(Duration::from_secs(3845)), "1:04:05");
+    }
+
+    #[test]
+    fn current_and_next_stages_are_marked() {
+        let course = Course::from_lengths(
+            &CourseDetails::new("", "Toast"),
+            vec![
+                Stage::new("Bread in toaster", "2m"),
+                Stage::new("Butter", "30s"),
+            ],
+        );
+        let now = Utc::now();
+        let state = Session::starting("cli", &course, now).unwrap().state(now);
+        let mut terminal = Terminal::new(TestBackend::new(40, 14)).unwrap();
+
+        terminal
+            .draw(|frame| draw(frame, &state, now, None))
+            .unwrap();
+
+        let screen = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(40)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>();
+        let row = |name: &str| screen.iter().find(|row| row.contains(name)).cloned();
+        assert!(row("2:00").is_some());
+        assert!(row("▶").unwrap().contains("Bread in toaster"));
+        assert!(row("›").unwrap().contains("Butter"));
+    }
+
+    #[test]
+    fn progress_changes_with_stages_but_not_pauses() {
+        let course = Course::from_lengths(
+            &CourseDetails::new("", "Toast"),
+            vec![
+                Stage::new("Bread in toaster", "2m"),
+                Stage::new("Butter", "30s"),
+            ],
+        );
+        let now = Utc::now();
+        let mut session = Session::starting("cli", &course, now).unwrap();
+        let start = progress(&session.state(now));
+
+        session.pause(now).unwrap();
+        assert_eq!(progress(&session.state(now)), start);
+        session.resume(now).unwrap();
+        assert_eq!(progress(&session.state(now)), start);
+        session.skip(now).unwrap();
+        assert_ne!(progress(&session.state(now)), start);
+    }
+}
@@ -0,0 +1,188 @@
+//! Course files on disk, in whichever format their extension says:
+//! `.json` for a course or a list of courses as the API sends them, or the backend's own
+//! `courses.json`, `.course` for the plain text format and `.cook` for a Cooklang recipe,
+//! named after the file.
+
+use common::{Course, CourseDetails, Metadata, Stage};
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+/// What a `.json` file may hold.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Json {
+    One(Course),
+    Many(Vec<Course>),
+    /// The backend's file storage, with each course under its id.
+    Stored(HashMap<String, StoredCourse>),
+}
+
+/// A course as the backend stores it in a file; its owner is not needed here.
+#[derive(Deserialize)]
+struct StoredCourse {
+    #[serde(default)]
+    name: String,
+    stages: Vec<Stage>,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+impl StoredCourse {
+    fn into_course(self, id: &str) -> Course {
+        let mut course = Course::from_stages(&CourseDetails::new(id, &self.name), self.stages);
+        course.set_metadata(self.metadata);
+        course
+    }
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Whether `path` names a course file, judging by its extension.
+pub fn is_course_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| ["json", "course", "cook"].iter().any(|e| extension == *e))
+}
+
+/// Read every course in a file.
+pub fn courses(path: &Path) -> Result<Vec<Course>, Error> {
+    let invalid = |reason: String| Error::Invalid {
+        path: path.to_owned(),
+        reason,
+    };
+    let text = read(path)?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => match serde_json::from_str(&text) {
+            Ok(Json::One(course)) => Ok(vec![course]),
+            Ok(Json::Many(courses)) => Ok(courses),
+            Ok(Json::Stored(stored)) => {
+                let mut stored = stored.into_iter().collect::<Vec<_>>();
+                stored.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Ok(stored
+                    .into_iter()
+                    .map(|(id, course)| course.into_course(&id))
+                    .collect())
+            }
+            Err(e) => Err(invalid(e.to_string())),
+        },
+        Some("course") => common::text::parse(&text).map_err(|e| invalid(e.to_string())),
+        Some("cook") => {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy())
+                .unwrap_or_default();
+            common::cooklang::parse(&name, &text)
+                .map(|course| vec![course])
+                .map_err(|e| invalid(e.to_string()))
+        }
+        _ => Err(invalid(
+            "not a course file; expected .json, .course or .cook".to_owned(),
+        )),
+    }
+}
+
+/// The courses in a file with the given name, or all of them; an error if there are none.
+pub fn named(path: &Path, name: Option<&str>) -> Result<Vec<Course>, Error> {
+    let courses = courses(path)?
+        .into_iter()
+        .filter(|course| name.is_none_or(|name| course.details().name() == name))
+        .collect::<Vec<_>>();
+    if courses.is_empty() {
+        return Err(Error::Invalid {
+            path: path.to_owned(),
+            reason: match name {
+                Some(name) => format!("no course named '{}'", name),
+                None => "no courses".to_owned(),
+            },
+        });
+    }
+    Ok(courses)
+}
+
+/// The course files named by `paths`, looking inside any directories, in name order
+/// within each directory.
+pub fn expand(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.to_owned());
+            continue;
+        }
+        let io = |source| Error::Io {
+            path: path.to_owned(),
+            source,
+        };
+        let mut found = Vec::new();
+        for entry in fs::read_dir(path).map_err(io)? {
+            let path = entry.map_err(io)?.path();
+            if is_course_file(&path) {
+                found.push(path);
+            }
+        }
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn json_holds_one_course_or_many() {
+        let course = Course::new(&CourseDetails::new("", "Toast"));
+        let one = file("one.json", &serde_json::to_string(&course).unwrap());
+        let many = file(
+            "many.json",
+            &serde_json::to_string(&[&course, &course]).unwrap(),
+        );
+
+        assert_eq!(courses(&one).unwrap(), vec![course.clone()]);
+        assert_eq!(courses(&many).unwrap().len(), 2);
+        fs::remove_file(one).unwrap();
+        fs::remove_file(many).unwrap();
+    }
+
+    #[test]
+    fn json_may_be_the_backends_course_store() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../backend/courses.json");
+
+        let courses = courses(&path).unwrap();
+
+        assert_eq!(courses.len(), 7);
+        assert_eq!(courses[0].details().id(), "1");
+        assert_eq!(courses[0].raw_stages().len(), 8);
+    }
+
+    #[test]
+    fn errors_name_the_file() {
+        let path = file("bad.course", "Toast:\n  Bread in toaster soon\n");
+
+        let error = courses(&path).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{}: line 2, column 24: expected how long the stage lasts, e.g. '5m'",
+                path.display()
+            )
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
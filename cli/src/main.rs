@@ -0,0 +1,337 @@
+use clap::{Parser, Subcommand};
+use common::{ApiError, Course, CourseDetails, ScheduleError, Session};
+use itertools::Itertools;
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+mod cook;
+mod files;
+
+/// Plan courses from the command line: read and check course files, print timelines, and
+/// list, import and export the courses of a running course planner backend.
+#[derive(Parser)]
+#[command(name = "course_planner", version)]
+struct Cli {
+    /// Where the backend is running.
+    #[arg(long, default_value = "http://localhost:1111", global = true)]
+    server: String,
+    /// An API key made in the web app, for commands which use the backend. A read-only
+    /// key is enough for `list` and `export`.
+    #[arg(
+        long,
+        env = "COURSE_PLANNER_API_KEY",
+        hide_env_values = true,
+        global = true
+    )]
+    api_key: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the courses the backend knows about.
+    List,
+    /// Import course files, or every course file in a directory.
+    Import {
+        /// Files, or directories of them, in any supported format.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Print courses in the plain text format, ready to edit and import again.
+    Export {
+        /// The course to print; every course if left out.
+        id: Option<String>,
+    },
+    /// Check that course files can be read, without importing them.
+    Validate {
+        /// Files, or directories of them, in any supported format.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Print when to start each stage of the courses in a file.
+    Timeline {
+        /// A course file in any supported format.
+        path: PathBuf,
+        /// When to serve, e.g. `2022-03-27T19:30`, or RFC 3339 with an offset.
+        #[arg(long)]
+        serve_at: String,
+        /// The IANA time zone of `--serve-at`, e.g. `Europe/London`; UTC if left out.
+        #[arg(long)]
+        tz: Option<String>,
+        /// Only the course with this name.
+        #[arg(long)]
+        course: Option<String>,
+    },
+    /// Count down through a course in the terminal, ringing the bell as each stage starts.
+    Cook {
+        /// A course file in any supported format.
+        path: PathBuf,
+        /// Which course to cook, if the file holds several.
+        #[arg(long)]
+        course: Option<String>,
+        /// When to serve, as for `timeline`; the first stage starts straight away if left out.
+        #[arg(long)]
+        serve_at: Option<String>,
+        /// The IANA time zone of `--serve-at`; UTC if left out.
+        #[arg(long)]
+        tz: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+enum Error {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A file which is not a course in the format its extension says.
+    Invalid {
+        path: PathBuf,
+        reason: String,
+    },
+    /// A serving time or time zone which could not be understood.
+    Schedule(ScheduleError),
+    /// The backend refused a request.
+    Api(ApiError),
+    /// The backend could not be reached, or answered with something other than JSON.
+    Connection(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Invalid { path, reason } => write!(f, "{}: {}", path.display(), reason),
+            Error::Schedule(e) => write!(f, "{}", e),
+            Error::Api(e) => write!(f, "{}", e),
+            Error::Connection(message) => write!(f, "cannot talk to the backend: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ureq::Error> for Error {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(_, response) => match response.into_json() {
+                Ok(e) => Error::Api(e),
+                Err(e) => Error::Connection(e.to_string()),
+            },
+            e => Error::Connection(e.to_string()),
+        }
+    }
+}
+
+/// The backend, and the API key to use it with.
+struct Backend<'a> {
+    server: &'a str,
+    api_key: Option<&'a str>,
+}
+
+impl Backend<'_> {
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let request = ureq::request(method, &format!("{}{}", self.server, path));
+        match self.api_key {
+            Some(key) => request.set("Authorization", &format!("Bearer {}", key)),
+            None => request,
+        }
+    }
+}
+
+fn list(backend: &Backend) -> Result<bool, Error> {
+    let courses: Vec<CourseDetails> = backend
+        .request("GET", "/courses")
+        .call()?
+        .into_json()
+        .map_err(|e| Error::Connection(e.to_string()))?;
+    for course in courses {
+        println!("{}  {}", course.id(), course.name());
+    }
+    Ok(true)
+}
+
+fn import_course(backend: &Backend, course: &Course) -> Result<CourseDetails, Error> {
+    backend
+        .request("PUT", "/course/import")
+        .send_json(course)?
+        .into_json()
+        .map_err(|e| Error::Connection(e.to_string()))
+}
+
+/// Import each course in turn, carrying on past any which fail.
+///
+/// Files are read before anything is sent, so the backend sees the same course as
+/// `validate` and `timeline` do.
+fn import(backend: &Backend, paths: &[PathBuf]) -> Result<bool, Error> {
+    let mut ok = true;
+    for path in files::expand(paths)? {
+        let courses = match files::courses(&path) {
+            Ok(courses) => courses,
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+                continue;
+            }
+        };
+        for course in courses {
+            match import_course(backend, &course) {
+                Ok(details) => println!("{}: imported '{}'", path.display(), details.name()),
+                Err(e @ Error::Connection(_)) => return Err(e),
+                Err(e) => {
+                    eprintln!("{}: {}", path.display(), e);
+                    ok = false;
+                }
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn export(backend: &Backend, id: Option<&str>) -> Result<bool, Error> {
+    let path = match id {
+        Some(id) => format!("/course/{}/text", id),
+        None => "/courses/text".to_owned(),
+    };
+    let text = backend
+        .request("GET", &path)
+        .call()?
+        .into_string()
+        .map_err(|e| Error::Connection(e.to_string()))?;
+    print!("{}", text);
+    Ok(true)
+}
+
+fn validate(paths: &[PathBuf]) -> Result<bool, Error> {
+    let mut ok = true;
+    for path in files::expand(paths)? {
+        match files::courses(&path) {
+            Ok(courses) => println!(
+                "{}: {}",
+                path.display(),
+                courses
+                    .iter()
+                    .map(|course| course.details().name())
+                    .join(", ")
+            ),
+            Err(e) => {
+                eprintln!("{}", e);
+                ok = false;
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn timeline(
+    path: &Path,
+    serve_at: &str,
+    tz: Option<&str>,
+    name: Option<&str>,
+) -> Result<bool, Error> {
+    let serve_at = common::schedule::serve_time(serve_at, tz).map_err(Error::Schedule)?;
+    let courses = files::named(path, name)?;
+    for (i, course) in courses.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!(
+            "{}, served {}",
+            course.details().name(),
+            serve_at.format("%a %-d %b %H:%M %Z")
+        );
+        for stage in course.schedule(&serve_at).map_err(Error::Schedule)? {
+            // Stages on an earlier day than serving say which day.
+            let format = if stage.start().date_naive() == serve_at.date_naive() {
+                "%H:%M"
+            } else {
+                "%a %H:%M"
+            };
+            print!(
+                "  {:>9}  {}",
+                stage.start().format(format).to_string(),
+                stage.name()
+            );
+            match (*stage.end() - *stage.start()).to_std() {
+                Ok(length) if !length.is_zero() => {
+                    println!(" ({})", humantime::format_duration(length))
+                }
+                _ => println!(),
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn cook(
+    path: &Path,
+    name: Option<&str>,
+    serve_at: Option<&str>,
+    tz: Option<&str>,
+) -> Result<bool, Error> {
+    let serve_at = serve_at
+        .map(|serve_at| common::schedule::serve_time(serve_at, tz))
+        .transpose()
+        .map_err(Error::Schedule)?
+        .map(|serve_at| serve_at.with_timezone(&chrono::Utc));
+    let course = match files::named(path, name)?.as_slice() {
+        [course] => course.clone(),
+        _ => {
+            return Err(Error::Invalid {
+                path: path.to_owned(),
+                reason: "holds several courses; choose one with --course".to_owned(),
+            })
+        }
+    };
+    let session = match serve_at {
+        Some(serve_at) => Session::new("cli", &course, serve_at),
+        None => Session::starting("cli", &course, chrono::Utc::now()),
+    }
+    .map_err(|e| Error::Invalid {
+        path: path.to_owned(),
+        reason: e.to_string(),
+    })?;
+    cook::run(session).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let backend = Backend {
+        server: &cli.server,
+        api_key: cli.api_key.as_deref(),
+    };
+    let result = match &cli.command {
+        Command::List => list(&backend),
+        Command::Import { paths } => import(&backend, paths),
+        Command::Export { id } => export(&backend, id.as_deref()),
+        Command::Validate { paths } => validate(paths),
+        Command::Timeline {
+            path,
+            serve_at,
+            tz,
+            course,
+        } => timeline(path, serve_at, tz.as_deref(), course.as_deref()),
+        Command::Cook {
+            path,
+            course,
+            serve_at,
+            tz,
+        } => cook(path, course.as_deref(), serve_at.as_deref(), tz.as_deref()),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// What requests made with an API key may do.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Only look, i.e. `GET` requests.
+    Read,
+    /// Anything the key's owner could do.
+    Write,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// An API key as its owner sees it, without the key itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiKeyDetails {
+    id: String,
+    name: String,
+    scope: Scope,
+    created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyDetails {
+    pub fn new(id: &str, name: &str, scope: Scope, created_at: DateTime<Utc>) -> Self {
+        Self {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            scope,
+            created_at,
+            last_used_at: None,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    pub fn created_at(&self) -> &DateTime<Utc> {
+        &self.created_at
+    }
+
+    pub fn last_used_at(&self) -> Option<&DateTime<Utc>> {
+        self.last_used_at.as_ref()
+    }
+
+    pub fn set_last_used_at(&mut self, at: DateTime<Utc>) {
+        self.last_used_at = Some(at);
+    }
+}
+
+/// What to call a new API key, and what it may do.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewApiKey {
+    name: String,
+    scope: Scope,
+}
+
+impl NewApiKey {
+    pub fn new(name: &str, scope: Scope) -> Self {
+        Self {
+            name: name.trim().to_owned(),
+            scope,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.trim()
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+}
+
+/// A newly made API key. The server keeps only a hash of `key`, so this is the only
+/// time it can be seen.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CreatedApiKey {
+    details: ApiKeyDetails,
+    key: String,
+}
+
+impl CreatedApiKey {
+    pub fn new(details: ApiKeyDetails, key: &str) -> Self {
+        Self {
+            details,
+            key: key.to_owned(),
+        }
+    }
+
+    pub fn details(&self) -> &ApiKeyDetails {
+        &self.details
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
@@ -0,0 +1,359 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::time::Duration;
+
+use itertools::Itertools;
+
+use crate::{Course, Stage, TimelineEntry};
+
+/// How much of the cook's attention a stage needs.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum StageKind {
+    /// Hands on, e.g. chopping onions.
+    ///
+    /// Stages with no kind given, including every stage saved before stages had kinds,
+    /// are taken to be active, so that a plan never counts on the cook being free when
+    /// they might not be.
+    #[default]
+    Active,
+    /// Cooking unattended, e.g. roasting.
+    Passive,
+    /// Waiting, which can safely be made longer, e.g. resting meat or proving dough.
+    Resting,
+}
+
+impl fmt::Display for StageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StageKind::Active => write!(f, "active"),
+            StageKind::Passive => write!(f, "passive"),
+            StageKind::Resting => write!(f, "resting"),
+        }
+    }
+}
+
+/// A stretch of time before serving, from `start` until `end`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Window {
+    start: Duration,
+    end: Duration,
+    /// The active stages, as `course: stage`, keeping the cook busy; empty when idle.
+    stages: Vec<String>,
+}
+
+impl Window {
+    pub fn start(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.start)
+    }
+
+    pub fn end(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.end)
+    }
+
+    pub fn stages(&self) -> &[String] {
+        &self.stages
+    }
+
+    fn overlaps(&self, other: &Window) -> bool {
+        self.start > other.end && other.start > self.end
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "T-{} to T-{}",
+            humantime::format_duration(self.start),
+            humantime::format_duration(self.end)
+        )?;
+        if !self.stages.is_empty() {
+            write!(f, ": {}", self.stages.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A timeline for a single cook, with when they are busy or free.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AttentionPlan {
+    timeline: Vec<TimelineEntry>,
+    busy: Vec<Window>,
+    idle: Vec<Window>,
+    /// Active stages which still overlap; each window lists the stages competing for the cook.
+    clashes: Vec<Window>,
+}
+
+impl AttentionPlan {
+    pub fn timeline(&self) -> &[TimelineEntry] {
+        &self.timeline
+    }
+
+    pub fn busy(&self) -> &[Window] {
+        &self.busy
+    }
+
+    pub fn idle(&self) -> &[Window] {
+        &self.idle
+    }
+
+    pub fn clashes(&self) -> &[Window] {
+        &self.clashes
+    }
+
+    /// Whether one cook can carry out every active stage in turn.
+    pub fn is_feasible(&self) -> bool {
+        self.clashes.is_empty()
+    }
+}
+
+/// The hands-on windows of a course, each stage moved `shifts[i]` earlier.
+fn active_windows(course: &Course, stages: &[&Stage], shifts: &[Duration]) -> Vec<Window> {
+    stages
+        .iter()
+        .zip(shifts)
+        .tuple_windows()
+        .filter(|((stage, _), _)| stage.kind == StageKind::Active)
+        .map(|((stage, shift), (next, next_shift))| Window {
+            start: stage.duration + *shift,
+            end: next.duration + *next_shift,
+            stages: vec![format!("{}: {}", course.details.name, stage.name)],
+        })
+        .filter(|window| window.start > window.end)
+        .collect()
+}
+
+/// Plan the meal so that one cook is never needed in two places at once.
+///
+/// Passive and resting stages leave the cook free, as does serving, which takes no time
+/// and appears once in the timeline for the whole meal. When active stages from different
+/// courses overlap, everything in a course before one of its resting stages may be done
+/// earlier, lengthening the rest, so courses are placed quickest first and then pulled
+/// earlier, one resting stage at a time, only as far as needed. Active stages which cannot
+/// be separated this way are reported as clashes.
+pub fn plan<'a>(courses: impl IntoIterator<Item = &'a Course>) -> AttentionPlan {
+    let courses = courses
+        .into_iter()
+        .sorted_by_key(|course| course.stages.iter().map(|s| s.duration).max())
+        .collect_vec();
+
+    let mut placed: Vec<Window> = Vec::new();
+    let mut timeline = Vec::new();
+    for course in &courses {
+        let stages = course
+            .stages
+            .iter()
+            .sorted_by_key(|stage| std::cmp::Reverse(stage.duration))
+            .collect_vec();
+        let mut shifts = vec![Duration::ZERO; stages.len()];
+
+        // Work back from serving; each resting stage lets everything before it move earlier.
+        for rest in (0..stages.len()).rev() {
+            if stages[rest].kind != StageKind::Resting {
+                continue;
+            }
+            let floor = shifts.get(rest + 1).copied().unwrap_or(Duration::ZERO);
+            let segment = &stages[..=rest];
+            let candidates = std::iter::once(floor).chain(
+                placed
+                    .iter()
+                    .cartesian_product(active_windows(
+                        course,
+                        segment,
+                        &vec![Duration::ZERO; segment.len()],
+                    ))
+                    .filter_map(|(p, w)| p.start.checked_sub(w.end))
+                    .filter(|shift| *shift > floor)
+                    .sorted()
+                    .dedup(),
+            );
+            let shift = candidates
+                .into_iter()
+                .find(|&shift| {
+                    active_windows(course, segment, &vec![shift; segment.len()])
+                        .iter()
+                        .all(|w| placed.iter().all(|p| !p.overlaps(w)))
+                })
+                .unwrap_or(floor);
+            debug!(
+                "Move {} before {} by {:?}",
+                course.details.name, stages[rest].name, shift
+            );
+            shifts[..=rest].iter_mut().for_each(|s| *s = shift);
+        }
+
+        placed.extend(active_windows(course, &stages, &shifts));
+        timeline.extend(
+            stages
+                .iter()
+                .zip(&shifts)
+                .map(|(stage, shift)| TimelineEntry {
+                    course: course.details.name.clone(),
+                    stage: stage.name.clone(),
+                    before_serving: stage.duration + *shift,
+                    at: None,
+                }),
+        );
+    }
+
+    AttentionPlan {
+        timeline: crate::meal::serve_once(timeline),
+        busy: busy_windows(&placed),
+        idle: idle_windows(&placed, &courses),
+        clashes: clashes(&placed),
+    }
+}
+
+/// Merge overlapping and touching active windows.
+fn busy_windows(active: &[Window]) -> Vec<Window> {
+    active
+        .iter()
+        .sorted_by_key(|w| std::cmp::Reverse(w.start))
+        .cloned()
+        .coalesce(|a, b| {
+            if b.start >= a.end {
+                Ok(Window {
+                    start: a.start,
+                    end: a.end.min(b.end),
+                    stages: a.stages.into_iter().chain(b.stages).collect(),
+                })
+            } else {
+                Err((a, b))
+            }
+        })
+        .collect()
+}
+
+/// The gaps between busy windows, from the first stage until serving.
+fn idle_windows(active: &[Window], courses: &[&Course]) -> Vec<Window> {
+    let first = courses
+        .iter()
+        .flat_map(|course| course.stages.iter().map(|s| s.duration))
+        .chain(active.iter().map(|w| w.start))
+        .max()
+        .unwrap_or(Duration::ZERO);
+    let busy = busy_windows(active);
+    std::iter::once(first)
+        .chain(busy.iter().flat_map(|w| [w.start, w.end]))
+        .chain(std::iter::once(Duration::ZERO))
+        .tuples()
+        .filter(|(start, end)| start > end)
+        .map(|(start, end)| Window {
+            start,
+            end,
+            stages: Vec::new(),
+        })
+        .collect()
+}
+
+/// Each active window which overlaps another, with every stage it competes with.
+fn clashes(active: &[Window]) -> Vec<Window> {
+    let clashing = active
+        .iter()
+        .filter(|w| active.iter().filter(|o| o.overlaps(w)).count() > 1)
+        .cloned()
+        .collect_vec();
+    busy_windows(&clashing)
+        .into_iter()
+        .filter(|w| w.stages.len() > 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CourseDetails;
+
+    fn course(name: &str, stages: Vec<Stage>) -> Course {
+        let mut course = Course::new(&CourseDetails::new(name, name));
+        for stage in stages {
+            course.add(stage);
+        }
+        course
+    }
+
+    #[test]
+    fn plan_reports_idle_and_busy_windows() {
+        let roast = course(
+            "Roast",
+            vec![
+                Stage::new("Season beef", "1h 10min"),
+                Stage::new("Beef in oven", "1h").with_kind(StageKind::Passive),
+            ],
+        );
+
+        let plan = plan(vec![&roast]);
+
+        assert!(plan.is_feasible());
+        assert_eq!(
+            plan.busy()
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec!["T-1h 10m to T-1h: Roast: Season beef"]
+        );
+        assert_eq!(
+            plan.idle()
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec!["T-1h to T-0s"]
+        );
+    }
+
+    #[test]
+    fn plan_moves_active_stage_before_a_rest() {
+        let gravy = course("Gravy", vec![Stage::new("Make gravy", "12min")]);
+        let sprouts = course(
+            "Sprouts",
+            vec![
+                Stage::new("Trim sprouts", "15min"),
+                Stage::new("Soak", "10min").with_kind(StageKind::Resting),
+                Stage::new("Boil", "5min").with_kind(StageKind::Passive),
+            ],
+        );
+
+        let plan = plan(vec![&gravy, &sprouts]);
+
+        assert!(plan.is_feasible(), "{:?}", plan.clashes());
+        assert_eq!(
+            plan.timeline()[0].to_string(),
+            "T-17m -- Sprouts: Trim sprouts"
+        );
+        assert_eq!(
+            plan.busy()
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>(),
+            vec!["T-17m to T-0s: Sprouts: Trim sprouts, Gravy: Make gravy"]
+        );
+    }
+
+    #[test]
+    fn plan_reports_clash_without_slack() {
+        let toast = course("Toast", vec![Stage::new("Butter toast", "2min")]);
+        let eggs = course("Eggs", vec![Stage::new("Scramble eggs", "3min")]);
+
+        let plan = plan(vec![&toast, &eggs]);
+
+        assert!(!plan.is_feasible());
+        assert_eq!(
+            plan.clashes()[0].to_string(),
+            "T-3m to T-0s: Eggs: Scramble eggs, Toast: Butter toast"
+        );
+        assert_eq!(
+            plan.timeline()
+                .iter()
+                .map(|entry| entry.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "T-3m -- Eggs: Scramble eggs",
+                "T-2m -- Toast: Butter toast",
+                "T-0s -- Serving"
+            ]
+        );
+    }
+}
@@ -0,0 +1,520 @@
+//! Recipes in [Cooklang](https://cooklang.org), e.g. `Boil the @pasta{500%g} for ~{10%minutes}.`
+//!
+//! Each step becomes a stage lasting as long as its timers, or no time at all if it has
+//! none, and ingredients and `>> key: value` lines become the course's metadata.
+//! Cookware is kept only as the text of its step.
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Course, CourseDetails, Ingredient, Metadata, Stage, MAX_STAGE_DURATION};
+
+/// Why a Cooklang recipe could not be read; `line` counts from 1.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum CooklangError {
+    /// A `{` with no `}` after it on the same line.
+    Unclosed { line: usize },
+    /// A timer whose length is not a number and a unit of time, e.g. `~{soon}`, or which
+    /// makes its step longer than `MAX_STAGE_DURATION`.
+    InvalidTimer { line: usize, timer: String },
+}
+
+impl fmt::Display for CooklangError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CooklangError::Unclosed { line } => write!(f, "line {}: '{{' is never closed", line),
+            CooklangError::InvalidTimer { line, timer } => {
+                write!(f, "line {}: invalid timer '{{{}}}'", line, timer)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CooklangError {}
+
+/// A step as it is read, before it becomes a stage.
+#[derive(Default)]
+struct Step {
+    text: String,
+    duration: Duration,
+}
+
+/// An ingredient, cookware or timer after its sigil: `name`, then an optional `{amount}`.
+struct Component<'a> {
+    name: &'a str,
+    amount: Option<&'a str>,
+    /// Bytes taken up after the sigil.
+    len: usize,
+}
+
+/// Names of more than one word end at the `{` of their amount, so long as nothing
+/// between looks like the end of a phrase or the start of another component.
+fn component(text: &str, line: usize) -> Result<Option<Component<'_>>, CooklangError> {
+    if let Some(open) = text.find('{') {
+        let name = &text[..open];
+        if !name.contains(|c: char| "@#~}.,;:!?".contains(c)) {
+            let close = open
+                + text[open..]
+                    .find('}')
+                    .ok_or(CooklangError::Unclosed { line })?;
+            return Ok(Some(Component {
+                name: name.trim(),
+                amount: Some(&text[open + 1..close]),
+                len: close + 1,
+            }));
+        }
+    }
+    let len = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    Ok((len > 0).then(|| Component {
+        name: &text[..len],
+        amount: None,
+        len,
+    }))
+}
+
+fn non_empty(text: &str) -> Option<&str> {
+    Some(text.trim()).filter(|text| !text.is_empty())
+}
+
+/// Split an amount such as `500%g` into its quantity and unit.
+fn amount(amount: &str) -> (Option<&str>, Option<&str>) {
+    let (quantity, unit) = amount.split_once('%').unwrap_or((amount, ""));
+    (non_empty(quantity), non_empty(unit))
+}
+
+/// A quantity such as `2`, `1.5` or `1/2`.
+fn number(quantity: &str) -> Option<f64> {
+    let number = match quantity.split_once('/') {
+        Some((a, b)) => a.trim().parse::<f64>().ok()? / b.trim().parse::<f64>().ok()?,
+        None => quantity.trim().parse().ok()?,
+    };
+    Some(number).filter(|number| number.is_finite() && *number >= 0.0)
+}
+
+/// How long `quantity` of `unit` lasts, e.g. `25` `minutes`; plurals such as `mins` are
+/// understood too. Anything longer than a stage may last is `None`.
+fn length(quantity: &str, unit: &str) -> Option<Duration> {
+    let unit = humantime::parse_duration(&format!("1{}", unit))
+        .or_else(|e| match unit.strip_suffix('s') {
+            Some(singular) => humantime::parse_duration(&format!("1{}", singular)),
+            None => Err(e),
+        })
+        .ok()?;
+    let millis = number(quantity)? * unit.as_millis() as f64;
+    Some(Duration::from_millis(millis.round() as u64))
+        .filter(|length| *length <= MAX_STAGE_DURATION)
+}
+
+/// `{quantity%unit}`, leaving out whatever is missing.
+fn braces(quantity: Option<&str>, unit: Option<&str>) -> String {
+    match (quantity, unit) {
+        (Some(quantity), Some(unit)) => format!("{{{}%{}}}", quantity, unit),
+        (Some(quantity), None) => format!("{{{}}}", quantity),
+        _ => "{}".to_owned(),
+    }
+}
+
+fn parse_line(
+    line: &str,
+    number: usize,
+    step: &mut Step,
+    metadata: &mut Metadata,
+) -> Result<(), CooklangError> {
+    let mut rest = line;
+    while let Some(at) = rest.find(['@', '#', '~']) {
+        step.text.push_str(&rest[..at]);
+        let sigil = &rest[at..at + 1];
+        let after = &rest[at + 1..];
+        let component = match component(after, number)? {
+            // Timers must have an amount; anything else is just text.
+            Some(component) if sigil != "~" || component.amount.is_some() => component,
+            _ => {
+                step.text.push_str(sigil);
+                rest = after;
+                continue;
+            }
+        };
+        let (quantity, unit) = amount(component.amount.unwrap_or_default());
+        match sigil {
+            "@" => {
+                let known = metadata
+                    .ingredients()
+                    .iter()
+                    .any(|ingredient| ingredient.name() == component.name);
+                // A later mention without an amount is the same ingredient again.
+                if quantity.is_some() || !known {
+                    let mut ingredient = Ingredient::new(component.name);
+                    if let Some(quantity) = quantity {
+                        ingredient = ingredient.with_quantity(quantity);
+                    }
+                    if let Some(unit) = unit {
+                        ingredient = ingredient.with_unit(unit);
+                    }
+                    *metadata = std::mem::take(metadata).with_ingredient(ingredient);
+                }
+                step.text.push_str(component.name);
+            }
+            "#" => step.text.push_str(component.name),
+            _ => {
+                let timer = || CooklangError::InvalidTimer {
+                    line: number,
+                    timer: component.amount.unwrap_or_default().to_owned(),
+                };
+                let (quantity, unit) = quantity.zip(unit).ok_or_else(timer)?;
+                // A step as long as all its timers must still make a stage.
+                step.duration = length(quantity, unit)
+                    .and_then(|length| step.duration.checked_add(length))
+                    .filter(|duration| *duration <= MAX_STAGE_DURATION)
+                    .ok_or_else(timer)?;
+                step.text.push_str(&format!("{} {}", quantity, unit));
+            }
+        }
+        rest = &after[component.len..];
+    }
+    step.text.push_str(rest);
+    step.text.push(' ');
+    Ok(())
+}
+
+/// Drop `[- block -]` comments, keeping their line breaks so line numbers still count,
+/// and `-- line` comments.
+fn strip_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("[-") {
+        stripped.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("-]")
+            .map_or(rest.len(), |end| start + end + 2);
+        stripped.extend(rest[start..end].chars().filter(|c| *c == '\n'));
+        rest = &rest[end..];
+    }
+    stripped.push_str(rest);
+    stripped
+        .lines()
+        .map(|line| line.split_once("--").map_or(line, |(line, _)| line))
+        .join("\n")
+}
+
+/// Read a recipe as a course called `name`, unless the recipe gives a `title`.
+pub fn parse(name: &str, text: &str) -> Result<Course, CooklangError> {
+    let mut metadata = Metadata::default();
+    let mut steps = vec![Step::default()];
+    let mut title = name.to_owned();
+    for (index, line) in strip_comments(text).lines().enumerate() {
+        let line = line.trim();
+        if let Some(field) = line.strip_prefix(">>") {
+            let (key, value) = field.split_once(':').unwrap_or((field, ""));
+            match key.trim() {
+                "title" => title = value.trim().to_owned(),
+                key => metadata = metadata.with_field(key, value.trim()),
+            }
+        } else if line.is_empty() {
+            // A blank line ends the step.
+            steps.push(Step::default());
+        } else {
+            parse_line(line, index + 1, steps.last_mut().unwrap(), &mut metadata)?;
+        }
+    }
+
+    let stages = steps
+        .into_iter()
+        .filter_map(|step| {
+            let name = step.text.split_whitespace().join(" ");
+            (!name.is_empty()).then(|| Stage {
+                name,
+                duration: step.duration,
+                ..Stage::default()
+            })
+        })
+        .collect_vec();
+    let mut course = Course::from_lengths(&CourseDetails::new("", &title), stages);
+    course.set_metadata(metadata);
+    Ok(course)
+}
+
+/// A length of time written out in prose, e.g. `10 minutes`.
+pub(crate) struct TimePhrase<'a> {
+    /// Byte range of the phrase within its text.
+    pub start: usize,
+    pub end: usize,
+    pub quantity: &'a str,
+    pub unit: &'a str,
+    pub length: Duration,
+}
+
+/// Every number followed by a unit of time in `text`.
+pub(crate) fn time_phrases(text: &str) -> Vec<TimePhrase<'_>> {
+    let words = text
+        .split_whitespace()
+        .map(|word| {
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            (start, word)
+        })
+        .collect_vec();
+    words
+        .iter()
+        .tuple_windows()
+        .filter_map(|((start, quantity), (unit_start, unit))| {
+            let unit = unit.trim_end_matches(|c: char| !c.is_alphanumeric());
+            if unit.is_empty() || !unit.chars().all(char::is_alphabetic) {
+                return None;
+            }
+            Some(TimePhrase {
+                start: *start,
+                end: unit_start + unit.len(),
+                quantity,
+                unit,
+                length: length(quantity, unit)?,
+            })
+        })
+        .collect()
+}
+
+/// The largest whole unit which a timer of `duration` can be written in.
+fn timer(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if duration.subsec_millis() > 0 {
+        braces(Some(&duration.as_secs_f64().to_string()), Some("seconds"))
+    } else if seconds.is_multiple_of(3600) {
+        braces(Some(&(seconds / 3600).to_string()), Some("hours"))
+    } else if seconds.is_multiple_of(60) {
+        braces(Some(&(seconds / 60).to_string()), Some("minutes"))
+    } else {
+        braces(Some(&seconds.to_string()), Some("seconds"))
+    }
+}
+
+/// Mark the phrases of `text` which give its `duration`, such as `10 minutes`, as timers,
+/// or add a timer at the end if they do not add up to it.
+fn mark_timers(text: &str, duration: Duration) -> String {
+    let phrases = time_phrases(text);
+    let total: Duration = phrases.iter().map(|phrase| phrase.length).sum();
+
+    let mut text = text.to_owned();
+    if !phrases.is_empty() && total == duration {
+        for TimePhrase {
+            start,
+            end,
+            quantity,
+            unit,
+            ..
+        } in phrases.iter().rev()
+        {
+            text.replace_range(
+                start..end,
+                &format!("~{}", braces(Some(quantity), Some(unit))),
+            );
+        }
+    } else if duration > Duration::ZERO {
+        text.push_str(&format!(" ~{}", timer(duration)));
+    }
+    text
+}
+
+/// Mark the first mention of `ingredient` in `text` which is not already marked up,
+/// returning `false` if there is none.
+fn mark_ingredient(text: &mut String, ingredient: &Ingredient) -> bool {
+    let name = ingredient.name();
+    let mut from = 0;
+    while let Some(found) = text[from..].find(name) {
+        let (start, end) = (from + found, from + found + name.len());
+        let before = text[..start].chars().next_back();
+        let after = text[end..].chars().next();
+        let in_braces = text[..start].rfind('{') > text[..start].rfind('}');
+        if before.is_none_or(|c| !c.is_alphanumeric() && !"@#~".contains(c))
+            && after.is_none_or(|c| !c.is_alphanumeric())
+            && !in_braces
+        {
+            let marked = format!(
+                "@{}{}",
+                name,
+                braces(ingredient.quantity(), ingredient.unit())
+            );
+            text.replace_range(start..end, &marked);
+            return true;
+        }
+        from = end;
+    }
+    false
+}
+
+/// Write a course as a recipe, each stage a step.
+///
+/// Ingredients are marked where their stage mentions them; any which are not mentioned
+/// are gathered in a first step.
+pub fn write(course: &Course) -> String {
+    let mut recipe = format!(">> title: {}\n", course.details().name());
+    for (key, value) in course.metadata().fields() {
+        recipe.push_str(&format!(">> {}: {}\n", key, value));
+    }
+
+    let mut steps = course
+        .stages()
+        .map(|stage| mark_timers(stage.name(), *stage.duration().get_ref()))
+        .collect_vec();
+    let mut unmentioned = Vec::new();
+    for ingredient in course.metadata().ingredients() {
+        if !steps
+            .iter_mut()
+            .any(|step| mark_ingredient(step, ingredient))
+        {
+            unmentioned.push(format!(
+                "@{}{}",
+                ingredient.name(),
+                braces(ingredient.quantity(), ingredient.unit())
+            ));
+        }
+    }
+    if !unmentioned.is_empty() {
+        steps.insert(0, format!("Gather {}.", unmentioned.join(", ")));
+    }
+
+    for step in steps {
+        recipe.push('\n');
+        recipe.push_str(&step);
+        recipe.push('\n');
+    }
+    recipe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECIPE: &str = "\
+>> servings: 2
+-- Weeknight standby
+Bring a #large pot{} of water to the boil. [- salted! -]
+
+Cook the @spaghetti{200%g} for ~{10%minutes},
+then drain.
+
+Toss with @grated parmesan{50%g} and @pepper.
+";
+
+    fn stages(course: &Course) -> Vec<(String, String)> {
+        course
+            .stages()
+            .map(|stage| (stage.name().to_owned(), stage.duration().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn steps_become_stages_and_ingredients_metadata() {
+        let course = parse("Pasta", RECIPE).unwrap();
+
+        assert_eq!(course.details().name(), "Pasta");
+        assert_eq!(
+            stages(&course),
+            vec![
+                (
+                    "Bring a large pot of water to the boil.".to_owned(),
+                    "0s".to_owned()
+                ),
+                (
+                    "Cook the spaghetti for 10 minutes, then drain.".to_owned(),
+                    "10m".to_owned()
+                ),
+                (
+                    "Toss with grated parmesan and pepper.".to_owned(),
+                    "0s".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(
+            course.metadata(),
+            &Metadata::default()
+                .with_ingredient(
+                    Ingredient::new("spaghetti")
+                        .with_quantity("200")
+                        .with_unit("g")
+                )
+                .with_ingredient(
+                    Ingredient::new("grated parmesan")
+                        .with_quantity("50")
+                        .with_unit("g")
+                )
+                .with_ingredient(Ingredient::new("pepper"))
+                .with_field("servings", "2")
+        );
+    }
+
+    #[test]
+    fn timers_in_one_step_add_up() {
+        let course = parse("Eggs", "Boil ~{1/2%hour}, then rest ~{90%sec}.").unwrap();
+
+        assert_eq!(
+            stages(&course),
+            vec![(
+                "Boil 1/2 hour, then rest 90 sec.".to_owned(),
+                "31m 30s".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn bad_timers_are_reported_with_their_line() {
+        assert_eq!(
+            parse("Toast", "Toast it.\n\nWait ~{a while%minutes}.").unwrap_err(),
+            CooklangError::InvalidTimer {
+                line: 3,
+                timer: "a while%minutes".to_owned()
+            }
+        );
+        assert_eq!(
+            parse("Toast", "Wait ~{5%minutes").unwrap_err(),
+            CooklangError::Unclosed { line: 1 }
+        );
+    }
+
+    #[test]
+    fn overlong_timers_are_invalid() {
+        assert_eq!(
+            parse("Toast", "Wait ~{1000000%hours}.").unwrap_err(),
+            CooklangError::InvalidTimer {
+                line: 1,
+                timer: "1000000%hours".to_owned()
+            }
+        );
+        let many = "Wait ~{1e300%hours}. ".repeat(1100);
+        assert!(matches!(
+            parse("Toast", &many).unwrap_err(),
+            CooklangError::InvalidTimer { line: 1, .. }
+        ));
+        let years = "Wait ~{1000%days}. ".repeat(2);
+        assert!(parse("Toast", &years).is_err());
+    }
+
+    #[test]
+    fn written_recipe_reads_back_the_same() {
+        let course = parse("Pasta", RECIPE).unwrap();
+        let mut extra = course.clone();
+        extra.set_metadata(
+            course
+                .metadata()
+                .clone()
+                .with_ingredient(Ingredient::new("basil")),
+        );
+
+        let written = write(&extra);
+        let read = parse("Ignored", &written).unwrap();
+
+        assert!(written.contains("Cook the @spaghetti{200%g} for ~{10%minutes}, then drain."));
+        assert_eq!(read.details().name(), "Pasta");
+        let mut ingredients = read.metadata().ingredients().to_vec();
+        ingredients.sort_by_key(|ingredient| ingredient.name().to_owned());
+        let mut expected = extra.metadata().ingredients().to_vec();
+        expected.sort_by_key(|ingredient| ingredient.name().to_owned());
+        assert_eq!(ingredients, expected);
+        assert_eq!(read.metadata().fields(), extra.metadata().fields());
+        assert_eq!(stages(&read)[1..], stages(&course)[..]);
+        assert_eq!(stages(&read)[0].0, "Gather basil.");
+    }
+}
@@ -0,0 +1,151 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use crate::{
+    CooklangError, CredentialsError, GraphError, ResourceConflict, ScheduleError, SchemaOrgError,
+    SessionError, StageError, TextError,
+};
+
+/// The body of every failed API response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiError {
+    /// The HTTP status code, repeated so the body stands on its own.
+    code: u16,
+    message: String,
+    /// Machine readable detail, e.g. the `StageError` behind a 422.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn new(code: u16, message: &str) -> Self {
+        Self {
+            code,
+            message: message.to_owned(),
+            details: None,
+        }
+    }
+
+    pub fn bad_request(message: &str) -> Self {
+        Self::new(400, message)
+    }
+
+    pub fn unauthorized(message: &str) -> Self {
+        Self::new(401, message)
+    }
+
+    pub fn not_found(message: &str) -> Self {
+        Self::new(404, message)
+    }
+
+    pub fn internal(message: &str) -> Self {
+        Self::new(500, message)
+    }
+
+    pub fn with_details(mut self, details: &impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The details as `T`, if there are any and they are one.
+    pub fn details<T: DeserializeOwned>(&self) -> Option<T> {
+        serde_json::from_value(self.details.clone()?).ok()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<StageError> for ApiError {
+    fn from(e: StageError) -> Self {
+        Self::new(422, &e.to_string()).with_details(&e)
+    }
+}
+
+impl From<CredentialsError> for ApiError {
+    fn from(e: CredentialsError) -> Self {
+        Self::new(422, &e.to_string()).with_details(&e)
+    }
+}
+
+impl From<CooklangError> for ApiError {
+    fn from(e: CooklangError) -> Self {
+        Self::new(422, &e.to_string()).with_details(&e)
+    }
+}
+
+impl From<SchemaOrgError> for ApiError {
+    fn from(e: SchemaOrgError) -> Self {
+        Self::new(422, &e.to_string()).with_details(&e)
+    }
+}
+
+impl From<TextError> for ApiError {
+    fn from(e: TextError) -> Self {
+        Self::new(422, &e.to_string()).with_details(&e)
+    }
+}
+
+impl From<GraphError> for ApiError {
+    fn from(e: GraphError) -> Self {
+        Self::new(422, &e.to_string())
+    }
+}
+
+impl From<ScheduleError> for ApiError {
+    fn from(e: ScheduleError) -> Self {
+        Self::bad_request(&e.to_string())
+    }
+}
+
+impl From<ResourceConflict> for ApiError {
+    fn from(conflict: ResourceConflict) -> Self {
+        Self::new(409, &conflict.to_string()).with_details(&conflict)
+    }
+}
+
+impl From<SessionError> for ApiError {
+    fn from(e: SessionError) -> Self {
+        match e {
+            SessionError::ExtensionTooLong | SessionError::OutOfRange => {
+                Self::bad_request(&e.to_string())
+            }
+            _ => Self::new(409, &e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_error_round_trips_through_details() {
+        let error = StageError::InvalidFormat {
+            input: "toast".to_owned(),
+        };
+
+        let api: ApiError =
+            serde_json::from_str(&serde_json::to_string(&ApiError::from(error.clone())).unwrap())
+                .unwrap();
+
+        assert_eq!(api.code(), 422);
+        assert_eq!(api.message(), error.to_string());
+        assert_eq!(api.details::<StageError>(), Some(error));
+    }
+}
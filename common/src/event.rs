@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{CourseDetails, SessionState};
+
+/// A change on the server, streamed to every client so they stay in step.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEvent {
+    CourseCreated {
+        course: CourseDetails,
+    },
+    /// The course was renamed or its stages changed.
+    CourseUpdated {
+        course: CourseDetails,
+    },
+    CourseDeleted {
+        id: String,
+    },
+    /// A session was started, paused, resumed, skipped or extended.
+    SessionUpdated {
+        session: SessionState,
+    },
+    SessionEnded {
+        id: String,
+    },
+    /// The cook should begin `stage`.
+    StageStarted {
+        session: SessionState,
+        stage: String,
+    },
+    /// The time for `stage` is up.
+    StageDue {
+        session: SessionState,
+        stage: String,
+    },
+}
+
+impl ServerEvent {
+    /// The id of the course this event changed, if it changed one.
+    pub fn course_id(&self) -> Option<&str> {
+        match self {
+            ServerEvent::CourseCreated { course } | ServerEvent::CourseUpdated { course } => {
+                Some(course.id())
+            }
+            ServerEvent::CourseDeleted { id } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The stages of a session which fell due or started between two of its states.
+    ///
+    /// `before` should be the state last seen rather than one taken from the session as
+    /// it is now, so that stages reached by skipping or changing the session are reported.
+    pub fn stage_changes(before: &SessionState, after: &SessionState) -> Vec<Self> {
+        if before.current_index() == after.current_index() {
+            return Vec::new();
+        }
+        let due = before.current().map(|stage| ServerEvent::StageDue {
+            session: after.clone(),
+            stage: stage.name().to_owned(),
+        });
+        let started = after.current().map(|stage| ServerEvent::StageStarted {
+            session: after.clone(),
+            stage: stage.name().to_owned(),
+        });
+        due.into_iter().chain(started).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Course, Session, Stage};
+
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn stage_changes_reports_due_then_started() {
+        let mut course = Course::new(&CourseDetails::new("0", "Toast"));
+        for stage in Stage::chain(vec![
+            Stage::new("Bread in toaster", "2min"),
+            Stage::new("Butter", "1min"),
+        ]) {
+            course.add(stage);
+        }
+        let start = "2022-01-10T19:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut session = Session::starting("s", &course, start).unwrap();
+        let at = |seconds| start + chrono::Duration::seconds(seconds);
+
+        let names = |events: Vec<ServerEvent>| {
+            events
+                .into_iter()
+                .map(|event| match event {
+                    ServerEvent::StageDue { stage, .. } => format!("due: {}", stage),
+                    ServerEvent::StageStarted { stage, .. } => format!("started: {}", stage),
+                    other => format!("{:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+        let changes = |session: &Session, since, now| {
+            names(ServerEvent::stage_changes(
+                &session.state(at(since)),
+                &session.state(at(now)),
+            ))
+        };
+        assert!(changes(&session, 10, 20).is_empty());
+        assert_eq!(
+            changes(&session, 119, 120),
+            vec!["due: Bread in toaster", "started: Butter"]
+        );
+        assert_eq!(changes(&session, 179, 180), vec!["due: Butter"]);
+
+        let before = session.state(at(30));
+        session.skip(at(30)).unwrap();
+        assert_eq!(
+            names(ServerEvent::stage_changes(&before, &session.state(at(31)))),
+            vec!["due: Bread in toaster", "started: Butter"]
+        );
+    }
+
+    #[test]
+    fn stage_changes_ignores_extending_a_stage() {
+        let mut course = Course::new(&CourseDetails::new("0", "Toast"));
+        for stage in Stage::chain(vec![
+            Stage::new("Bread in toaster", "2min"),
+            Stage::new("Butter", "1min"),
+        ]) {
+            course.add(stage);
+        }
+        let start = "2022-01-10T19:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut session = Session::starting("s", &course, start).unwrap();
+        let now = start + chrono::Duration::seconds(30);
+
+        let before = session.state(now);
+        session
+            .extend(now, std::time::Duration::from_secs(60))
+            .unwrap();
+
+        assert!(ServerEvent::stage_changes(&before, &session.state(now)).is_empty());
+    }
+}
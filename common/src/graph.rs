@@ -0,0 +1,458 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Course, Stage};
+
+/// Stages with explicit predecessors, forming a directed acyclic graph.
+///
+/// Unlike the flat list held by a `Course`, each stage here records only its own length;
+/// when it starts is derived from what must happen before and after it.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StageGraph {
+    stages: Vec<GraphStage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GraphStage {
+    name: String,
+    /// How long the stage lasts, as text like a `NewStage`'s, e.g. `1h 20m`.
+    #[serde(with = "text_duration")]
+    duration: Duration,
+    #[serde(default)]
+    after: Vec<String>,
+}
+
+/// A graph stage placed as late as possible before serving.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlannedStage {
+    name: String,
+    /// How long before serving the stage must start.
+    start: Duration,
+    /// How long before serving the stage finishes.
+    end: Duration,
+    /// How much earlier the stage could start without delaying anything else.
+    slack: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphError {
+    DuplicateStage(String),
+    UnknownStage(String),
+    /// The named stages depend on each other in a loop.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::DuplicateStage(name) => write!(f, "stage '{}' is defined twice", name),
+            GraphError::UnknownStage(name) => write!(f, "unknown stage '{}'", name),
+            GraphError::Cycle(names) => {
+                write!(f, "stages depend on each other: {}", names.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+mod text_duration {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&humantime::format_duration(*duration))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        crate::parse_duration(&text).map_err(de::Error::custom)
+    }
+}
+
+impl GraphStage {
+    pub fn new(name: &str, duration: Duration, after: &[&str]) -> Self {
+        Self {
+            name: name.to_owned(),
+            duration,
+            after: after.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn duration(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.duration)
+    }
+
+    /// Names of the stages which must finish before this one starts.
+    pub fn after(&self) -> &[String] {
+        &self.after
+    }
+}
+
+impl PlannedStage {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn start(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.start)
+    }
+
+    pub fn end(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.end)
+    }
+
+    pub fn slack(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.slack)
+    }
+
+    /// Whether any delay to this stage delays serving.
+    pub fn is_critical(&self) -> bool {
+        self.slack.is_zero()
+    }
+}
+
+impl StageGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a stage, which must come after the already-added stages named in its `after` list.
+    pub fn add(&mut self, stage: GraphStage) -> Result<(), GraphError> {
+        debug!("Add {:?} to graph", stage);
+        if self.index(&stage.name).is_some() {
+            return Err(GraphError::DuplicateStage(stage.name));
+        }
+        if let Some(unknown) = stage.after.iter().find(|name| self.index(name).is_none()) {
+            return Err(GraphError::UnknownStage(unknown.clone()));
+        }
+        self.stages.push(stage);
+        Ok(())
+    }
+
+    pub fn stages(&self) -> &[GraphStage] {
+        &self.stages
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.stages.iter().position(|stage| stage.name == name)
+    }
+
+    fn predecessors(&self) -> Result<Vec<Vec<usize>>, GraphError> {
+        self.stages
+            .iter()
+            .map(|stage| {
+                stage
+                    .after
+                    .iter()
+                    .map(|name| {
+                        self.index(name)
+                            .ok_or_else(|| GraphError::UnknownStage(name.clone()))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Stage indices in dependency order (Kahn's algorithm), or the stages caught in a cycle.
+    ///
+    /// A deserialized graph has not been through `add`, so may reference itself or name
+    /// two stages the same.
+    fn topological_order(&self) -> Result<Vec<usize>, GraphError> {
+        if let Some(twice) = self
+            .stages
+            .iter()
+            .enumerate()
+            .find(|(i, stage)| self.index(&stage.name) != Some(*i))
+        {
+            return Err(GraphError::DuplicateStage(twice.1.name.clone()));
+        }
+        let predecessors = self.predecessors()?;
+        let mut successors = vec![Vec::new(); self.stages.len()];
+        let mut pending = vec![0; self.stages.len()];
+        for (stage, before) in predecessors.iter().enumerate() {
+            pending[stage] = before.len();
+            for &b in before {
+                successors[b].push(stage);
+            }
+        }
+        let mut ready = (0..self.stages.len())
+            .filter(|&stage| pending[stage] == 0)
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(self.stages.len());
+        while let Some(stage) = ready.pop_front() {
+            order.push(stage);
+            for &next in &successors[stage] {
+                pending[next] -= 1;
+                if pending[next] == 0 {
+                    ready.push_back(next);
+                }
+            }
+        }
+        if order.len() < self.stages.len() {
+            let cycle = (0..self.stages.len())
+                .filter(|&stage| pending[stage] > 0)
+                .map(|stage| self.stages[stage].name.clone())
+                .collect();
+            return Err(GraphError::Cycle(cycle));
+        }
+        Ok(order)
+    }
+
+    /// Names of the stages in an order which respects every dependency.
+    pub fn topological_sort(&self) -> Result<Vec<&str>, GraphError> {
+        Ok(self
+            .topological_order()?
+            .into_iter()
+            .map(|stage| self.stages[stage].name.as_str())
+            .collect())
+    }
+
+    /// Place every stage as late as possible, so that the final stages finish at serving.
+    ///
+    /// The result is ordered by start, earliest first. Stages with zero slack are
+    /// critical, see `critical_path`; any stages whose `start`..`end` windows overlap can run in parallel.
+    pub fn plan(&self) -> Result<Vec<PlannedStage>, GraphError> {
+        let order = self.topological_order()?;
+        let predecessors = self.predecessors()?;
+
+        // Backward pass: a stage must end before any of its successors start.
+        let mut end: Vec<Option<Duration>> = vec![None; self.stages.len()];
+        let mut start = vec![Duration::ZERO; self.stages.len()];
+        for &stage in order.iter().rev() {
+            let finish = end[stage].unwrap_or(Duration::ZERO);
+            start[stage] = finish + self.stages[stage].duration;
+            for &before in &predecessors[stage] {
+                end[before] = Some(end[before].map_or(start[stage], |e| e.max(start[stage])));
+            }
+        }
+        let total = start.iter().copied().max().unwrap_or(Duration::ZERO);
+
+        // Forward pass: a stage can begin as soon as all of its predecessors are done.
+        let mut earliest = vec![Duration::ZERO; self.stages.len()];
+        for &stage in &order {
+            earliest[stage] = predecessors[stage]
+                .iter()
+                .map(|&before| earliest[before] + self.stages[before].duration)
+                .max()
+                .unwrap_or(Duration::ZERO);
+        }
+
+        let mut planned = order
+            .into_iter()
+            .map(|stage| PlannedStage {
+                name: self.stages[stage].name.clone(),
+                start: start[stage],
+                end: end[stage].unwrap_or(Duration::ZERO),
+                slack: total - earliest[stage] - start[stage],
+            })
+            .collect::<Vec<_>>();
+        planned.sort_by_key(|stage| std::cmp::Reverse(stage.start));
+        Ok(planned)
+    }
+
+    /// A longest chain of dependent stages, which determines the total preparation time,
+    /// from the first stage to one finishing at serving.
+    ///
+    /// Where parallel stages are equally long, only one of them is on the path.
+    pub fn critical_path(&self) -> Result<Vec<PlannedStage>, GraphError> {
+        let plan = self.plan()?;
+        let planned = |name: &str| plan.iter().find(|stage| stage.name == name);
+        let mut path: Vec<PlannedStage> = Vec::new();
+        // The plan starts with the earliest stage, and an earliest stage is critical.
+        let mut next = plan.first().filter(|stage| stage.is_critical());
+        while let Some(stage) = next {
+            path.push(stage.clone());
+            next = self
+                .stages
+                .iter()
+                .filter(|after| after.after.contains(&stage.name))
+                .filter_map(|after| planned(&after.name))
+                .find(|after| after.is_critical() && after.start == stage.end);
+        }
+        Ok(path)
+    }
+}
+
+impl Course {
+    /// Add the stages of `graph`, each starting when `StageGraph::plan` places it and
+    /// remembering which stages it comes after. Returns the plan.
+    pub fn add_graph(&mut self, graph: &StageGraph) -> Result<Vec<PlannedStage>, GraphError> {
+        let plan = graph.plan()?;
+        let mut ids = HashMap::new();
+        for planned in &plan {
+            self.add(Stage {
+                name: planned.name.clone(),
+                duration: planned.start,
+                ..Stage::default()
+            });
+            let id = self.stages.last().map_or(0, |stage| stage.id);
+            ids.insert(planned.name.as_str(), id);
+        }
+        for stage in &graph.stages {
+            let after = stage.after.iter().map(|name| ids[name.as_str()]).collect();
+            if let Some(added) = self
+                .stages
+                .iter_mut()
+                .find(|s| s.id == ids[stage.name.as_str()])
+            {
+                added.after = after;
+            }
+        }
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mins(m: u64) -> Duration {
+        Duration::from_secs(m * 60)
+    }
+
+    fn bechamel() -> StageGraph {
+        let mut graph = StageGraph::new();
+        graph
+            .add(GraphStage::new("Make roux", mins(5), &[]))
+            .unwrap();
+        graph
+            .add(GraphStage::new("Warm milk", mins(3), &[]))
+            .unwrap();
+        graph
+            .add(GraphStage::new(
+                "Combine",
+                mins(10),
+                &["Make roux", "Warm milk"],
+            ))
+            .unwrap();
+        graph
+    }
+
+    #[test]
+    fn plan_runs_independent_stages_in_parallel() {
+        let plan = bechamel().plan().unwrap();
+        let summary = plan
+            .iter()
+            .map(|p| (p.name(), p.start.as_secs() / 60, p.slack.as_secs() / 60))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("Make roux", 15, 0),
+                ("Warm milk", 13, 2),
+                ("Combine", 10, 0)
+            ]
+        );
+        let critical = bechamel().critical_path().unwrap();
+        assert_eq!(
+            critical.iter().map(PlannedStage::name).collect::<Vec<_>>(),
+            vec!["Make roux", "Combine"]
+        );
+    }
+
+    #[test]
+    fn course_keeps_graph_edges() {
+        let mut course = Course::new(&crate::CourseDetails::new("0", "Bechamel"));
+
+        let plan = course.add_graph(&bechamel()).unwrap();
+
+        assert_eq!(plan.len(), 3);
+        let id = |name: &str| {
+            course
+                .raw_stages()
+                .iter()
+                .find(|stage| stage.name() == name)
+                .unwrap()
+                .id()
+        };
+        let combine = course.stage(id("Combine")).unwrap();
+        assert_eq!(combine.after(), &[id("Make roux"), id("Warm milk")]);
+        assert_eq!(combine.duration().to_string(), "10m");
+        assert_eq!(
+            course.stages().next().map(|stage| stage.name().to_owned()),
+            Some("Make roux".to_owned())
+        );
+    }
+
+    #[test]
+    fn graph_durations_are_text() {
+        let stage: GraphStage =
+            serde_json::from_str(r#"{"name": "Bake", "duration": "1h 20m"}"#).unwrap();
+        assert_eq!(stage.duration, mins(80));
+        assert_eq!(
+            serde_json::to_string(&stage).unwrap(),
+            r#"{"name":"Bake","duration":"1h 20m","after":[]}"#
+        );
+        assert!(
+            serde_json::from_str::<GraphStage>(r#"{"name": "Bake", "duration": "soon"}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn add_rejects_unknown_predecessor() {
+        let mut graph = bechamel();
+        assert_eq!(
+            graph.add(GraphStage::new("Bake", mins(20), &["Grate cheese"])),
+            Err(GraphError::UnknownStage("Grate cheese".to_owned()))
+        );
+    }
+
+    #[test]
+    fn critical_path_is_one_chain() {
+        let graph: StageGraph = serde_json::from_str(
+            r#"{"stages": [
+                {"name": "Make roux", "duration": "5m"},
+                {"name": "Warm milk", "duration": "5m"},
+                {"name": "Combine", "duration": "10m", "after": ["Make roux", "Warm milk"]}
+            ]}"#,
+        )
+        .unwrap();
+        let critical = graph.critical_path().unwrap();
+        assert_eq!(
+            critical.iter().map(PlannedStage::name).collect::<Vec<_>>(),
+            vec!["Make roux", "Combine"]
+        );
+    }
+
+    #[test]
+    fn plan_rejects_deserialized_duplicates() {
+        let graph: StageGraph = serde_json::from_str(
+            r#"{"stages": [
+                {"name": "A", "duration": "1m"},
+                {"name": "A", "duration": "2m"},
+                {"name": "B", "duration": "1m", "after": ["A"]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            graph.plan(),
+            Err(GraphError::DuplicateStage("A".to_owned()))
+        );
+    }
+
+    #[test]
+    fn plan_detects_cycles() {
+        let graph: StageGraph = serde_json::from_str(
+            r#"{"stages": [
+                {"name": "A", "duration": "1m", "after": ["B"]},
+                {"name": "B", "duration": "1m", "after": ["A"]},
+                {"name": "C", "duration": "1m"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            graph.plan(),
+            Err(GraphError::Cycle(vec!["A".to_owned(), "B".to_owned()]))
+        );
+    }
+}
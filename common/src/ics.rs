@@ -0,0 +1,160 @@
+use chrono::{DateTime, Utc};
+
+use std::fmt;
+
+use crate::ScheduledStage;
+
+/// A stage as an iCalendar `VEVENT`, with a reminder when it starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    /// Stable across exports, so calendar apps update events rather than duplicate them.
+    uid: String,
+    summary: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+/// An iCalendar (RFC 5545) document, written out by `Display`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calendar {
+    name: String,
+    /// When the calendar was generated.
+    stamp: DateTime<Utc>,
+    events: Vec<CalendarEvent>,
+}
+
+const PRODUCT: &str = "-//course_planner//Course Planner//EN";
+/// Lines longer than this many octets are folded.
+const LINE_LIMIT: usize = 75;
+
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value.
+fn text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Write a content line, folding it so no line is longer than `LINE_LIMIT` octets.
+fn line(f: &mut fmt::Formatter<'_>, name: &str, value: &str) -> fmt::Result {
+    let content = format!("{}:{}", name, value);
+    let mut width = 0;
+    for c in content.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            write!(f, "\r\n ")?;
+            // The leading space of a continuation line counts towards its length.
+            width = 1;
+        }
+        write!(f, "{}", c)?;
+        width += c.len_utf8();
+    }
+    write!(f, "\r\n")
+}
+
+impl Calendar {
+    pub fn new(name: &str, stamp: DateTime<Utc>) -> Self {
+        Self {
+            name: name.to_owned(),
+            stamp,
+            events: Vec::new(),
+        }
+    }
+
+    /// Add an event for each stage of a scheduled course.
+    ///
+    /// `uid` identifies this schedule; each stage's UID is built from it and the stage's id,
+    /// so adding, removing or moving other stages leaves it unchanged.
+    pub fn add_schedule(&mut self, uid: &str, course: &str, stages: &[ScheduledStage]) {
+        self.events.extend(stages.iter().map(|stage| CalendarEvent {
+            uid: format!("{}-{}@course-planner", uid, stage.id()),
+            summary: format!("{}: {}", course, stage.name()),
+            start: stage.start().with_timezone(&Utc),
+            end: stage.end().with_timezone(&Utc),
+        }));
+    }
+
+    pub fn events(&self) -> &[CalendarEvent] {
+        &self.events
+    }
+}
+
+impl fmt::Display for Calendar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        line(f, "BEGIN", "VCALENDAR")?;
+        line(f, "VERSION", "2.0")?;
+        line(f, "PRODID", PRODUCT)?;
+        line(f, "CALSCALE", "GREGORIAN")?;
+        line(f, "X-WR-CALNAME", &text(&self.name))?;
+        for event in &self.events {
+            line(f, "BEGIN", "VEVENT")?;
+            line(f, "UID", &event.uid)?;
+            line(f, "DTSTAMP", &timestamp(&self.stamp))?;
+            line(f, "DTSTART", &timestamp(&event.start))?;
+            // An event without an end lasts no time at all, e.g. serving.
+            if event.end > event.start {
+                line(f, "DTEND", &timestamp(&event.end))?;
+            }
+            line(f, "SUMMARY", &text(&event.summary))?;
+            line(f, "BEGIN", "VALARM")?;
+            line(f, "ACTION", "DISPLAY")?;
+            line(f, "DESCRIPTION", &text(&event.summary))?;
+            line(f, "TRIGGER", "PT0S")?;
+            line(f, "END", "VALARM")?;
+            line(f, "END", "VEVENT")?;
+        }
+        line(f, "END", "VCALENDAR")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Course, CourseDetails, Stage};
+
+    #[test]
+    fn calendar_has_event_and_alarm_per_stage() {
+        let mut course = Course::new(&CourseDetails::new("0", "Toast"));
+        course.add(Stage::new("Bread in toaster, then butter", "5min"));
+        let serve_at = "2022-01-10T19:10:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut calendar = Calendar::new("Toast", serve_at);
+
+        calendar.add_schedule("0", "Toast", &course.schedule(&serve_at).unwrap());
+        let ics = calendar.to_string();
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.contains(
+            "UID:0-2@course-planner\r\nDTSTAMP:20220110T191000Z\r\n\
+             DTSTART:20220110T190500Z\r\nDTEND:20220110T191000Z\r\n\
+             SUMMARY:Toast: Bread in toaster\\, then butter\r\n"
+        ));
+        assert_eq!(ics.matches("BEGIN:VALARM").count(), 2);
+        assert!(ics.contains("DTSTART:20220110T191000Z\r\nSUMMARY:Toast: Serving\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+
+        course.add(Stage::new("Plug in toaster", "10min"));
+        let mut calendar = Calendar::new("Toast", serve_at);
+        calendar.add_schedule("0", "Toast", &course.schedule(&serve_at).unwrap());
+        assert!(calendar.to_string().contains(
+            "UID:0-2@course-planner\r\nDTSTAMP:20220110T191000Z\r\nDTSTART:20220110T190500Z"
+        ));
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let calendar = Calendar::new(&"x".repeat(100), Utc::now());
+
+        let ics = calendar.to_string();
+
+        assert!(ics.split("\r\n").all(|line| line.len() <= LINE_LIMIT));
+        assert!(ics.contains(&format!(
+            "X-WR-CALNAME:{}\r\n {}",
+            "x".repeat(62),
+            "x".repeat(38)
+        )));
+    }
+}
@@ -0,0 +1,410 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use itertools::Itertools;
+
+use crate::{Course, TimelineEntry};
+
+/// Something in the kitchen which a stage keeps busy while it runs.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Resource {
+    /// An oven set to the given temperature (°C). Dishes at the same temperature can share.
+    Oven(u16),
+    Hob,
+    Microwave,
+    Cook,
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resource::Oven(temperature) => write!(f, "oven at {}°C", temperature),
+            Resource::Hob => write!(f, "hob ring"),
+            Resource::Microwave => write!(f, "microwave"),
+            Resource::Cook => write!(f, "cook"),
+        }
+    }
+}
+
+/// What is available in the kitchen, and how long cooked courses may be kept warm.
+///
+/// Anything left out when it is sent is as in the default kitchen.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Kitchen {
+    ovens: usize,
+    hob_rings: usize,
+    microwaves: usize,
+    cooks: usize,
+    max_hold: Duration,
+}
+
+impl Default for Kitchen {
+    fn default() -> Self {
+        Self {
+            ovens: 1,
+            hob_rings: 4,
+            microwaves: 1,
+            cooks: 1,
+            max_hold: Duration::ZERO,
+        }
+    }
+}
+
+impl Kitchen {
+    pub fn new(ovens: usize, hob_rings: usize, microwaves: usize, cooks: usize) -> Self {
+        Self {
+            ovens,
+            hob_rings,
+            microwaves,
+            cooks,
+            max_hold: Duration::ZERO,
+        }
+    }
+
+    /// Allow courses to finish up to `max_hold` early, and be kept warm, to free resources.
+    pub fn with_max_hold(mut self, max_hold: Duration) -> Self {
+        self.max_hold = max_hold;
+        self
+    }
+
+    /// The first resource over capacity among the given simultaneously running stages.
+    fn overloaded(&self, running: &[&Booking]) -> Option<Resource> {
+        let uses = running
+            .iter()
+            .flat_map(|booking| booking.resources.iter().copied())
+            .collect::<Vec<_>>();
+        let count = |resource: Resource| uses.iter().filter(|&&r| r == resource).count();
+        if let Some(oven) = uses
+            .iter()
+            .copied()
+            .filter(|r| matches!(r, Resource::Oven(_)))
+            .unique()
+            .nth(self.ovens)
+        {
+            return Some(oven);
+        }
+        [
+            (Resource::Hob, self.hob_rings),
+            (Resource::Microwave, self.microwaves),
+            (Resource::Cook, self.cooks),
+        ]
+        .into_iter()
+        .find(|&(resource, available)| count(resource) > available)
+        .map(|(resource, _)| resource)
+    }
+}
+
+/// A course finished ahead of serving so that the meal fits in the kitchen.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Hold {
+    course: String,
+    duration: Duration,
+}
+
+impl Hold {
+    pub fn course(&self) -> &str {
+        &self.course
+    }
+
+    pub fn duration(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.duration)
+    }
+}
+
+/// A timeline which fits in the kitchen.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KitchenPlan {
+    timeline: Vec<TimelineEntry>,
+    holds: Vec<Hold>,
+}
+
+impl KitchenPlan {
+    pub fn timeline(&self) -> &[TimelineEntry] {
+        &self.timeline
+    }
+
+    /// Courses which must be ready early and kept warm until serving.
+    pub fn holds(&self) -> &[Hold] {
+        &self.holds
+    }
+}
+
+/// The stages which ask for more of a resource than the kitchen has.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResourceConflict {
+    resource: Resource,
+    stages: Vec<String>,
+    before_serving: Duration,
+}
+
+impl ResourceConflict {
+    pub fn resource(&self) -> Resource {
+        self.resource
+    }
+
+    /// The clashing stages, as `course: stage`.
+    pub fn stages(&self) -> &[String] {
+        &self.stages
+    }
+}
+
+impl fmt::Display for ResourceConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "not enough {} at T-{} for {}",
+            self.resource,
+            humantime::format_duration(self.before_serving),
+            self.stages.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ResourceConflict {}
+
+/// One stage's claim on resources, between `start` and `end` before serving.
+#[derive(Debug, Clone)]
+struct Booking {
+    course: String,
+    stage: String,
+    start: Duration,
+    end: Duration,
+    resources: Vec<Resource>,
+}
+
+impl Booking {
+    fn shifted(&self, by: Duration) -> Self {
+        Self {
+            start: self.start + by,
+            end: self.end + by,
+            ..self.clone()
+        }
+    }
+
+    fn running_at(&self, t: Duration) -> bool {
+        self.start >= t && t > self.end
+    }
+}
+
+fn bookings(course: &Course) -> Vec<Booking> {
+    course
+        .stages
+        .iter()
+        .sorted_by_key(|stage| std::cmp::Reverse(stage.duration))
+        .tuple_windows()
+        .filter(|(stage, _)| !stage.resources.is_empty())
+        .map(|(stage, next)| Booking {
+            course: course.details.name.clone(),
+            stage: stage.name.clone(),
+            start: stage.duration,
+            end: next.duration,
+            resources: stage.resources.clone(),
+        })
+        .collect()
+}
+
+/// Check every moment at which a stage starts, returning the first overload found.
+fn conflict(kitchen: &Kitchen, bookings: &[Booking]) -> Option<ResourceConflict> {
+    bookings
+        .iter()
+        .map(|booking| booking.start)
+        .sorted_by_key(|&t| std::cmp::Reverse(t))
+        .dedup()
+        .find_map(|t| {
+            let running = bookings.iter().filter(|b| b.running_at(t)).collect_vec();
+            kitchen
+                .overloaded(&running)
+                .map(|resource| ResourceConflict {
+                    resource,
+                    stages: running
+                        .iter()
+                        .filter(|b| match resource {
+                            Resource::Oven(_) => {
+                                b.resources.iter().any(|r| matches!(r, Resource::Oven(_)))
+                            }
+                            _ => b.resources.contains(&resource),
+                        })
+                        .map(|b| format!("{}: {}", b.course, b.stage))
+                        .collect(),
+                    before_serving: t,
+                })
+        })
+}
+
+/// Fit the courses into the kitchen, all served together.
+///
+/// Each stage keeps its resources until the next stage of its course starts. Courses are
+/// placed quickest first, as they suffer most from being kept warm; when one clashes with
+/// those already placed it is moved earlier, as little as possible and by no more than the
+/// kitchen's `max_hold`. If no such move exists the clash is reported as it stands at the
+/// target serving time.
+pub fn plan<'a>(
+    kitchen: &Kitchen,
+    courses: impl IntoIterator<Item = &'a Course>,
+) -> Result<KitchenPlan, ResourceConflict> {
+    let courses = courses
+        .into_iter()
+        .sorted_by_key(|course| course.stages.iter().map(|s| s.duration).max())
+        .collect_vec();
+
+    let mut placed: Vec<Booking> = Vec::new();
+    let mut holds = Vec::new();
+    // By course id, as different courses may share a name.
+    let mut shifts = HashMap::new();
+    for course in &courses {
+        let wanted = bookings(course);
+        let candidates = std::iter::once(Duration::ZERO).chain(
+            placed
+                .iter()
+                .cartesian_product(&wanted)
+                .filter_map(|(p, w)| p.start.checked_sub(w.end))
+                .filter(|shift| *shift <= kitchen.max_hold)
+                .sorted()
+                .dedup(),
+        );
+        let shift = candidates.into_iter().find(|&shift| {
+            let trial = placed
+                .iter()
+                .cloned()
+                .chain(wanted.iter().map(|b| b.shifted(shift)))
+                .collect_vec();
+            conflict(kitchen, &trial).is_none()
+        });
+        match shift {
+            Some(shift) => {
+                debug!("Place {} {:?} early", course.details.name, shift);
+                if !shift.is_zero() {
+                    holds.push(Hold {
+                        course: course.details.name.clone(),
+                        duration: shift,
+                    });
+                    shifts.insert(course.details.id.as_str(), shift);
+                }
+                placed.extend(wanted.iter().map(|b| b.shifted(shift)));
+            }
+            None => {
+                let unshifted = placed.iter().cloned().chain(wanted).collect_vec();
+                return Err(conflict(kitchen, &unshifted)
+                    .expect("a course which cannot be placed must clash at its own time"));
+            }
+        }
+    }
+
+    let timeline = crate::meal::merge(courses.iter().map(|&course| {
+        let early = shifts
+            .get(course.details.id.as_str())
+            .copied()
+            .unwrap_or(Duration::ZERO);
+        (course, early)
+    }));
+    Ok(KitchenPlan { timeline, holds })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CourseDetails, Stage};
+
+    fn course(name: &str, stages: Vec<Stage>) -> Course {
+        let mut course = Course::new(&CourseDetails::new(name, name));
+        for stage in stages {
+            course.add(stage);
+        }
+        course
+    }
+
+    #[test]
+    fn plan_moves_course_to_free_the_oven() {
+        let roast = course(
+            "Roast",
+            vec![Stage::new("Beef in oven", "1h").using(Resource::Oven(180))],
+        );
+        let pudding = course(
+            "Yorkshires",
+            vec![Stage::new("Puddings in oven", "20min").using(Resource::Oven(220))],
+        );
+        let kitchen = Kitchen::new(1, 4, 1, 1).with_max_hold(Duration::from_secs(60 * 60));
+
+        let plan = plan(&kitchen, vec![&roast, &pudding]).unwrap();
+
+        assert_eq!(
+            plan.holds(),
+            &[Hold {
+                course: "Roast".to_owned(),
+                duration: Duration::from_secs(20 * 60)
+            }]
+        );
+        assert_eq!(
+            plan.timeline()[0].to_string(),
+            "T-1h 20m -- Roast: Beef in oven"
+        );
+    }
+
+    #[test]
+    fn plan_holds_only_the_moved_course_of_a_shared_name() {
+        let mut beef = Course::new(&CourseDetails::new("beef", "Bake"));
+        beef.add(Stage::new("Beef in oven", "1h").using(Resource::Oven(180)));
+        let mut puddings = Course::new(&CourseDetails::new("puddings", "Bake"));
+        puddings.add(Stage::new("Puddings in oven", "20min").using(Resource::Oven(220)));
+        let kitchen = Kitchen::new(1, 4, 1, 1).with_max_hold(Duration::from_secs(60 * 60));
+
+        let plan = plan(&kitchen, vec![&beef, &puddings]).unwrap();
+
+        assert_eq!(
+            plan.timeline()
+                .iter()
+                .map(|entry| entry.to_string())
+                .collect_vec(),
+            vec![
+                "T-1h 20m -- Bake: Beef in oven",
+                "T-20m -- Bake: Puddings in oven",
+                "T-20m -- Bake: Serving",
+                "T-0s -- Serving",
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_shares_oven_at_same_temperature() {
+        let roast = course(
+            "Roast",
+            vec![Stage::new("Beef in oven", "1h").using(Resource::Oven(180))],
+        );
+        let potatoes = course(
+            "Potatoes",
+            vec![Stage::new("Potatoes in oven", "45min").using(Resource::Oven(180))],
+        );
+
+        let plan = plan(&Kitchen::default(), vec![&roast, &potatoes]).unwrap();
+
+        assert!(plan.holds().is_empty());
+    }
+
+    #[test]
+    fn plan_reports_conflict() {
+        let gravy = course(
+            "Gravy",
+            vec![Stage::new("Stir gravy", "10min")
+                .using(Resource::Hob)
+                .using(Resource::Cook)],
+        );
+        let carve = course(
+            "Roast",
+            vec![Stage::new("Carve", "5min").using(Resource::Cook)],
+        );
+
+        let conflict = plan(&Kitchen::default(), vec![&gravy, &carve]).unwrap_err();
+
+        assert_eq!(conflict.resource(), Resource::Cook);
+        assert_eq!(
+            conflict.to_string(),
+            "not enough cook at T-5m for Roast: Carve, Gravy: Stir gravy"
+        );
+    }
+}
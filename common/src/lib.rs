@@ -3,13 +3,51 @@ use log::debug;
 use serde::{Deserialize, Serialize};
 
 use std::fmt;
+use std::str::FromStr;
 
 use itertools::Itertools;
 
+pub mod api_key;
+pub use api_key::{ApiKeyDetails, CreatedApiKey, NewApiKey, Scope};
+pub mod attention;
+pub use attention::{AttentionPlan, StageKind, Window};
+pub mod cooklang;
+pub use cooklang::CooklangError;
+pub mod error;
+pub use error::ApiError;
+pub mod event;
+pub use event::ServerEvent;
+pub mod graph;
+pub use graph::{GraphError, GraphStage, PlannedStage, StageGraph};
+pub mod ics;
+pub use ics::{Calendar, CalendarEvent};
+pub mod kitchen;
+pub use kitchen::{Kitchen, KitchenPlan, Resource, ResourceConflict};
+pub mod meal;
+pub use meal::{Meal, MealDetails, TimelineEntry};
+pub mod metadata;
+pub use metadata::{Ingredient, Metadata};
+pub mod plan;
+pub use plan::{Plan, Planned};
+pub mod schedule;
+pub use schedule::{ScheduleError, ScheduledStage};
+pub mod schema_org;
+pub use schema_org::SchemaOrgError;
+pub mod session;
+pub use session::{Session, SessionError, SessionState, SessionStatus};
+pub mod share;
+pub use share::{Share, SharedView};
+pub mod text;
+pub use text::TextError;
+pub mod user;
+pub use user::{Credentials, CredentialsError, UserDetails};
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Course {
     details: CourseDetails,
     stages: Vec<Stage>,
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    metadata: Metadata,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -20,22 +58,201 @@ pub struct CourseDetails {
 
 impl Course {
     pub fn new(details: &CourseDetails) -> Self {
-        let stages = vec![Stage::new("Serving", "0s")];
-        Self {
-            stages,
+        Self::from_stages(details, vec![Stage::new("Serving", "0s")])
+    }
+
+    /// Rebuild a course from stages previously taken from `raw_stages`.
+    ///
+    /// Stages saved before stages had ids are given one.
+    pub fn from_stages(details: &CourseDetails, stages: Vec<Stage>) -> Self {
+        let mut course = Self {
+            stages: Vec::new(),
             details: details.to_owned(),
+            metadata: Metadata::default(),
+        };
+        for stage in stages {
+            course.add(stage);
+        }
+        course
+    }
+
+    /// Build a course from stages in the order they are cooked, each lasting its duration,
+    /// followed by serving.
+    pub fn from_lengths(details: &CourseDetails, stages: Vec<Stage>) -> Self {
+        Self::from_lengths_ending(details, stages, Stage::new("Serving", "0s"))
+    }
+
+    /// As `from_lengths`, but ending with `last` rather than serving, which starts its
+    /// duration before the course is served.
+    pub fn from_lengths_ending(details: &CourseDetails, stages: Vec<Stage>, last: Stage) -> Self {
+        let mut stages = Stage::chain(stages).collect_vec();
+        for stage in stages.iter_mut() {
+            stage.duration += last.duration;
+        }
+        stages.reverse();
+        // After every stage, so that a last stage taking no time still comes before it.
+        stages.push(last);
+        Self::from_stages(details, stages)
+    }
+
+    /// The course with every stage checked as `Stage::try_new` checks a stage a user typed,
+    /// and added afresh so that stage ids are unique, for a whole course sent by a client.
+    pub fn validated(&self) -> Result<Self, StageError> {
+        let mut course = Self {
+            details: self.details.clone(),
+            stages: Vec::new(),
+            metadata: self.metadata.clone(),
+        };
+        let mut ids = std::collections::HashMap::new();
+        for stage in &self.stages {
+            let checked = Stage::try_new(
+                &stage.name,
+                &humantime::format_duration(stage.duration).to_string(),
+            )?;
+            course.add(Stage {
+                id: 0,
+                name: checked.name,
+                ..stage.clone()
+            });
+            let id = course.stages.last().map_or(0, |added| added.id);
+            ids.entry(stage.id).or_insert(id);
+        }
+        for stage in course.stages.iter_mut() {
+            stage.after = stage
+                .after
+                .iter()
+                .filter_map(|after| ids.get(after).copied())
+                .collect();
         }
+        Ok(course)
     }
 
+    /// Add a stage, giving it a new id unless it already has one.
     pub fn add(&mut self, stage: Stage) {
         debug!("Add {:?} to course", stage);
+        let mut stage = stage;
+        if stage.id == 0 {
+            stage.id = self.stages.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+        }
         self.stages.push(stage);
     }
 
+    pub fn rename(&mut self, name: &str) {
+        self.details.name = name.to_owned();
+    }
+
+    pub fn set_id(&mut self, id: &str) {
+        self.details.id = id.to_owned();
+    }
+
+    pub fn stage(&self, id: u32) -> Option<&Stage> {
+        self.stages.iter().find(|stage| stage.id == id)
+    }
+
+    /// Apply every change in `update` to a stage, or none of them if any is invalid.
+    ///
+    /// Returns `false` if the course has no stage with that id.
+    pub fn update_stage(&mut self, id: u32, update: &StageUpdate) -> Result<bool, StageError> {
+        let stage = match self.stages.iter_mut().find(|stage| stage.id == id) {
+            Some(stage) => stage,
+            None => return Ok(false),
+        };
+        let changed = Stage::try_new(
+            update.name.as_deref().unwrap_or(&stage.name),
+            update.duration.as_deref().unwrap_or("0s"),
+        )?;
+        stage.name = changed.name;
+        if let Some(kind) = update.kind {
+            stage.kind = kind;
+        }
+        if update.duration.is_some() {
+            self.set_length(id, changed.duration);
+        }
+        if let Some(position) = update.position {
+            self.move_stage(id, position);
+        }
+        Ok(true)
+    }
+
+    /// Remove a stage, returning `false` if the course has no stage with that id.
+    pub fn remove_stage(&mut self, id: u32) -> bool {
+        let before = self.stages.len();
+        self.stages.retain(|stage| stage.id != id);
+        for stage in self.stages.iter_mut() {
+            stage.after.retain(|&after| after != id);
+        }
+        self.stages.len() != before
+    }
+
+    /// Move a stage to `position` in the timeline given by `stages`, keeping how long
+    /// each stage lasts, so later stages start correspondingly earlier or later.
+    ///
+    /// Returns `false` if there is no such stage, or it is the final (serving) stage.
+    pub fn move_stage(&mut self, id: u32, position: usize) -> bool {
+        let mut timeline = self.stages().collect_vec();
+        let from = match timeline.iter().position(|stage| stage.id == id) {
+            Some(from) => from,
+            None => return false,
+        };
+        let stage = timeline.remove(from);
+        timeline.insert(position.min(timeline.len()), stage);
+        let last = self.last_stage().cloned().unwrap();
+        self.set_timeline(timeline, last);
+        true
+    }
+
+    /// Make a stage last `length`, keeping how long every other stage lasts, so earlier
+    /// stages start correspondingly earlier or later. The final stage's length is how long
+    /// before serving it starts.
+    fn set_length(&mut self, id: u32, length: std::time::Duration) {
+        let mut timeline = self.stages().collect_vec();
+        let mut last = match self.last_stage() {
+            Some(last) => last.clone(),
+            None => return,
+        };
+        match timeline.iter_mut().find(|stage| stage.id == id) {
+            Some(stage) => stage.duration = length,
+            None => last.duration = length,
+        }
+        self.set_timeline(timeline, last);
+    }
+
+    /// Replace the stages with `timeline`, as `stages` gives it, followed by `last`.
+    fn set_timeline(&mut self, timeline: Vec<Stage>, last: Stage) {
+        let mut stages = Stage::chain(timeline).collect_vec();
+        for stage in stages.iter_mut() {
+            stage.duration += last.duration;
+        }
+        stages.reverse();
+        stages.push(last);
+        self.stages = stages;
+    }
+
     pub fn details(&self) -> &CourseDetails {
         &self.details
     }
 
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+
+    /// The stages as added, each timed from when it starts until serving.
+    pub fn raw_stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// The final stage, usually serving, which `stages` leaves out.
+    pub fn last_stage(&self) -> Option<&Stage> {
+        self.stages
+            .iter()
+            .sorted_by_cached_key(|stage| -(stage.duration.as_millis() as i128))
+            .last()
+    }
+
     pub fn stages(&self) -> impl Iterator<Item = Stage> + '_ {
         self.stages
             .iter()
@@ -43,8 +260,8 @@ impl Course {
             .sorted_by_cached_key(|stage| -(stage.duration.as_millis() as i128))
             .tuple_windows()
             .map(|(a, b)| Stage {
-                name: a.name.clone(),
                 duration: a.duration - b.duration,
+                ..a
             })
     }
 }
@@ -72,20 +289,118 @@ impl CourseDetails {
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Stage {
+    /// Unique within its course, and unchanged by edits; `0` until added to a course.
+    #[serde(default)]
+    id: u32,
     name: String,
     duration: std::time::Duration,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resources: Vec<Resource>,
+    #[serde(default)]
+    kind: StageKind,
+    /// Ids of the stages which must finish before this one starts, for stages added from
+    /// a `StageGraph`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    after: Vec<u32>,
+}
+
+/// Why a stage entered by a user was rejected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum StageError {
+    EmptyName,
+    InvalidDuration {
+        input: String,
+        reason: String,
+    },
+    /// A duration longer than `MAX_STAGE_DURATION`.
+    DurationTooLong {
+        input: String,
+    },
+    /// Text which is not of the form `<duration> -- <name>`.
+    InvalidFormat {
+        input: String,
+    },
+}
+
+/// The longest a stage may be, which leaves room for a few years of preserving.
+pub const MAX_STAGE_DURATION: std::time::Duration =
+    std::time::Duration::from_secs(5 * 365 * 24 * 60 * 60);
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StageError::EmptyName => write!(f, "stage name must not be empty"),
+            StageError::InvalidDuration { input, reason } => {
+                write!(f, "invalid duration '{}': {}", input, reason)
+            }
+            StageError::DurationTooLong { input } => write!(
+                f,
+                "duration '{}' is longer than {}",
+                input,
+                humantime::format_duration(MAX_STAGE_DURATION)
+            ),
+            StageError::InvalidFormat { input } => {
+                write!(f, "expected '<duration> -- <name>', found '{}'", input)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StageError {}
+
+/// Parse a stage duration as a user would type it, e.g. `1h 20m`.
+pub fn parse_duration(input: &str) -> Result<std::time::Duration, StageError> {
+    let duration = input
+        .trim()
+        .parse::<Duration>()
+        .map_err(|e| StageError::InvalidDuration {
+            input: input.to_owned(),
+            reason: e.to_string(),
+        })?;
+    if *duration > MAX_STAGE_DURATION {
+        return Err(StageError::DurationTooLong {
+            input: input.to_owned(),
+        });
+    }
+    Ok(duration.into())
 }
 
 impl Stage {
+    /// Build a stage, treating an unparseable duration as zero.
+    ///
+    /// Only for durations known to be valid; use `try_new` for anything a user typed.
     pub fn new(name: &str, duration: &str) -> Self {
-        let duration = duration
-            .parse::<Duration>()
-            .map(|d| d.into())
-            .unwrap_or_else(|_| std::time::Duration::from_secs(0));
-        Self {
+        Self::try_new(name, duration).unwrap_or_else(|_| Self {
             name: name.to_string(),
-            duration,
+            ..Self::default()
+        })
+    }
+
+    pub fn try_new(name: &str, duration: &str) -> Result<Self, StageError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(StageError::EmptyName);
         }
+        Ok(Self {
+            id: 0,
+            name: name.to_string(),
+            duration: parse_duration(duration)?,
+            resources: Vec::new(),
+            kind: StageKind::default(),
+            after: Vec::new(),
+        })
+    }
+
+    pub fn with_kind(mut self, kind: StageKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Mark the stage as occupying `resource` until the next stage of its course starts.
+    pub fn using(mut self, resource: Resource) -> Self {
+        self.resources.push(resource);
+        self
     }
 
     pub fn chain(links: Vec<Stage>) -> impl Iterator<Item = Stage> {
@@ -106,6 +421,10 @@ impl Stage {
             })
     }
 
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -113,9 +432,22 @@ impl Stage {
     pub fn duration(&self) -> humantime::FormattedDuration {
         humantime::format_duration(self.duration)
     }
+
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+
+    pub fn kind(&self) -> StageKind {
+        self.kind
+    }
+
+    /// Ids of the stages which must finish before this one starts.
+    pub fn after(&self) -> &[u32] {
+        &self.after
+    }
 }
 
-impl<'a> fmt::Display for Stage {
+impl fmt::Display for Stage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -126,11 +458,253 @@ impl<'a> fmt::Display for Stage {
     }
 }
 
+/// Parses the `Display` form of a stage, e.g. `1m -- Bread in toaster`.
+impl FromStr for Stage {
+    type Err = StageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (duration, name) = s
+            .split_once(" -- ")
+            .ok_or_else(|| StageError::InvalidFormat {
+                input: s.to_owned(),
+            })?;
+        Self::try_new(name, duration)
+    }
+}
+
+/// A stage as submitted by a user, with its duration still as text.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewStage {
+    name: String,
+    duration: String,
+    #[serde(default)]
+    kind: StageKind,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resources: Vec<Resource>,
+}
+
+impl NewStage {
+    pub fn new(name: &str, duration: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            duration: duration.to_owned(),
+            kind: StageKind::default(),
+            resources: Vec::new(),
+        }
+    }
+
+    pub fn with_kind(mut self, kind: StageKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+/// Changes to an existing stage; fields left as `None` are kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct StageUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// How long the stage lasts, as `Course::stages` gives it, keeping every other stage's
+    /// length; for the final stage, how long before serving it starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<StageKind>,
+    /// Where to move the stage in the course timeline, see `Course::move_stage`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    position: Option<usize>,
+}
+
+impl StageUpdate {
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    pub fn with_duration(mut self, duration: &str) -> Self {
+        self.duration = Some(duration.to_owned());
+        self
+    }
+
+    pub fn with_kind(mut self, kind: StageKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn with_position(mut self, position: usize) -> Self {
+        self.position = Some(position);
+        self
+    }
+}
+
+/// Replace everything about a stage except where it sits in the course.
+impl From<NewStage> for StageUpdate {
+    fn from(stage: NewStage) -> Self {
+        Self {
+            name: Some(stage.name),
+            duration: Some(stage.duration),
+            kind: Some(stage.kind),
+            position: None,
+        }
+    }
+}
+
+impl TryFrom<NewStage> for Stage {
+    type Error = StageError;
+
+    fn try_from(stage: NewStage) -> Result<Self, Self::Error> {
+        Ok(Self {
+            resources: stage.resources,
+            kind: stage.kind,
+            ..Self::try_new(&stage.name, &stage.duration)?
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn try_new_rejects_bad_duration() {
+        assert!(matches!(
+            Stage::try_new("Bread in toaster", "5 minuts"),
+            Err(StageError::InvalidDuration { input, .. }) if input == "5 minuts"
+        ));
+        assert_eq!(Stage::try_new(" ", "5min"), Err(StageError::EmptyName));
+        assert_eq!(
+            Stage::try_new("Bread in toaster", "600000000years"),
+            Err(StageError::DurationTooLong {
+                input: "600000000years".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn validated_checks_stages_and_renumbers_them() {
+        let course: Course = serde_json::from_str(
+            r#"{"details": {"id": "", "name": "Toast"}, "stages": [
+                {"id": 7, "name": " Bread in toaster ", "duration": {"secs": 60, "nanos": 0}},
+                {"id": 7, "name": "Serving", "duration": {"secs": 0, "nanos": 0}, "after": [7]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let validated = course.validated().unwrap();
+
+        assert_eq!(
+            validated
+                .raw_stages()
+                .iter()
+                .map(|stage| (stage.id(), stage.name(), stage.after().to_vec()))
+                .collect::<Vec<_>>(),
+            vec![(1, "Bread in toaster", vec![]), (2, "Serving", vec![1])]
+        );
+
+        let unnamed: Course = serde_json::from_str(
+            r#"{"details": {"id": "", "name": "Toast"}, "stages": [
+                {"name": " ", "duration": {"secs": 60, "nanos": 0}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(unnamed.validated(), Err(StageError::EmptyName));
+    }
+
+    fn toast() -> Course {
+        let mut course = Course::new(&CourseDetails::new("0", "Toast"));
+        for stage in Stage::chain(vec![
+            Stage::new("Bread in toaster", "2min"),
+            Stage::new("Butter", "1min"),
+            Stage::new("Jam", "30s"),
+        ]) {
+            course.add(stage);
+        }
+        course
+    }
+
+    fn timeline(course: &Course) -> Vec<String> {
+        course.stages().map(|stage| stage.to_string()).collect()
+    }
+
+    #[test]
+    fn add_assigns_stable_ids() {
+        let mut course = toast();
+        assert_eq!(
+            course
+                .raw_stages()
+                .iter()
+                .map(Stage::id)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert!(course.remove_stage(3));
+        course.add(Stage::new("Marmalade", "10s"));
+        assert_eq!(course.stage(5).map(Stage::name), Some("Marmalade"));
+    }
+
+    #[test]
+    fn move_stage_keeps_stage_lengths() {
+        let mut course = toast();
+        let jam = course.stages().last().unwrap().id();
+
+        assert!(course.move_stage(jam, 1));
+
+        assert_eq!(
+            timeline(&course),
+            vec!["2m -- Bread in toaster", "30s -- Jam", "1m -- Butter"]
+        );
+    }
+
+    #[test]
+    fn update_stage_is_all_or_nothing() {
+        let mut course = toast();
+        let bad = StageUpdate::default()
+            .with_name("Sourdough in toaster")
+            .with_duration("4 minuts");
+
+        assert!(course.update_stage(4, &bad).is_err());
+        assert_eq!(course.stage(4).map(Stage::name), Some("Bread in toaster"));
+
+        let good = StageUpdate::default().with_duration("4m");
+        assert_eq!(course.update_stage(4, &good), Ok(true));
+        assert_eq!(timeline(&course)[0], "4m -- Bread in toaster");
+        assert_eq!(course.update_stage(99, &good), Ok(false));
+    }
+
+    #[test]
+    fn update_stage_sets_the_stages_own_length() {
+        let mut course = toast();
+        let before = course.clone();
+
+        let same = StageUpdate::default().with_duration("1m");
+        assert_eq!(course.update_stage(3, &same), Ok(true));
+        assert_eq!(course.stages().collect_vec(), before.stages().collect_vec());
+
+        let longer = StageUpdate::default().with_duration("10m");
+        assert_eq!(course.update_stage(3, &longer), Ok(true));
+        assert_eq!(
+            timeline(&course),
+            vec!["2m -- Bread in toaster", "10m -- Butter", "30s -- Jam"]
+        );
+        assert_eq!(
+            course.stage(4).unwrap().duration,
+            std::time::Duration::from_secs(750)
+        );
+    }
+
+    #[test]
+    fn from_str_reads_display_form() {
+        let stage = Stage::new("Bread in toaster", "1min");
+        assert_eq!(stage.to_string().parse::<Stage>(), Ok(stage));
+        assert!(matches!(
+            "Bread in toaster".parse::<Stage>(),
+            Err(StageError::InvalidFormat { .. })
+        ));
+    }
 }
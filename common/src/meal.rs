@@ -0,0 +1,264 @@
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::time::Duration;
+
+use itertools::Itertools;
+
+use crate::Course;
+
+/// A set of courses which are all served together at one target serving time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Meal {
+    details: MealDetails,
+    courses: Vec<String>,
+    /// When the meal is to be served, once that has been chosen.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    serve_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MealDetails {
+    id: String,
+    name: String,
+}
+
+impl Meal {
+    pub fn new(details: &MealDetails) -> Self {
+        Self {
+            details: details.to_owned(),
+            courses: Vec::new(),
+            serve_at: None,
+        }
+    }
+
+    /// Add the course with the given id to the meal, ignoring duplicates.
+    pub fn add(&mut self, course_id: &str) {
+        debug!("Add course {} to meal", course_id);
+        if !self.courses.iter().any(|id| id == course_id) {
+            self.courses.push(course_id.to_owned());
+        }
+    }
+
+    /// Remove the course with the given id, e.g. because it was deleted.
+    pub fn remove(&mut self, course_id: &str) {
+        self.courses.retain(|id| id != course_id);
+    }
+
+    pub fn details(&self) -> &MealDetails {
+        &self.details
+    }
+
+    /// Ids of the courses making up this meal.
+    pub fn courses(&self) -> &[String] {
+        &self.courses
+    }
+
+    pub fn serve_at(&self) -> Option<&DateTime<Utc>> {
+        self.serve_at.as_ref()
+    }
+
+    pub fn set_serve_at(&mut self, serve_at: Option<DateTime<Utc>>) {
+        self.serve_at = serve_at;
+    }
+
+    /// The merged timeline of `courses`, which should be this meal's, with the time of
+    /// each action once the meal has a serving time.
+    pub fn timeline<'a>(
+        &self,
+        courses: impl IntoIterator<Item = &'a Course>,
+    ) -> Vec<TimelineEntry> {
+        timed(timeline(courses), self.serve_at)
+    }
+}
+
+impl MealDetails {
+    pub fn new(id: &str, name: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn set_id(&mut self, id: &str) {
+        self.id = id.to_owned();
+    }
+}
+
+/// A single "start X at T-minus" action in a merged meal timeline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimelineEntry {
+    /// Empty for serving, which is shared by every course.
+    pub(crate) course: String,
+    pub(crate) stage: String,
+    pub(crate) before_serving: Duration,
+    /// When the action is due, if the serving time is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) at: Option<DateTime<Utc>>,
+}
+
+impl TimelineEntry {
+    pub fn course(&self) -> &str {
+        &self.course
+    }
+
+    pub fn stage(&self) -> &str {
+        &self.stage
+    }
+
+    pub fn before_serving(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.before_serving)
+    }
+
+    pub fn at(&self) -> Option<&DateTime<Utc>> {
+        self.at.as_ref()
+    }
+}
+
+impl fmt::Display for TimelineEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "T-{} -- ",
+            humantime::format_duration(self.before_serving)
+        )?;
+        if !self.course.is_empty() {
+            write!(f, "{}: ", self.course)?;
+        }
+        write!(f, "{}", self.stage)
+    }
+}
+
+/// Merge the stages of every course into a single timeline, earliest action first, ending
+/// with a single serving for them all.
+///
+/// Actions due at the same moment are ordered by course name, so the result is stable
+/// regardless of the order the courses are supplied in.
+pub fn timeline<'a>(courses: impl IntoIterator<Item = &'a Course>) -> Vec<TimelineEntry> {
+    merge(courses.into_iter().map(|course| (course, Duration::ZERO)))
+}
+
+/// Give each entry the time it is due, serving at `serve_at`.
+pub fn timed(
+    mut timeline: Vec<TimelineEntry>,
+    serve_at: Option<DateTime<Utc>>,
+) -> Vec<TimelineEntry> {
+    for entry in timeline.iter_mut() {
+        entry.at = serve_at.and_then(|serve_at| {
+            serve_at.checked_sub_signed(chrono::Duration::from_std(entry.before_serving).ok()?)
+        });
+    }
+    timeline
+}
+
+/// Merge courses into one timeline, each course finishing the given time before serving.
+///
+/// Each course's own `Serving` is left out in favour of one for the whole meal, unless the
+/// course finishes early, when it marks the course being ready.
+pub(crate) fn merge<'a>(
+    courses: impl IntoIterator<Item = (&'a Course, Duration)>,
+) -> Vec<TimelineEntry> {
+    serve_once(courses.into_iter().flat_map(|(course, early)| {
+        course.stages.iter().map(move |stage| TimelineEntry {
+            course: course.details.name.clone(),
+            stage: stage.name.clone(),
+            before_serving: stage.duration + early,
+            at: None,
+        })
+    }))
+}
+
+/// Sort entries from several courses into one timeline with a single `Serving` at the
+/// end, leaving out each course's own `Serving` unless it comes before then.
+pub(crate) fn serve_once(entries: impl IntoIterator<Item = TimelineEntry>) -> Vec<TimelineEntry> {
+    entries
+        .into_iter()
+        .filter(|entry| !(entry.stage == "Serving" && entry.before_serving.is_zero()))
+        .chain(std::iter::once(TimelineEntry {
+            course: String::new(),
+            stage: "Serving".to_owned(),
+            before_serving: Duration::ZERO,
+            at: None,
+        }))
+        .sorted_by(|a, b| {
+            b.before_serving
+                .cmp(&a.before_serving)
+                .then_with(|| a.course.cmp(&b.course))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CourseDetails, Stage};
+
+    fn course(name: &str, stages: &[(&str, &str)]) -> Course {
+        let mut course = Course::new(&CourseDetails::new(name, name));
+        for (stage, duration) in stages {
+            course.add(Stage::new(stage, duration));
+        }
+        course
+    }
+
+    #[test]
+    fn timeline_merges_courses_chronologically() {
+        let roast = course("Roast", &[("Beef in oven", "1h 30min"), ("Rest", "20min")]);
+        let potatoes = course("Potatoes", &[("Potatoes in oven", "45min")]);
+
+        let steps = timeline(vec![&roast, &potatoes])
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            steps,
+            vec![
+                "T-1h 30m -- Roast: Beef in oven",
+                "T-45m -- Potatoes: Potatoes in oven",
+                "T-20m -- Roast: Rest",
+                "T-0s -- Serving",
+            ]
+        );
+    }
+
+    #[test]
+    fn meal_timeline_is_timed_from_its_serving_time() {
+        let roast = course("Roast", &[("Beef in oven", "1h 30min")]);
+        let mut meal = Meal::new(&MealDetails::new("1", "Sunday dinner"));
+        meal.add("Roast");
+        let serve_at = "2022-01-10T19:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        assert_eq!(meal.timeline(vec![&roast])[0].at(), None);
+        meal.set_serve_at(Some(serve_at));
+        let timeline = meal.timeline(vec![&roast]);
+
+        assert_eq!(
+            timeline[0].at().map(|at| at.to_rfc3339()),
+            Some("2022-01-10T17:30:00+00:00".to_owned())
+        );
+        assert_eq!(timeline.last().unwrap().at(), Some(&serve_at));
+    }
+
+    #[test]
+    fn meal_ignores_duplicate_courses() {
+        let mut meal = Meal::new(&MealDetails::new("1", "Sunday dinner"));
+        meal.add("roast");
+        meal.add("potatoes");
+        meal.add("roast");
+        assert_eq!(
+            meal.courses(),
+            &["roast".to_string(), "potatoes".to_string()]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Something a course is made from, e.g. `500 g pasta`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Ingredient {
+    name: String,
+    /// Kept as written, since recipes use fractions such as `1/2`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quantity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unit: Option<String>,
+}
+
+impl Ingredient {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            quantity: None,
+            unit: None,
+        }
+    }
+
+    pub fn with_quantity(mut self, quantity: &str) -> Self {
+        self.quantity = Some(quantity.to_owned());
+        self
+    }
+
+    pub fn with_unit(mut self, unit: &str) -> Self {
+        self.unit = Some(unit.to_owned());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn quantity(&self) -> Option<&str> {
+        self.quantity.as_deref()
+    }
+
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+}
+
+impl fmt::Display for Ingredient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in [&self.quantity, &self.unit].into_iter().flatten() {
+            write!(f, "{} ", part)?;
+        }
+        write!(f, "{}", self.name)
+    }
+}
+
+/// What a course is made from, and anything else its recipe says about it.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    ingredients: Vec<Ingredient>,
+    /// Free-form values such as `servings` or `source`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    fields: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    pub fn with_ingredient(mut self, ingredient: Ingredient) -> Self {
+        self.ingredients.push(ingredient);
+        self
+    }
+
+    pub fn with_field(mut self, key: &str, value: &str) -> Self {
+        self.fields.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    pub fn ingredients(&self) -> &[Ingredient] {
+        &self.ingredients
+    }
+
+    pub fn fields(&self) -> &BTreeMap<String, String> {
+        &self.fields
+    }
+
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ingredients.is_empty() && self.fields.is_empty()
+    }
+}
@@ -0,0 +1,122 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Calendar, Course, ScheduleError};
+
+/// What is planned: a single course, or every course of a meal.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum Planned {
+    Course(String),
+    Meal(String),
+}
+
+/// A course or meal to be served at a future time, shown in a calendar feed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Plan {
+    id: String,
+    planned: Planned,
+    serve_at: DateTime<Utc>,
+}
+
+/// How long after serving a plan stays in the feed.
+const FEED_HISTORY: i64 = 7;
+
+impl Plan {
+    pub fn new(id: &str, planned: Planned, serve_at: DateTime<Utc>) -> Self {
+        Self {
+            id: id.to_owned(),
+            planned,
+            serve_at,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn planned(&self) -> &Planned {
+        &self.planned
+    }
+
+    pub fn serve_at(&self) -> &DateTime<Utc> {
+        &self.serve_at
+    }
+
+    /// Whether the plan still belongs in a feed, i.e. it was served at most a week ago.
+    pub fn is_current(&self, now: DateTime<Utc>) -> bool {
+        self.serve_at + Duration::days(FEED_HISTORY) >= now
+    }
+}
+
+impl Calendar {
+    /// Add every stage of the planned courses, however many days before serving they start.
+    ///
+    /// For a meal, `meal` names it in each event's summary, ahead of the course. Nothing is
+    /// added if any course cannot be scheduled.
+    pub fn add_plan(
+        &mut self,
+        plan: &Plan,
+        meal: Option<&str>,
+        courses: &[Course],
+    ) -> Result<(), ScheduleError> {
+        let schedules = courses
+            .iter()
+            .map(|course| course.schedule(&plan.serve_at))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (course, schedule) in courses.iter().zip(schedules) {
+            let name = match meal {
+                Some(meal) => format!("{} — {}", meal, course.details().name()),
+                None => course.details().name().to_owned(),
+            };
+            self.add_schedule(
+                &format!("{}-{}", plan.id, course.details().id()),
+                &name,
+                &schedule,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CourseDetails, Stage};
+
+    #[test]
+    fn meal_plan_covers_every_course_and_multi_day_prep() {
+        let mut lamb = Course::new(&CourseDetails::new("lamb", "Lamb"));
+        lamb.add(Stage::new("Marinate overnight", "1day"));
+        let mut rice = Course::new(&CourseDetails::new("rice", "Rice"));
+        rice.add(Stage::new("Boil", "12min"));
+        let serve_at = "2022-01-10T19:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let plan = Plan::new("p", Planned::Meal("m".to_owned()), serve_at);
+        let mut calendar = Calendar::new("Plans", serve_at);
+
+        calendar
+            .add_plan(&plan, Some("Dinner"), &[lamb, rice])
+            .unwrap();
+        let ics = calendar.to_string();
+
+        assert_eq!(calendar.events().len(), 4);
+        assert!(ics.contains(
+            "UID:p-lamb-2@course-planner\r\nDTSTAMP:20220110T190000Z\r\n\
+             DTSTART:20220109T190000Z\r\nDTEND:20220110T190000Z\r\n\
+             SUMMARY:Dinner — Lamb: Marinate overnight\r\n"
+        ));
+        assert!(ics.contains(
+            "DTSTART:20220110T184800Z\r\nDTEND:20220110T190000Z\r\nSUMMARY:Dinner — Rice: Boil\r\n"
+        ));
+    }
+
+    #[test]
+    fn plans_leave_the_feed_a_week_after_serving() {
+        let serve_at = "2022-01-10T19:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let plan = Plan::new("p", Planned::Course("c".to_owned()), serve_at);
+
+        assert!(plan.is_current(serve_at - Duration::days(30)));
+        assert!(plan.is_current(serve_at + Duration::days(7)));
+        assert!(!plan.is_current(serve_at + Duration::days(8)));
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::{DateTime, FixedOffset, LocalResult, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::Course;
+
+/// A stage anchored to wall-clock time, starting at `start` and running until `end`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduledStage {
+    /// The id of the stage within its course.
+    #[serde(default)]
+    id: u32,
+    name: String,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+}
+
+impl ScheduledStage {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn start(&self) -> &DateTime<FixedOffset> {
+        &self.start
+    }
+
+    pub fn end(&self) -> &DateTime<FixedOffset> {
+        &self.end
+    }
+}
+
+impl fmt::Display for ScheduledStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} — {}", self.start.format("%H:%M"), self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleError {
+    /// The serving time could not be parsed.
+    InvalidTime(String),
+    /// The time zone name is not a known IANA zone.
+    UnknownTimeZone(String),
+    /// The local serving time falls in a DST gap, so never happens on the wall clock.
+    NonExistentTime(NaiveDateTime, Tz),
+    /// A stage would start before the earliest time that can be represented.
+    OutOfRange,
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::InvalidTime(time) => write!(f, "invalid serving time '{}'", time),
+            ScheduleError::UnknownTimeZone(tz) => write!(f, "unknown time zone '{}'", tz),
+            ScheduleError::NonExistentTime(time, tz) => write!(
+                f,
+                "{} does not exist in {} (clocks change at that time)",
+                time, tz
+            ),
+            ScheduleError::OutOfRange => write!(f, "stages start too long before serving"),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// Resolve a serving time, as supplied by a user, to an instant.
+///
+/// `serve_at` is either an RFC 3339 timestamp carrying its own offset, or a local
+/// date and time (`2022-03-27T19:30`) interpreted in the IANA zone `tz` (UTC when absent).
+/// A local time repeated by a DST change resolves to its first occurrence.
+pub fn serve_time(serve_at: &str, tz: Option<&str>) -> Result<DateTime<Tz>, ScheduleError> {
+    let tz = match tz {
+        Some(tz) => tz
+            .parse::<Tz>()
+            .map_err(|_| ScheduleError::UnknownTimeZone(tz.to_owned()))?,
+        None => Tz::UTC,
+    };
+    if let Ok(time) = DateTime::parse_from_rfc3339(serve_at) {
+        return Ok(time.with_timezone(&tz));
+    }
+    let naive = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(serve_at, format).ok())
+        .ok_or_else(|| ScheduleError::InvalidTime(serve_at.to_owned()))?;
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(time) => Ok(time),
+        LocalResult::Ambiguous(earliest, _) => Ok(earliest),
+        LocalResult::None => Err(ScheduleError::NonExistentTime(naive, tz)),
+    }
+}
+
+impl Course {
+    /// Anchor each stage to wall-clock time, so that the course is served at `serve_at`.
+    ///
+    /// Offsets are applied to the instant rather than the local clock, so a schedule
+    /// spanning a DST change keeps real durations while reporting local times.
+    pub fn schedule<T: TimeZone>(
+        &self,
+        serve_at: &DateTime<T>,
+    ) -> Result<Vec<ScheduledStage>, ScheduleError> {
+        let starts: Vec<_> = self
+            .stages
+            .iter()
+            .sorted_by_cached_key(|stage| std::cmp::Reverse(stage.duration))
+            .map(|stage| {
+                let start = chrono::Duration::from_std(stage.duration)
+                    .ok()
+                    .and_then(|duration| serve_at.clone().checked_sub_signed(duration))
+                    .ok_or(ScheduleError::OutOfRange)?;
+                Ok((stage.id, stage.name.clone(), start.fixed_offset()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(starts
+            .into_iter()
+            .chain(std::iter::once((0, String::new(), serve_at.fixed_offset())))
+            .tuple_windows()
+            .map(|((id, name, start), (_, _, end))| {
+                debug!("{} -> {} {}", start, end, name);
+                ScheduledStage {
+                    id,
+                    name,
+                    start,
+                    end,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CourseDetails, Stage};
+
+    #[test]
+    fn schedule_uses_wall_clock() {
+        let mut course = Course::new(&CourseDetails::new("0", "Toast"));
+        course.add(Stage::new("Bread in toaster", "5min"));
+        let serve_at = serve_time("2022-01-10T19:10", Some("Europe/London")).unwrap();
+
+        let schedule = course.schedule(&serve_at).unwrap();
+
+        assert_eq!(
+            schedule.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            vec!["19:05 — Bread in toaster", "19:10 — Serving"]
+        );
+        assert_eq!(schedule[0].end(), schedule[1].start());
+    }
+
+    #[test]
+    fn schedule_spans_dst_change() {
+        let mut course = Course::new(&CourseDetails::new("0", "Marinade"));
+        course.add(Stage::new("Start marinating", "2h"));
+        // Clocks go forward at 01:00 GMT on 2022-03-27.
+        let serve_at = serve_time("2022-03-27T03:30", Some("Europe/London")).unwrap();
+
+        let schedule = course.schedule(&serve_at).unwrap();
+
+        assert_eq!(
+            schedule[0].start().to_rfc3339(),
+            "2022-03-27T00:30:00+00:00"
+        );
+        assert_eq!(
+            schedule[1].start().to_rfc3339(),
+            "2022-03-27T03:30:00+01:00"
+        );
+    }
+
+    #[test]
+    fn schedule_rejects_stages_starting_out_of_range() {
+        let mut course = Course::new(&CourseDetails::new("0", "Toast"));
+        course.add(Stage::new("Bread in toaster", "5min"));
+
+        assert_eq!(
+            course.schedule(&DateTime::<chrono::Utc>::MIN_UTC),
+            Err(ScheduleError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn serve_time_rejects_dst_gap() {
+        assert!(matches!(
+            serve_time("2022-03-27T01:30", Some("Europe/London")),
+            Err(ScheduleError::NonExistentTime(_, _))
+        ));
+        assert_eq!(
+            serve_time("tea time", None),
+            Err(ScheduleError::InvalidTime("tea time".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Recipes from web pages, which embed them as schema.org `Recipe` JSON-LD.
+//!
+//! A recipe rarely says how long each step takes, so what is read is only a proposal,
+//! for the cook to review before it is saved.
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::cooklang::time_phrases;
+use crate::{Course, CourseDetails, Ingredient, Metadata, Stage, StageKind};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum SchemaOrgError {
+    /// Neither JSON nor a page with JSON-LD in it.
+    InvalidJson { reason: String },
+    /// JSON-LD, but none of it is a `Recipe`.
+    NoRecipe,
+    /// Not an ISO 8601 duration made of weeks, days, hours, minutes and seconds.
+    InvalidDuration { input: String },
+}
+
+impl fmt::Display for SchemaOrgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaOrgError::InvalidJson { reason } => write!(f, "invalid JSON-LD: {}", reason),
+            SchemaOrgError::NoRecipe => write!(f, "no schema.org Recipe found"),
+            SchemaOrgError::InvalidDuration { input } => {
+                write!(f, "invalid ISO 8601 duration '{}'", input)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaOrgError {}
+
+/// An ISO 8601 duration such as `PT1H30M` or `P1DT12H`.
+///
+/// Years and months are refused, since they have no fixed length.
+pub fn parse_duration(input: &str) -> Result<Duration, SchemaOrgError> {
+    let invalid = || SchemaOrgError::InvalidDuration {
+        input: input.to_owned(),
+    };
+    let rest = input.trim().strip_prefix('P').ok_or_else(invalid)?;
+    let mut seconds = 0.0;
+    let mut in_time = false;
+    let mut number = String::new();
+    let mut any = false;
+    for c in rest.chars() {
+        match c {
+            'T' if !in_time && number.is_empty() => in_time = true,
+            '0'..='9' | '.' => number.push(c),
+            ',' => number.push('.'),
+            _ => {
+                let value: f64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                let unit = match (in_time, c) {
+                    (false, 'W') => 7 * 86400,
+                    (false, 'D') => 86400,
+                    (true, 'H') => 3600,
+                    (true, 'M') => 60,
+                    (true, 'S') => 1,
+                    _ => return Err(invalid()),
+                };
+                seconds += value * unit as f64;
+                any = true;
+            }
+        }
+    }
+    if !any || !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_millis((seconds * 1000.0).round() as u64))
+}
+
+/// The contents of each `<script type="application/ld+json">` in a page.
+fn json_ld_scripts(html: &str) -> Vec<&str> {
+    // Same byte offsets as `html`, as only ASCII letters change case.
+    let lower = html.to_ascii_lowercase();
+    let mut scripts = Vec::new();
+    let mut from = 0;
+    while let Some(start) = lower[from..].find("<script").map(|start| from + start) {
+        let open_end = match lower[start..].find('>') {
+            Some(end) => start + end + 1,
+            None => break,
+        };
+        let close = lower[open_end..]
+            .find("</script")
+            .map_or(html.len(), |close| open_end + close);
+        if lower[start..open_end].contains("application/ld+json") {
+            scripts.push(&html[open_end..close]);
+        }
+        from = close;
+    }
+    scripts
+}
+
+fn is_recipe(value: &Value) -> bool {
+    match value.get("@type") {
+        Some(Value::String(kind)) => kind == "Recipe",
+        Some(Value::Array(kinds)) => kinds.iter().any(|kind| kind == "Recipe"),
+        _ => false,
+    }
+}
+
+/// The first recipe in a JSON-LD document, which may be a list or an `@graph` of things.
+fn find_recipe(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Array(items) => items.iter().find_map(find_recipe),
+        Value::Object(_) if is_recipe(value) => Some(value),
+        Value::Object(object) => ["@graph", "mainEntity"]
+            .iter()
+            .filter_map(|key| object.get(*key))
+            .find_map(find_recipe),
+        _ => None,
+    }
+}
+
+/// Text without HTML tags or the commonest entities, on one line.
+fn clean(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                plain.push(' ');
+            }
+            c if !in_tag => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .split_whitespace()
+        .join(" ")
+}
+
+/// The text of a property which may be a string, a number or a list of either.
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(clean(text)).filter(|text| !text.is_empty()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Array(items) => items.iter().find_map(text),
+        _ => None,
+    }
+}
+
+/// Flatten `recipeInstructions`: text, `HowToStep`s, or `HowToSection`s of them.
+fn steps(value: &Value, steps_so_far: &mut Vec<String>) {
+    match value {
+        Value::String(text) => {
+            steps_so_far.extend(text.lines().map(clean).filter(|line| !line.is_empty()))
+        }
+        Value::Array(items) => {
+            for item in items {
+                steps(item, steps_so_far);
+            }
+        }
+        Value::Object(object) => {
+            if let Some(items) = object.get("itemListElement") {
+                steps(items, steps_so_far);
+            } else if let Some(text) = object.get("text").or_else(|| object.get("name")) {
+                steps(text, steps_so_far);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// How long a step says it takes, from phrases such as `bake for 25 minutes`.
+fn step_length(step: &str) -> Duration {
+    time_phrases(step)
+        .iter()
+        // Shorter units, such as `m`, are more often metres than minutes.
+        .filter(|phrase| phrase.unit.len() >= 3 && phrase.length < Duration::from_secs(7 * 86400))
+        .map(|phrase| phrase.length)
+        .sum()
+}
+
+fn recipe(input: &str) -> Result<Value, SchemaOrgError> {
+    let invalid = |e: serde_json::Error| SchemaOrgError::InvalidJson {
+        reason: e.to_string(),
+    };
+    let trimmed = input.trim_start();
+    if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        let value: Value = serde_json::from_str(trimmed).map_err(invalid)?;
+        return find_recipe(&value).cloned().ok_or(SchemaOrgError::NoRecipe);
+    }
+    let scripts = json_ld_scripts(input);
+    if scripts.is_empty() {
+        return Err(SchemaOrgError::InvalidJson {
+            reason: "no JSON-LD in page".to_owned(),
+        });
+    }
+    // One broken script should not hide a recipe in another.
+    scripts
+        .iter()
+        .filter_map(|script| serde_json::from_str::<Value>(script).ok())
+        .find_map(|value| find_recipe(&value).cloned())
+        .ok_or(SchemaOrgError::NoRecipe)
+}
+
+/// Propose a course from a saved web page, or the JSON-LD taken from one.
+///
+/// Each instruction step becomes a stage lasting as long as the times it mentions. If no
+/// step mentions one, the recipe's `prepTime` and `cookTime` become two stages instead,
+/// and the steps are kept as the `instructions` field of the course's metadata.
+pub fn propose(input: &str) -> Result<Course, SchemaOrgError> {
+    let recipe = recipe(input)?;
+    let property = |key: &str| recipe.get(key).and_then(text);
+    let name = property("name").unwrap_or_else(|| "Imported recipe".to_owned());
+
+    let mut metadata = Metadata::default();
+    if let Some(Value::Array(ingredients)) = recipe.get("recipeIngredient") {
+        for ingredient in ingredients.iter().filter_map(text) {
+            metadata = metadata.with_ingredient(Ingredient::new(&ingredient));
+        }
+    }
+    for (key, field) in [("recipeYield", "servings"), ("url", "source")] {
+        if let Some(value) = property(key) {
+            metadata = metadata.with_field(field, &value);
+        }
+    }
+
+    let mut instructions = Vec::new();
+    if let Some(value) = recipe.get("recipeInstructions") {
+        steps(value, &mut instructions);
+    }
+    let timed = instructions
+        .iter()
+        .map(|step| (step, step_length(step)))
+        .collect_vec();
+    let stages = if timed.iter().any(|(_, length)| !length.is_zero()) {
+        timed
+            .into_iter()
+            .map(|(step, length)| Stage {
+                name: step.to_owned(),
+                duration: length,
+                ..Stage::default()
+            })
+            .collect_vec()
+    } else {
+        if !instructions.is_empty() {
+            let numbered = instructions
+                .iter()
+                .enumerate()
+                .map(|(i, step)| format!("{}. {}", i + 1, step))
+                .join("\n");
+            metadata = metadata.with_field("instructions", &numbered);
+        }
+        let time = |key| property(key).and_then(|time| parse_duration(&time).ok());
+        [
+            ("Prep", time("prepTime"), StageKind::Active),
+            ("Cook", time("cookTime"), StageKind::Passive),
+        ]
+        .into_iter()
+        .filter_map(|(name, length, kind)| {
+            Some(Stage {
+                name: name.to_owned(),
+                duration: length.filter(|length| !length.is_zero())?,
+                kind,
+                ..Stage::default()
+            })
+        })
+        .collect_vec()
+    };
+
+    let mut course = Course::from_lengths(&CourseDetails::new("", &name), stages);
+    course.set_metadata(metadata);
+    Ok(course)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stages(course: &Course) -> Vec<(String, String)> {
+        course
+            .stages()
+            .map(|stage| (stage.name().to_owned(), stage.duration().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn iso_8601_durations() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        assert_eq!(parse_duration("PT1H30M"), Ok(minutes(90)));
+        assert_eq!(parse_duration("P1DT12H"), Ok(minutes(36 * 60)));
+        assert_eq!(parse_duration("PT0.5H"), Ok(minutes(30)));
+        assert_eq!(parse_duration("P1W"), Ok(minutes(7 * 24 * 60)));
+        for invalid in ["", "PT", "P1M", "1H", "PT1H30"] {
+            assert_eq!(
+                parse_duration(invalid),
+                Err(SchemaOrgError::InvalidDuration {
+                    input: invalid.to_owned()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn steps_are_timed_from_their_text() {
+        let page = r#"<html><head>
+            <script type="application/ld+json">{ not json</script>
+            <SCRIPT type="application/ld+json">
+            {"@context": "https://schema.org", "@graph": [
+                {"@type": "WebPage", "name": "Best loaf"},
+                {"@type": ["Recipe"], "name": "Soda bread", "recipeYield": ["1 loaf"],
+                 "recipeIngredient": ["500g flour", "1 tsp bicarbonate of soda"],
+                 "recipeInstructions": [
+                    {"@type": "HowToSection", "name": "Dough", "itemListElement": [
+                        {"@type": "HowToStep", "text": "Mix &amp; knead into a round."}
+                    ]},
+                    {"@type": "HowToStep", "text": "<p>Bake for 30 mins, then 10 minutes upside down.</p>"}
+                 ]}
+            ]}
+            </SCRIPT></head></html>"#;
+
+        let course = propose(page).unwrap();
+
+        assert_eq!(course.details().name(), "Soda bread");
+        assert_eq!(
+            stages(&course),
+            vec![
+                ("Mix & knead into a round.".to_owned(), "0s".to_owned()),
+                (
+                    "Bake for 30 mins, then 10 minutes upside down.".to_owned(),
+                    "40m".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(course.metadata().ingredients().len(), 2);
+        assert_eq!(course.metadata().field("servings"), Some("1 loaf"));
+    }
+
+    #[test]
+    fn untimed_steps_fall_back_to_prep_and_cook_times() {
+        let json = r#"{"@type": "Recipe", "name": "Stew", "prepTime": "PT20M",
+            "cookTime": "PT2H", "recipeInstructions": "Brown the meat.\nSimmer gently."}"#;
+
+        let course = propose(json).unwrap();
+
+        assert_eq!(
+            stages(&course),
+            vec![
+                ("Prep".to_owned(), "20m".to_owned()),
+                ("Cook".to_owned(), "2h".to_owned())
+            ]
+        );
+        assert_eq!(
+            course.metadata().field("instructions"),
+            Some("1. Brown the meat.\n2. Simmer gently.")
+        );
+    }
+
+    #[test]
+    fn pages_without_a_recipe_are_refused() {
+        assert_eq!(
+            propose(r#"{"@type": "Article"}"#).unwrap_err(),
+            SchemaOrgError::NoRecipe
+        );
+        assert!(matches!(
+            propose("<html></html>"),
+            Err(SchemaOrgError::InvalidJson { .. })
+        ));
+    }
+}
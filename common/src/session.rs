@@ -0,0 +1,432 @@
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Course, ScheduledStage};
+
+/// Where a cooking session has got to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    /// Serving is far enough off that the first stage has not started.
+    Waiting,
+    Running,
+    Paused,
+    /// The course has been served.
+    Finished,
+}
+
+impl fmt::Display for SessionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionStatus::Waiting => write!(f, "waiting"),
+            SessionStatus::Running => write!(f, "running"),
+            SessionStatus::Paused => write!(f, "paused"),
+            SessionStatus::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionError {
+    /// Pausing a session which is already paused.
+    Paused,
+    /// Resuming a session which is not paused.
+    NotPaused,
+    Finished,
+    /// Extending a stage by more than `MAX_EXTENSION`.
+    ExtensionTooLong,
+    /// Moving serving beyond the times that can be represented.
+    OutOfRange,
+}
+
+/// The most a stage may be extended by at once.
+pub const MAX_EXTENSION: Duration = Duration::from_secs(24 * 60 * 60);
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Paused => write!(f, "session is already paused"),
+            SessionError::NotPaused => write!(f, "session is not paused"),
+            SessionError::Finished => write!(f, "session has finished"),
+            SessionError::ExtensionTooLong => write!(
+                f,
+                "a stage may be extended by at most {}",
+                humantime::format_duration(MAX_EXTENSION)
+            ),
+            SessionError::OutOfRange => write!(f, "session time is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// One run through a course, as it is being cooked.
+///
+/// The course is copied when the session starts, so editing it does not disturb the cook.
+/// Everything is worked out from the serve time: pausing and skipping just move serving
+/// later or earlier, while extending a stage also lengthens it in the session's copy.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Session {
+    id: String,
+    course: Course,
+    serve_at: DateTime<Utc>,
+    paused_at: Option<DateTime<Utc>>,
+}
+
+/// A snapshot of a session, for showing the same countdown on every device.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionState {
+    id: String,
+    course_id: String,
+    course_name: String,
+    status: SessionStatus,
+    /// Index into `stages` of the stage being cooked, if any.
+    current: Option<usize>,
+    /// Until the current stage ends or, while waiting, until the first one starts.
+    remaining: Duration,
+    serve_at: DateTime<Utc>,
+    stages: Vec<ScheduledStage>,
+}
+
+impl SessionState {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn course_id(&self) -> &str {
+        &self.course_id
+    }
+
+    pub fn course_name(&self) -> &str {
+        &self.course_name
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        self.status
+    }
+
+    pub fn current(&self) -> Option<&ScheduledStage> {
+        self.stages.get(self.current?)
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Time left in the snapshot's stage at `now`, for counting down between updates.
+    pub fn remaining_at(&self, now: DateTime<Utc>) -> Duration {
+        let until = match self.status {
+            SessionStatus::Running => self.current().map(|stage| *stage.end()),
+            SessionStatus::Waiting => self.stages.first().map(|stage| *stage.start()),
+            SessionStatus::Paused | SessionStatus::Finished => return self.remaining,
+        };
+        until.map_or(Duration::ZERO, |until| {
+            to_std(until.with_timezone(&Utc) - now)
+        })
+    }
+
+    pub fn remaining(&self) -> humantime::FormattedDuration {
+        humantime::format_duration(self.remaining)
+    }
+
+    pub fn serve_at(&self) -> &DateTime<Utc> {
+        &self.serve_at
+    }
+
+    pub fn stages(&self) -> &[ScheduledStage] {
+        &self.stages
+    }
+}
+
+impl fmt::Display for SessionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.course_name, self.status)?;
+        if let Some(stage) = self.current() {
+            write!(f, ": {}", stage.name())?;
+        }
+        write!(f, " ({} left)", self.remaining())
+    }
+}
+
+fn to_std(duration: chrono::Duration) -> Duration {
+    duration.to_std().unwrap_or(Duration::ZERO)
+}
+
+impl Session {
+    /// Start a session so that the course is served at `serve_at`.
+    pub fn new(id: &str, course: &Course, serve_at: DateTime<Utc>) -> Result<Self, SessionError> {
+        let mut session = Self {
+            id: id.to_owned(),
+            course: course.clone(),
+            serve_at,
+            paused_at: None,
+        };
+        session.serve_at(serve_at)?;
+        Ok(session)
+    }
+
+    /// Start a session with the first stage of the course starting `now`.
+    pub fn starting(id: &str, course: &Course, now: DateTime<Utc>) -> Result<Self, SessionError> {
+        let length = course
+            .stages
+            .iter()
+            .map(|stage| stage.duration)
+            .max()
+            .unwrap_or(Duration::ZERO);
+        let serve_at = later(now, length).ok_or(SessionError::OutOfRange)?;
+        Self::new(id, course, serve_at)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn course(&self) -> &Course {
+        &self.course
+    }
+
+    /// The session as it stands at `now`, which is frozen at the pause while paused.
+    pub fn state(&self, now: DateTime<Utc>) -> SessionState {
+        let now = self.paused_at.unwrap_or(now);
+        // Checked by `serve_at` whenever serving moves.
+        let stages = self.course.schedule(&self.serve_at).unwrap_or_default();
+        let current = stages
+            .iter()
+            .position(|stage| *stage.start() <= now && now < *stage.end());
+        let (status, remaining) = if now >= self.serve_at {
+            (SessionStatus::Finished, Duration::ZERO)
+        } else if let Some(current) = current {
+            (
+                SessionStatus::Running,
+                to_std(stages[current].end().with_timezone(&Utc) - now),
+            )
+        } else {
+            let first = stages
+                .first()
+                .map(|stage| stage.start().with_timezone(&Utc))
+                .unwrap_or(now);
+            (SessionStatus::Waiting, to_std(first - now))
+        };
+        SessionState {
+            id: self.id.clone(),
+            course_id: self.course.details.id().to_owned(),
+            course_name: self.course.details.name().to_owned(),
+            status: if self.paused_at.is_some() {
+                SessionStatus::Paused
+            } else {
+                status
+            },
+            current,
+            remaining,
+            serve_at: self.serve_at,
+            stages,
+        }
+    }
+
+    /// Whether serving was more than `by` before `now`, or would have been had the session
+    /// not been paused, so it can be forgotten.
+    pub fn is_past_serving(&self, now: DateTime<Utc>, by: chrono::Duration) -> bool {
+        now - self.serve_at > by
+    }
+
+    /// Serve at `serve_at`, as long as every stage can still be scheduled.
+    fn serve_at(&mut self, serve_at: DateTime<Utc>) -> Result<(), SessionError> {
+        self.course
+            .schedule(&serve_at)
+            .map_err(|_| SessionError::OutOfRange)?;
+        self.serve_at = serve_at;
+        Ok(())
+    }
+
+    fn check_running(&self, now: DateTime<Utc>) -> Result<(), SessionError> {
+        match self.state(now).status {
+            SessionStatus::Finished => Err(SessionError::Finished),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn pause(&mut self, now: DateTime<Utc>) -> Result<(), SessionError> {
+        self.check_running(now)?;
+        if self.paused_at.is_some() {
+            return Err(SessionError::Paused);
+        }
+        self.paused_at = Some(now);
+        Ok(())
+    }
+
+    /// Carry on from where the session was paused, serving that much later.
+    pub fn resume(&mut self, now: DateTime<Utc>) -> Result<(), SessionError> {
+        let paused_at = self.paused_at.ok_or(SessionError::NotPaused)?;
+        if now > paused_at {
+            let serve_at = self
+                .serve_at
+                .checked_add_signed(now - paused_at)
+                .ok_or(SessionError::OutOfRange)?;
+            self.serve_at(serve_at)?;
+        }
+        self.paused_at = None;
+        Ok(())
+    }
+
+    /// End the current stage now, or start the first one if still waiting.
+    pub fn skip(&mut self, now: DateTime<Utc>) -> Result<(), SessionError> {
+        self.check_running(now)?;
+        let state = self.state(now);
+        debug!("Skip {} in session {}", state.status, self.id);
+        let serve_at = chrono::Duration::from_std(state.remaining)
+            .ok()
+            .and_then(|remaining| self.serve_at.checked_sub_signed(remaining))
+            .ok_or(SessionError::OutOfRange)?;
+        self.serve_at(serve_at)
+    }
+
+    /// Give the current stage longer, pushing everything after it back.
+    ///
+    /// The current stage, and those before it, keep their start times. While waiting,
+    /// everything is pushed back.
+    pub fn extend(&mut self, now: DateTime<Utc>, by: Duration) -> Result<(), SessionError> {
+        self.check_running(now)?;
+        if by > MAX_EXTENSION {
+            return Err(SessionError::ExtensionTooLong);
+        }
+        let serve_at = later(self.serve_at, by).ok_or(SessionError::OutOfRange)?;
+        if let Some(current) = self.state(now).current() {
+            let started = to_std(self.serve_at - current.start().with_timezone(&Utc));
+            for stage in self
+                .course
+                .stages
+                .iter_mut()
+                .filter(|stage| stage.duration >= started)
+            {
+                stage.duration = stage.duration.saturating_add(by);
+            }
+        }
+        self.serve_at = serve_at;
+        Ok(())
+    }
+}
+
+/// `time` plus `by`, unless that is beyond the times chrono can represent.
+fn later(time: DateTime<Utc>, by: Duration) -> Option<DateTime<Utc>> {
+    time.checked_add_signed(chrono::Duration::from_std(by).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CourseDetails, Stage};
+
+    fn toast() -> Course {
+        let mut course = Course::new(&CourseDetails::new("0", "Toast"));
+        for stage in Stage::chain(vec![
+            Stage::new("Bread in toaster", "2min"),
+            Stage::new("Butter", "1min"),
+        ]) {
+            course.add(stage);
+        }
+        course
+    }
+
+    fn at(minutes: i64, seconds: i64) -> DateTime<Utc> {
+        "2022-01-10T19:00:00Z".parse::<DateTime<Utc>>().unwrap()
+            + chrono::Duration::minutes(minutes)
+            + chrono::Duration::seconds(seconds)
+    }
+
+    #[test]
+    fn state_follows_the_clock() {
+        let session = Session::starting("s", &toast(), at(0, 0)).unwrap();
+
+        assert_eq!(
+            session.state(at(0, 30)).to_string(),
+            "Toast running: Bread in toaster (1m 30s left)"
+        );
+        assert_eq!(
+            session.state(at(2, 0)).to_string(),
+            "Toast running: Butter (1m left)"
+        );
+        assert_eq!(session.state(at(3, 0)).status(), SessionStatus::Finished);
+    }
+
+    #[test]
+    fn pause_skip_and_extend_move_serving() {
+        let mut session = Session::new("s", &toast(), at(10, 0)).unwrap();
+        assert_eq!(
+            session.state(at(0, 0)).to_string(),
+            "Toast waiting (7m left)"
+        );
+
+        session.skip(at(0, 0)).unwrap();
+        assert_eq!(*session.state(at(0, 0)).serve_at(), at(3, 0));
+
+        session.pause(at(1, 0)).unwrap();
+        assert_eq!(session.pause(at(1, 0)), Err(SessionError::Paused));
+        assert_eq!(
+            session.state(at(5, 0)).to_string(),
+            "Toast paused: Bread in toaster (1m left)"
+        );
+        session.resume(at(5, 0)).unwrap();
+        session.extend(at(5, 0), Duration::from_secs(30)).unwrap();
+
+        assert_eq!(
+            session.state(at(5, 0)).to_string(),
+            "Toast running: Bread in toaster (1m 30s left)"
+        );
+        assert_eq!(*session.state(at(5, 0)).serve_at(), at(7, 30));
+    }
+
+    #[test]
+    fn extend_keeps_the_current_stage_start() {
+        let mut session = Session::starting("s", &toast(), at(0, 0)).unwrap();
+
+        session
+            .extend(at(1, 0), Duration::from_secs(5 * 60))
+            .unwrap();
+
+        let state = session.state(at(1, 0));
+        assert_eq!(
+            state.to_string(),
+            "Toast running: Bread in toaster (6m left)"
+        );
+        assert_eq!(state.stages()[0].start().with_timezone(&Utc), at(0, 0));
+        assert_eq!(state.stages()[1].start().with_timezone(&Utc), at(7, 0));
+        assert_eq!(*state.serve_at(), at(8, 0));
+    }
+
+    #[test]
+    fn extend_rejects_overlong_extensions() {
+        let mut session = Session::starting("s", &toast(), at(0, 0)).unwrap();
+
+        assert_eq!(
+            session.extend(at(1, 0), Duration::from_secs(u64::MAX)),
+            Err(SessionError::ExtensionTooLong)
+        );
+        assert_eq!(*session.state(at(1, 0)).serve_at(), at(3, 0));
+
+        let mut session = Session::new("s", &toast(), DateTime::<Utc>::MAX_UTC).unwrap();
+        assert_eq!(
+            session.extend(at(1, 0), Duration::from_secs(60)),
+            Err(SessionError::OutOfRange)
+        );
+        assert_eq!(
+            Session::new("s", &toast(), DateTime::<Utc>::MIN_UTC),
+            Err(SessionError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn remaining_at_counts_down_unless_paused() {
+        let mut session = Session::starting("s", &toast(), at(0, 0)).unwrap();
+        let state = session.state(at(0, 0));
+        assert_eq!(state.remaining_at(at(0, 45)), Duration::from_secs(75));
+        assert_eq!(state.remaining_at(at(5, 0)), Duration::ZERO);
+
+        session.pause(at(0, 30)).unwrap();
+        let state = session.state(at(0, 30));
+        assert_eq!(state.remaining_at(at(5, 0)), Duration::from_secs(90));
+    }
+}
@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Course, Planned, TimelineEntry};
+
+/// A read-only link to a course or meal, for guests without an account.
+///
+/// Anyone who knows the token can see what is shared until it is revoked or expires.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Share {
+    token: String,
+    shared: Planned,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl Share {
+    pub fn new(token: &str, shared: Planned, expires_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            token: token.to_owned(),
+            shared,
+            expires_at,
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn shared(&self) -> &Planned {
+        &self.shared
+    }
+
+    pub fn expires_at(&self) -> Option<&DateTime<Utc>> {
+        self.expires_at.as_ref()
+    }
+
+    /// Whether the link still works, i.e. it never expires or has not yet.
+    pub fn is_current(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+/// What a share link shows: the courses themselves, so a guest can cook them, and their
+/// merged timeline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SharedView {
+    name: String,
+    courses: Vec<Course>,
+    timeline: Vec<TimelineEntry>,
+}
+
+impl SharedView {
+    /// The view of `courses`, shared under `name`: the course's own, or the meal's, timed
+    /// from `serve_at` if the meal has a serving time.
+    pub fn new(name: &str, courses: Vec<Course>, serve_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            name: name.to_owned(),
+            timeline: crate::meal::timed(crate::meal::timeline(&courses), serve_at),
+            courses,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn courses(&self) -> &[Course] {
+        &self.courses
+    }
+
+    pub fn timeline(&self) -> &[TimelineEntry] {
+        &self.timeline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Duration;
+
+    #[test]
+    fn shares_work_until_they_expire() {
+        let now = Utc::now();
+        let course = Planned::Course("toast".to_owned());
+
+        assert!(Share::new("t", course.clone(), None).is_current(now));
+        assert!(Share::new("t", course.clone(), Some(now + Duration::hours(1))).is_current(now));
+        assert!(!Share::new("t", course, Some(now)).is_current(now));
+    }
+}
@@ -0,0 +1,629 @@
+//! Courses as plain text, for writing and editing by hand:
+//!
+//! ```text
+//! # Comments run to the end of the line.
+//! Toast:
+//!   Bread in toaster 1m
+//!   Butter 30s
+//!   Serving
+//!
+//! Roast chicken:
+//!   Roast 1h 20m [passive, oven 200]
+//!   Rest 15m [resting]
+//!   Serving
+//! ```
+//!
+//! Each course is its name followed by a `:`, then its stages indented below it in the
+//! order they are cooked, each ending with how long it lasts and optionally its kind and
+//! the resources it uses in `[…]`. Durations always have units, like `1h 20m`, so a
+//! number without one, as in `Bake at 250 10m`, is part of the name. A name which would
+//! otherwise be misread, e.g. one ending in a number or holding a `#`, is written in double
+//! quotes, escaping `"` and `\` with a `\`.
+//!
+//! A final `Serving` may be left out. A course which ends with something else marks its
+//! last stage `[serving]`, with its duration being how long before serving it starts.
+//! Only the stages are kept; ingredients and other metadata are not.
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use crate::{Course, CourseDetails, Resource, Stage, StageError, StageKind};
+
+/// Why text could not be read as courses; `line` and `column` count from 1.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum TextError {
+    /// A line which is neither indented as a stage nor a course name ending in `:`.
+    ExpectedCourse {
+        line: usize,
+        column: usize,
+    },
+    /// A stage before the name of any course.
+    StageOutsideCourse {
+        line: usize,
+        column: usize,
+    },
+    /// A stage after its course's `Serving`.
+    StageAfterServing {
+        line: usize,
+        column: usize,
+    },
+    EmptyName {
+        line: usize,
+        column: usize,
+    },
+    MissingDuration {
+        line: usize,
+        column: usize,
+    },
+    InvalidDuration {
+        line: usize,
+        column: usize,
+        input: String,
+        reason: String,
+    },
+    /// A `[` with no `]` after it.
+    Unclosed {
+        line: usize,
+        column: usize,
+    },
+    /// A `"` with no `"` after it.
+    UnclosedQuote {
+        line: usize,
+        column: usize,
+    },
+    /// Something in `[…]` which is neither a stage kind nor a resource.
+    UnknownAnnotation {
+        line: usize,
+        column: usize,
+        annotation: String,
+    },
+}
+
+impl TextError {
+    /// Where in the text the problem was found, as `(line, column)`.
+    pub fn position(&self) -> (usize, usize) {
+        match self {
+            TextError::ExpectedCourse { line, column }
+            | TextError::StageOutsideCourse { line, column }
+            | TextError::StageAfterServing { line, column }
+            | TextError::EmptyName { line, column }
+            | TextError::MissingDuration { line, column }
+            | TextError::InvalidDuration { line, column, .. }
+            | TextError::Unclosed { line, column }
+            | TextError::UnclosedQuote { line, column }
+            | TextError::UnknownAnnotation { line, column, .. } => (*line, *column),
+        }
+    }
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, column) = self.position();
+        write!(f, "line {}, column {}: ", line, column)?;
+        match self {
+            TextError::ExpectedCourse { .. } => {
+                write!(
+                    f,
+                    "expected a course name ending in ':', or an indented stage"
+                )
+            }
+            TextError::StageOutsideCourse { .. } => write!(f, "stage before any course"),
+            TextError::StageAfterServing { .. } => write!(f, "stage after 'Serving'"),
+            TextError::EmptyName { .. } => write!(f, "name must not be empty"),
+            TextError::MissingDuration { .. } => {
+                write!(f, "expected how long the stage lasts, e.g. '5m'")
+            }
+            TextError::InvalidDuration { input, reason, .. } => {
+                write!(f, "invalid duration '{}': {}", input, reason)
+            }
+            TextError::Unclosed { .. } => write!(f, "'[' is never closed"),
+            TextError::UnclosedQuote { .. } => write!(f, "'\"' is never closed"),
+            TextError::UnknownAnnotation { annotation, .. } => {
+                write!(f, "unknown kind or resource '{}'", annotation)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// A line being read, to report positions within it.
+struct Line<'a> {
+    text: &'a str,
+    number: usize,
+}
+
+impl<'a> Line<'a> {
+    /// The column of `part`, which must be a slice of this line.
+    fn column(&self, part: &str) -> usize {
+        let offset = part.as_ptr() as usize - self.text.as_ptr() as usize;
+        self.text[..offset].chars().count() + 1
+    }
+
+    fn at(&self, part: &str) -> (usize, usize) {
+        (self.number, self.column(part))
+    }
+}
+
+/// A course as it is read, before its stages are chained.
+struct Draft {
+    details: CourseDetails,
+    stages: Vec<Stage>,
+    /// The stage marked `[serving]`, or `Serving` itself, once it has been read.
+    last: Option<Stage>,
+}
+
+impl Draft {
+    fn finish(self) -> Course {
+        match self.last {
+            Some(last) => Course::from_lengths_ending(&self.details, self.stages, last),
+            None => Course::from_lengths(&self.details, self.stages),
+        }
+    }
+}
+
+/// Split a name in double quotes off the start of `text`, undoing its escapes, and return
+/// it with the rest of `text`; `None` if the quote is never closed.
+fn unquote(text: &str) -> Option<(String, &str)> {
+    let mut name = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => name.extend(chars.next().map(|(_, c)| c)),
+            '"' => return Some((name, &text[i + 1..])),
+            c => name.push(c),
+        }
+    }
+    None
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Whether `name` must be quoted to be read back the same.
+fn needs_quotes(name: &str) -> bool {
+    name.starts_with('"')
+        || name.contains(['#', '['])
+        || name
+            .split_whitespace()
+            .last()
+            .is_some_and(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// `text` up to any comment, skipping a quoted name at its start.
+fn strip_comment(text: &str) -> &str {
+    let trimmed = text.trim_start();
+    let from = if trimmed.starts_with('"') {
+        unquote(trimmed).map_or(text.len(), |(_, rest)| text.len() - rest.len())
+    } else {
+        text.len() - trimmed.len()
+    };
+    match text[from..].find('#') {
+        Some(comment) => &text[..from + comment],
+        None => text,
+    }
+}
+
+fn annotation(line: &Line, text: &str, stage: Stage) -> Result<Stage, TextError> {
+    let unknown = || {
+        let (line, column) = line.at(text);
+        TextError::UnknownAnnotation {
+            line,
+            column,
+            annotation: text.to_owned(),
+        }
+    };
+    Ok(match text.split_whitespace().collect_vec().as_slice() {
+        ["active"] => stage.with_kind(StageKind::Active),
+        ["passive"] => stage.with_kind(StageKind::Passive),
+        ["resting"] => stage.with_kind(StageKind::Resting),
+        ["hob"] => stage.using(Resource::Hob),
+        ["microwave"] => stage.using(Resource::Microwave),
+        ["cook"] => stage.using(Resource::Cook),
+        ["oven", temperature] => {
+            stage.using(Resource::Oven(temperature.parse().map_err(|_| unknown())?))
+        }
+        _ => return Err(unknown()),
+    })
+}
+
+/// Whether `word` is a number followed by a unit, like `20m` or `1h20m`.
+fn looks_like_duration(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit()) && word.ends_with(char::is_alphabetic)
+}
+
+/// Parse a stage, e.g. `Roast 1h 20m [passive, oven 200]`, without its indentation.
+///
+/// Also returns whether it is the course's last stage, i.e. `Serving` or marked
+/// `[serving]`.
+fn stage(line: &Line, text: &str) -> Result<(Stage, bool), TextError> {
+    if text == "Serving" {
+        return Ok((Stage::new("Serving", "0s"), true));
+    }
+    let (quoted, text) = if text.starts_with('"') {
+        let (name, rest) = unquote(text).ok_or_else(|| {
+            let (line, column) = line.at(text);
+            TextError::UnclosedQuote { line, column }
+        })?;
+        (Some(name), rest)
+    } else {
+        (None, text)
+    };
+    let (text, annotations) = match text.find('[') {
+        Some(open) => {
+            let close = text[open..].find(']').ok_or_else(|| {
+                let (line, column) = line.at(&text[open..]);
+                TextError::Unclosed { line, column }
+            })?;
+            (&text[..open], Some(&text[open + 1..open + close]))
+        }
+        None => (text, None),
+    };
+    // The duration is made of the trailing words which are each a number with a unit,
+    // e.g. `1h 20m`, so that bare numbers, as in `Gas mark 6 20m`, stay in the name.
+    // Failing that, a last word which looks like one is reported as an invalid duration.
+    let words = text.split_whitespace().collect_vec();
+    let split = if quoted.is_some() {
+        0
+    } else {
+        words
+            .iter()
+            .rposition(|word| {
+                !(looks_like_duration(word) && humantime::parse_duration(word).is_ok())
+            })
+            .map_or(0, |last_name| last_name + 1)
+    };
+    let split = match words.last() {
+        Some(last) if split == words.len() && looks_like_duration(last) => split - 1,
+        _ => split,
+    };
+    let offset = |word: &str| word.as_ptr() as usize - text.as_ptr() as usize;
+    let name = match (&quoted, words.get(split)) {
+        (Some(name), _) => name.as_str(),
+        (None, Some(word)) => text[..offset(word)].trim(),
+        (None, None) => text.trim(),
+    };
+    if name.trim().is_empty() {
+        let (line, column) = line.at(text);
+        return Err(TextError::EmptyName { line, column });
+    }
+    let duration = match words.get(split) {
+        Some(word) => text[offset(word)..].trim_end(),
+        None => {
+            let (line, column) = line.at(&text[text.trim_end().len()..]);
+            return Err(TextError::MissingDuration { line, column });
+        }
+    };
+    let mut stage = Stage::try_new(name, duration).map_err(|e| {
+        let (line, column) = line.at(duration);
+        TextError::InvalidDuration {
+            line,
+            column,
+            input: duration.to_owned(),
+            reason: match e {
+                StageError::InvalidDuration { reason, .. } => reason,
+                e => e.to_string(),
+            },
+        }
+    })?;
+    let mut last = false;
+    for part in annotations.into_iter().flat_map(|a| a.split(',')) {
+        match part.trim() {
+            "" => {}
+            "serving" => last = true,
+            part => stage = annotation(line, part, stage)?,
+        }
+    }
+    Ok((stage, last))
+}
+
+/// Read every course in `text`, each with an empty id.
+pub fn parse(text: &str) -> Result<Vec<Course>, TextError> {
+    let mut courses = Vec::new();
+    let mut draft: Option<Draft> = None;
+    for (number, text) in text.lines().enumerate() {
+        let line = Line {
+            text,
+            number: number + 1,
+        };
+        let content = strip_comment(text).trim_end();
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed.len() < content.len() {
+            let draft = draft.as_mut().ok_or_else(|| {
+                let (line, column) = line.at(trimmed);
+                TextError::StageOutsideCourse { line, column }
+            })?;
+            if draft.last.is_some() {
+                let (line, column) = line.at(trimmed);
+                return Err(TextError::StageAfterServing { line, column });
+            }
+            match stage(&line, trimmed)? {
+                (stage, true) => draft.last = Some(stage),
+                (stage, false) => draft.stages.push(stage),
+            }
+        } else {
+            let expected = TextError::ExpectedCourse {
+                line: line.number,
+                column: 1,
+            };
+            let name = content.strip_suffix(':').ok_or(expected.clone())?;
+            let name = match name.strip_prefix('"') {
+                Some(_) => match unquote(name) {
+                    Some((name, "")) => name,
+                    Some(_) => return Err(expected),
+                    None => {
+                        return Err(TextError::UnclosedQuote {
+                            line: line.number,
+                            column: 1,
+                        })
+                    }
+                },
+                None => name.to_owned(),
+            };
+            if name.trim().is_empty() {
+                return Err(TextError::EmptyName {
+                    line: line.number,
+                    column: 1,
+                });
+            }
+            courses.extend(draft.take().map(Draft::finish));
+            draft = Some(Draft {
+                details: CourseDetails::new("", name.trim()),
+                stages: Vec::new(),
+                last: None,
+            });
+        }
+    }
+    courses.extend(draft.map(Draft::finish));
+    Ok(courses)
+}
+
+fn write_stage(text: &mut String, stage: &Stage, last: bool) {
+    let annotations = last
+        .then(|| "serving".to_owned())
+        .into_iter()
+        .chain((stage.kind() != StageKind::default()).then(|| stage.kind().to_string()))
+        .chain(stage.resources().iter().map(|resource| match resource {
+            Resource::Oven(temperature) => format!("oven {}", temperature),
+            Resource::Hob => "hob".to_owned(),
+            Resource::Microwave => "microwave".to_owned(),
+            Resource::Cook => "cook".to_owned(),
+        }))
+        .join(", ");
+    let name = match needs_quotes(stage.name()) {
+        true => quote(stage.name()),
+        false => stage.name().to_owned(),
+    };
+    text.push_str(&format!("  {} {}", name, stage.duration()));
+    if !annotations.is_empty() {
+        text.push_str(&format!(" [{}]", annotations));
+    }
+    text.push('\n');
+}
+
+/// Write a course so that `parse` reads back the same stages.
+pub fn write(course: &Course) -> String {
+    let name = course.details().name();
+    let mut text = match name.starts_with('"') || name.contains('#') {
+        true => format!("{}:\n", quote(name)),
+        false => format!("{}:\n", name),
+    };
+    for stage in course.stages() {
+        write_stage(&mut text, &stage, false);
+    }
+    match course.last_stage() {
+        Some(last)
+            if last.name() != "Serving"
+                || !last.duration.is_zero()
+                || last.kind() != StageKind::default()
+                || !last.resources().is_empty() =>
+        {
+            write_stage(&mut text, last, true)
+        }
+        _ => text.push_str("  Serving\n"),
+    }
+    text
+}
+
+/// Write several courses, separated by blank lines.
+pub fn write_all<'a>(courses: impl IntoIterator<Item = &'a Course>) -> String {
+    courses.into_iter().map(write).join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stages(course: &Course) -> Vec<(String, String, StageKind)> {
+        course
+            .stages()
+            .map(|stage| {
+                (
+                    stage.name().to_owned(),
+                    stage.duration().to_string(),
+                    stage.kind(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn courses_are_read_with_their_stages_in_order() {
+        let courses = parse(
+            "# Breakfast\nToast:\n  Bread in toaster 1m\n  Butter 30s\n  Serving\n\n\
+             Roast chicken:\n  Roast 1h 20m [passive, oven 200] # hot\n  Rest 15m [resting]\n",
+        )
+        .unwrap();
+
+        assert_eq!(courses.len(), 2);
+        assert_eq!(courses[0].details().name(), "Toast");
+        assert_eq!(
+            stages(&courses[0]),
+            vec![
+                (
+                    "Bread in toaster".to_owned(),
+                    "1m".to_owned(),
+                    StageKind::Active
+                ),
+                ("Butter".to_owned(), "30s".to_owned(), StageKind::Active),
+            ]
+        );
+        assert_eq!(
+            stages(&courses[1]),
+            vec![
+                ("Roast".to_owned(), "1h 20m".to_owned(), StageKind::Passive),
+                ("Rest".to_owned(), "15m".to_owned(), StageKind::Resting),
+            ]
+        );
+        assert_eq!(
+            courses[1].stages().next().unwrap().resources(),
+            &[Resource::Oven(200)]
+        );
+    }
+
+    #[test]
+    fn bare_numbers_stay_in_the_name() {
+        let courses =
+            parse("Pizza:\n  Preheat to gas mark 6 20m\n  Bake at 250 10m\n  Rest 1h20m 5m\n")
+                .unwrap();
+
+        assert_eq!(
+            stages(&courses[0]),
+            vec![
+                (
+                    "Preheat to gas mark 6".to_owned(),
+                    "20m".to_owned(),
+                    StageKind::Active
+                ),
+                (
+                    "Bake at 250".to_owned(),
+                    "10m".to_owned(),
+                    StageKind::Active
+                ),
+                ("Rest".to_owned(), "1h 25m".to_owned(), StageKind::Active),
+            ]
+        );
+        assert_eq!(
+            parse("Pizza:\n  Bake at 250\n").unwrap_err(),
+            TextError::MissingDuration {
+                line: 2,
+                column: 14
+            }
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let error = |text| parse(text).unwrap_err();
+
+        let invalid = error("Toast:\n  Bread in toaster 1x\n");
+        assert_eq!(invalid.position(), (2, 20));
+        assert!(matches!(invalid, TextError::InvalidDuration { input, .. } if input == "1x"));
+        assert_eq!(
+            error("Toast:\n  Bread in toaster\n"),
+            TextError::MissingDuration {
+                line: 2,
+                column: 19
+            }
+        );
+        assert_eq!(
+            error("Toast\n"),
+            TextError::ExpectedCourse { line: 1, column: 1 }
+        );
+        assert_eq!(
+            error("  Butter 30s\n"),
+            TextError::StageOutsideCourse { line: 1, column: 3 }
+        );
+        assert_eq!(
+            error("Toast:\n  Serving\n  Butter 30s\n"),
+            TextError::StageAfterServing { line: 3, column: 3 }
+        );
+        assert_eq!(
+            error("Roast:\n  Roast 1h [passive, grill]\n"),
+            TextError::UnknownAnnotation {
+                line: 2,
+                column: 22,
+                annotation: "grill".to_owned(),
+            }
+        );
+        assert_eq!(
+            error("Roast:\n  Roast 1h [passive\n"),
+            TextError::Unclosed {
+                line: 2,
+                column: 12
+            }
+        );
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        assert_eq!(
+            parse("Crème brûlée:\n  Brûlée 2x\n")
+                .unwrap_err()
+                .position(),
+            (2, 10)
+        );
+    }
+
+    #[test]
+    fn written_courses_read_back_the_same() {
+        let details = CourseDetails::new("", "Roast chicken");
+        let course = Course::from_lengths(
+            &details,
+            vec![
+                Stage::new("Prep", "10m"),
+                Stage::new("Roast", "1h 20m")
+                    .with_kind(StageKind::Passive)
+                    .using(Resource::Oven(200))
+                    .using(Resource::Hob),
+                Stage::new("Carve", "0s"),
+            ],
+        );
+        let toast = Course::from_lengths(
+            &CourseDetails::new("", "Toast"),
+            vec![Stage::new("Bread in toaster", "1m")],
+        );
+
+        let text = write_all([&course, &toast]);
+        let read = parse(&text).unwrap();
+
+        assert_eq!(read, vec![course, toast]);
+        assert_eq!(write_all(&read), text);
+    }
+
+    #[test]
+    fn awkward_names_and_last_stages_read_back_the_same() {
+        let pizza = Course::from_lengths_ending(
+            &CourseDetails::new("", "Pizza #2"),
+            vec![
+                Stage::new("Preheat to gas mark 6", "20m"),
+                Stage::new("Bake at 250", "10m"),
+                Stage::new("Top with \"basil\" [optional]", "1m"),
+            ],
+            Stage::new("Rest", "5m").with_kind(StageKind::Resting),
+        );
+        let salad = Course::from_lengths_ending(
+            &CourseDetails::new("", "\"Salad\""),
+            vec![Stage::new("Wash", "5m")],
+            Stage::new("Dress", "0s"),
+        );
+
+        let text = write_all([&pizza, &salad]);
+        let read = parse(&text).unwrap();
+
+        assert_eq!(read, vec![pizza, salad]);
+        assert_eq!(write_all(&read), text);
+        assert!(text.contains("  Rest 5m [serving, resting]\n"));
+        assert_eq!(
+            parse("Toast:\n  \"Bread 2\n").unwrap_err(),
+            TextError::UnclosedQuote { line: 2, column: 3 }
+        );
+    }
+}
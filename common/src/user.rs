@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// The shortest password an account may have.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Someone with an account, as clients see them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserDetails {
+    id: String,
+    name: String,
+}
+
+impl UserDetails {
+    pub fn new(id: &str, name: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A name and password, to register or sign in with.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct Credentials {
+    name: String,
+    password: String,
+}
+
+/// Leaves the password out, so that credentials can be logged safely.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why credentials cannot be used for a new account.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum CredentialsError {
+    EmptyName,
+    ShortPassword { min: usize },
+}
+
+impl fmt::Display for CredentialsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CredentialsError::EmptyName => write!(f, "user name must not be empty"),
+            CredentialsError::ShortPassword { min } => {
+                write!(f, "password must be at least {} characters", min)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CredentialsError {}
+
+impl Credentials {
+    pub fn new(name: &str, password: &str) -> Self {
+        Self {
+            name: name.trim().to_owned(),
+            password: password.to_owned(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.trim()
+    }
+
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    /// Check the credentials are good enough for a new account.
+    pub fn validate(&self) -> Result<(), CredentialsError> {
+        if self.name().is_empty() {
+            return Err(CredentialsError::EmptyName);
+        }
+        if self.password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(CredentialsError::ShortPassword {
+                min: MIN_PASSWORD_LENGTH,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_accounts_need_a_name_and_long_password() {
+        assert_eq!(Credentials::new("ann", "correct horse").validate(), Ok(()));
+        assert_eq!(
+            Credentials::new("  ", "correct horse").validate(),
+            Err(CredentialsError::EmptyName)
+        );
+        assert_eq!(
+            Credentials::new("ann", "horse").validate(),
+            Err(CredentialsError::ShortPassword { min: 8 })
+        );
+        assert!(!format!("{:?}", Credentials::new("ann", "horse")).contains("horse"));
+    }
+}
@@ -0,0 +1,152 @@
+use js_sys::{Function, Promise, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    AudioContext, Notification, NotificationOptions, NotificationPermission, OscillatorType,
+};
+
+/// The sound played when a stage is due.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tone {
+    Beep,
+    Chime,
+    Bell,
+    Off,
+}
+
+impl Tone {
+    pub const ALL: [Tone; 4] = [Tone::Beep, Tone::Chime, Tone::Bell, Tone::Off];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tone::Beep => "beep",
+            Tone::Chime => "chime",
+            Tone::Bell => "bell",
+            Tone::Off => "off",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|tone| tone.name() == name)
+            .unwrap_or(Tone::Beep)
+    }
+
+    /// Frequencies in Hz, each played for 0.3s, and the waveform to play them with.
+    fn notes(&self) -> (&'static [f32], OscillatorType) {
+        match self {
+            Tone::Beep => (&[880.0, 880.0, 880.0], OscillatorType::Square),
+            Tone::Chime => (&[659.3, 784.0, 1046.5], OscillatorType::Sine),
+            Tone::Bell => (&[1318.5, 1318.5], OscillatorType::Triangle),
+            Tone::Off => (&[], OscillatorType::Sine),
+        }
+    }
+}
+
+/// How the cook wants to be told a stage is due, kept in the browser's local storage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlarmSettings {
+    pub tone: Tone,
+    /// From 0 to 1.
+    pub volume: f32,
+}
+
+impl Default for AlarmSettings {
+    fn default() -> Self {
+        Self {
+            tone: Tone::Beep,
+            volume: 0.5,
+        }
+    }
+}
+
+const TONE_KEY: &str = "alarm_tone";
+const VOLUME_KEY: &str = "alarm_volume";
+
+impl AlarmSettings {
+    pub fn load() -> Self {
+        let storage = match gloo_utils::window().local_storage() {
+            Ok(Some(storage)) => storage,
+            _ => return Self::default(),
+        };
+        let get = |key| storage.get_item(key).ok().flatten();
+        Self {
+            tone: get(TONE_KEY).map_or(Tone::Beep, |tone| Tone::from_name(&tone)),
+            volume: get(VOLUME_KEY)
+                .and_then(|volume| volume.parse().ok())
+                .unwrap_or(0.5),
+        }
+    }
+
+    pub fn save(&self) {
+        if let Ok(Some(storage)) = gloo_utils::window().local_storage() {
+            let _ = storage.set_item(TONE_KEY, self.tone.name());
+            let _ = storage.set_item(VOLUME_KEY, &self.volume.to_string());
+        }
+    }
+}
+
+/// Play the alarm; `audio` should be created while handling a click, or browsers keep it muted.
+pub fn play(audio: &AudioContext, settings: AlarmSettings) -> Result<(), JsValue> {
+    let (notes, wave) = settings.tone.notes();
+    let gain = audio.create_gain()?;
+    gain.gain().set_value(settings.volume);
+    gain.connect_with_audio_node(&audio.destination())?;
+    for (i, frequency) in notes.iter().enumerate() {
+        let oscillator = audio.create_oscillator()?;
+        oscillator.set_type(wave);
+        oscillator.frequency().set_value(*frequency);
+        oscillator.connect_with_audio_node(&gain)?;
+        let start = audio.current_time() + i as f64 * 0.4;
+        oscillator.start_with_when(start)?;
+        oscillator.stop_with_when(start + 0.3)?;
+    }
+    Ok(())
+}
+
+/// Ask to show notifications, unless the cook has already answered.
+pub fn request_notifications() {
+    if Notification::permission() == NotificationPermission::Default {
+        let _ = Notification::request_permission();
+    }
+}
+
+pub fn notify(title: &str, body: &str) {
+    if Notification::permission() != NotificationPermission::Granted {
+        return;
+    }
+    let options = NotificationOptions::new();
+    options.set_body(body);
+    if let Err(e) = Notification::new_with_options(title, &options) {
+        log::warn!("cannot show notification: {e:?}");
+    }
+}
+
+/// Keep the screen on, returning the lock to release, if the browser supports it.
+///
+/// `navigator.wakeLock` is still an unstable API in `web-sys`, so it is called dynamically.
+pub async fn lock_screen() -> Option<JsValue> {
+    let navigator = gloo_utils::window().navigator();
+    let wake_lock = Reflect::get(&navigator, &"wakeLock".into())
+        .ok()
+        .filter(|wake_lock| !wake_lock.is_undefined())?;
+    let request: Function = Reflect::get(&wake_lock, &"request".into())
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    let promise: Promise = request
+        .call1(&wake_lock, &"screen".into())
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    wasm_bindgen_futures::JsFuture::from(promise).await.ok()
+}
+
+pub fn unlock_screen(lock: &JsValue) {
+    let release = Reflect::get(lock, &"release".into())
+        .ok()
+        .and_then(|release| release.dyn_into::<Function>().ok());
+    if let Some(release) = release {
+        let _ = release.call0(lock);
+    }
+}
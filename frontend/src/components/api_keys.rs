@@ -0,0 +1,135 @@
+use chrono::{DateTime, Local, Utc};
+use common::{ApiError, ApiKeyDetails, CreatedApiKey, NewApiKey, Scope};
+use reqwasm::http::Method;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::{
+    function_component, html, use_effect_with_deps, use_node_ref, use_state, Callback,
+    UseStateHandle,
+};
+
+use crate::{request, SERVER};
+
+fn fetch_keys(keys: UseStateHandle<Vec<ApiKeyDetails>>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let fetched: Vec<ApiKeyDetails> = request!(&format!("{SERVER}/api_keys"), Method::GET);
+        keys.set(fetched);
+    });
+}
+
+fn when(at: &DateTime<Utc>) -> String {
+    at.with_timezone(&Local)
+        .format("%e %b %Y %H:%M")
+        .to_string()
+}
+
+/// Personal API keys, for scripts to use the backend without signing in.
+#[function_component(ApiKeys)]
+pub fn api_keys() -> Html {
+    let keys = use_state(Vec::<ApiKeyDetails>::new);
+    // Shown once, straight after it is made.
+    let created = use_state(|| None::<String>);
+    let error = use_state(|| None::<String>);
+    let name_ref = use_node_ref();
+    let scope_ref = use_node_ref();
+    {
+        let keys = keys.clone();
+        use_effect_with_deps(
+            move |_| {
+                fetch_keys(keys);
+                || ()
+            },
+            (),
+        );
+    }
+
+    let add_key = {
+        let keys = keys.clone();
+        let created = created.clone();
+        let error = error.clone();
+        let name_ref = name_ref.clone();
+        let scope_ref = scope_ref.clone();
+        Callback::from(move |_| {
+            let name = match name_ref.cast::<HtmlInputElement>() {
+                Some(input) => input.value(),
+                None => return,
+            };
+            let scope = match scope_ref.cast::<HtmlSelectElement>() {
+                Some(select) if select.value() == "write" => Scope::Write,
+                _ => Scope::Read,
+            };
+            let new = NewApiKey::new(&name, scope);
+            let keys = keys.clone();
+            let created = created.clone();
+            let error = error.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let response = crate::api(&format!("{SERVER}/api_key"))
+                    .method(Method::POST)
+                    .body(serde_json::to_string(&new).unwrap())
+                    .send()
+                    .await
+                    .unwrap();
+                if response.ok() {
+                    let key: CreatedApiKey = response.json().await.unwrap();
+                    error.set(None);
+                    created.set(Some(key.key().to_owned()));
+                    fetch_keys(keys);
+                } else {
+                    let e: ApiError = response.json().await.unwrap();
+                    error.set(Some(e.message().to_owned()));
+                }
+            });
+        })
+    };
+
+    let entries = keys.iter().map(|key| {
+        let revoke = {
+            let keys = keys.clone();
+            let id = key.id().to_owned();
+            move |_| {
+                let keys = keys.clone();
+                let id = id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let response = crate::api(&format!("{SERVER}/api_key/{id}"))
+                        .method(Method::DELETE)
+                        .send()
+                        .await
+                        .unwrap();
+                    if response.ok() {
+                        fetch_keys(keys);
+                    }
+                });
+            }
+        };
+        let used = key
+            .last_used_at()
+            .map(|at| format!("last used {}", when(at)))
+            .unwrap_or_else(|| "never used".to_owned());
+        html! {
+            <li>
+                { key.name() }{ " (" }{ key.scope() }{ "), made " }{ when(key.created_at()) }{ ", " }{ used }
+                <span style="cursor: pointer; padding-left: 0.5em;" onclick={revoke} title="Revoke">{ "\u{2716}" }</span>
+            </li>
+        }
+    });
+
+    html! {
+        <div class={"api-keys"}>
+            <input type="text" ref={name_ref} placeholder="Key name …" />
+            <select ref={scope_ref}>
+                <option value="read">{ "Read only" }</option>
+                <option value="write">{ "Read and write" }</option>
+            </select>
+            <button onclick={add_key}>{ "Make a key" }</button>
+            if let Some(error) = error.as_ref() {
+                <p style="color: #c00;">{ error }</p>
+            }
+            if let Some(key) = created.as_ref() {
+                <p>
+                    { "Copy this key now; it will not be shown again:" }
+                    <input type="text" readonly=true value={key.clone()} style="width: 100%;" />
+                </p>
+            }
+            <ul>{ for entries }</ul>
+        </div>
+    }
+}
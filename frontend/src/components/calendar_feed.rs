@@ -0,0 +1,196 @@
+use chrono::Local;
+use common::{ApiError, CourseDetails, MealDetails, Plan, Planned};
+use reqwasm::http::Method;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::{
+    function_component, html, use_effect_with_deps, use_node_ref, use_state, Callback, Properties,
+    UseStateHandle,
+};
+
+use crate::components::schedule::schedule_query;
+use crate::{request, SERVER};
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct CalendarFeedProps {
+    pub courses: Vec<CourseDetails>,
+    pub meals: Vec<MealDetails>,
+}
+
+/// The feed's token, kept in local storage so its URL stays the same for this browser.
+const TOKEN_KEY: &str = "calendar_token";
+
+fn stored_token() -> Option<String> {
+    gloo_utils::window()
+        .local_storage()
+        .ok()
+        .flatten()
+        .and_then(|storage| storage.get_item(TOKEN_KEY).ok().flatten())
+}
+
+/// Load this browser's feed, starting a new one if it has none or the server has lost it.
+fn load_feed(token: UseStateHandle<Option<String>>, plans: UseStateHandle<Vec<Plan>>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Some(stored) = stored_token() {
+            let response = crate::api(&format!("{SERVER}/calendar/{stored}/plans"))
+                .method(Method::GET)
+                .send()
+                .await
+                .unwrap();
+            if response.ok() {
+                plans.set(response.json().await.unwrap());
+                token.set(Some(stored));
+                return;
+            }
+        }
+        let created: String = request!(&format!("{SERVER}/calendar"), Method::POST);
+        if let Ok(Some(storage)) = gloo_utils::window().local_storage() {
+            let _ = storage.set_item(TOKEN_KEY, &created);
+        }
+        plans.set(Vec::new());
+        token.set(Some(created));
+    });
+}
+
+/// Plan courses and meals for later, and subscribe to them from a calendar app.
+#[function_component(CalendarFeed)]
+pub fn calendar_feed(CalendarFeedProps { courses, meals }: &CalendarFeedProps) -> Html {
+    let token = use_state(|| None::<String>);
+    let plans = use_state(Vec::<Plan>::new);
+    let planned_ref = use_node_ref();
+    let serve_at_ref = use_node_ref();
+
+    {
+        let token = token.clone();
+        let plans = plans.clone();
+        use_effect_with_deps(
+            move |_| {
+                load_feed(token, plans);
+                || ()
+            },
+            (),
+        );
+    }
+
+    let token = match token.as_ref() {
+        Some(token) => token.clone(),
+        None => return html! {},
+    };
+
+    let add_plan = {
+        let plans = plans.clone();
+        let token = token.clone();
+        let planned_ref = planned_ref.clone();
+        let serve_at_ref = serve_at_ref.clone();
+        Callback::from(move |_| {
+            let (planned, serve_at) = match (
+                planned_ref.cast::<HtmlSelectElement>(),
+                serve_at_ref.cast::<HtmlInputElement>(),
+            ) {
+                (Some(planned), Some(serve_at)) => (planned.value(), serve_at.value()),
+                _ => return,
+            };
+            if planned.is_empty() || serve_at.is_empty() {
+                return;
+            }
+            let plans = plans.clone();
+            let token = token.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let query = schedule_query(&serve_at);
+                let response = crate::api(&format!("{SERVER}/calendar/{token}/{planned}?{query}"))
+                    .method(Method::POST)
+                    .send()
+                    .await
+                    .unwrap();
+                if !response.ok() {
+                    let error: ApiError = response.json().await.unwrap();
+                    let _ = gloo_utils::window().alert_with_message(error.message());
+                    return;
+                }
+                let plan: Plan = response.json().await.unwrap();
+                let mut changed = (*plans).clone();
+                changed.push(plan);
+                plans.set(changed);
+            });
+        })
+    };
+
+    let name = |planned: &Planned| match planned {
+        Planned::Course(id) => courses
+            .iter()
+            .find(|course| course.id() == id)
+            .map(|course| course.name().to_owned()),
+        Planned::Meal(id) => meals
+            .iter()
+            .find(|meal| meal.id() == id)
+            .map(|meal| meal.name().to_owned()),
+    };
+
+    let mut upcoming: Vec<&Plan> = plans.iter().collect();
+    upcoming.sort_by_key(|plan| *plan.serve_at());
+    let entries = upcoming.into_iter().map(|plan| {
+        let remove = {
+            let plans = plans.clone();
+            let token = token.clone();
+            let id = plan.id().to_owned();
+            Callback::from(move |_| {
+                let plans = plans.clone();
+                let token = token.clone();
+                let id = id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let response = crate::api(&format!("{SERVER}/calendar/{token}/plan/{id}"))
+                        .method(Method::DELETE)
+                        .send()
+                        .await
+                        .unwrap();
+                    if response.ok() {
+                        plans.set(
+                            plans
+                                .iter()
+                                .filter(|plan| plan.id() != id)
+                                .cloned()
+                                .collect(),
+                        );
+                    }
+                });
+            })
+        };
+        html! {
+            <li>
+                { plan.serve_at().with_timezone(&Local).format("%a %e %b %H:%M").to_string() }{ " — " }
+                { name(plan.planned()).unwrap_or_else(|| "(deleted)".to_owned()) }
+                <span style="cursor: pointer; padding-left: 0.5em;" onclick={remove} title="Remove">{ "\u{2716}" }</span>
+            </li>
+        }
+    });
+
+    let feed = format!("{SERVER}/calendar/{token}.ics");
+    let subscribe = match feed.split_once("://") {
+        Some((_, rest)) => format!("webcal://{rest}"),
+        None => feed.clone(),
+    };
+
+    html! {
+        <div class={"calendar-feed"}>
+            <select ref={planned_ref}>
+                <optgroup label="Courses">
+                    { for courses.iter().map(|course| html! {
+                        <option value={format!("course/{}", course.id())}>{ course.name() }</option>
+                    }) }
+                </optgroup>
+                <optgroup label="Meals">
+                    { for meals.iter().map(|meal| html! {
+                        <option value={format!("meal/{}", meal.id())}>{ meal.name() }</option>
+                    }) }
+                </optgroup>
+            </select>
+            <input type="datetime-local" ref={serve_at_ref} />
+            <button onclick={add_plan}>{ "Plan" }</button>
+            <ul>{ for entries }</ul>
+            <p>
+                <a href={subscribe}>{ "Subscribe in your calendar" }</a>
+                <br />
+                <input type="text" readonly=true value={feed} style="width: 100%;" />
+            </p>
+        </div>
+    }
+}
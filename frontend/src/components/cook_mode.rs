@@ -0,0 +1,384 @@
+use crate::components::alarm::{self, AlarmSettings, Tone};
+use chrono::{DateTime, Utc};
+use common::{
+    Course, CourseDetails, ServerEvent, Session, SessionError, SessionState, SessionStatus,
+};
+use gloo_timers::callback::Interval;
+use reqwasm::http::Method;
+use std::time::Duration;
+use wasm_bindgen::JsValue;
+use web_sys::{AudioContext, HtmlInputElement, HtmlSelectElement};
+use yew::{
+    function_component, html, use_effect_with_deps, use_mut_ref, use_state, Callback, Properties,
+    TargetCast, UseStateHandle,
+};
+
+use crate::SERVER;
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct CookModeProps {
+    pub course_details: CourseDetails,
+    pub on_close: Callback<()>,
+    /// Count down through this course in the browser instead of in a session kept by the
+    /// server, for guests on a share link.
+    #[prop_or_default]
+    pub local: Option<Course>,
+}
+
+/// How long `+1 min` gives the current stage.
+const EXTEND_BY: Duration = Duration::from_secs(60);
+
+/// `m:ss`, or `h:mm:ss` once an hour or more is left.
+fn countdown(left: Duration) -> String {
+    let seconds = left.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+/// Send a session control such as `pause`, showing the session as it now is.
+fn control(session: UseStateHandle<Option<SessionState>>, path: String) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let response = crate::api(&format!("{SERVER}/session/{path}"))
+            .method(Method::POST)
+            .send()
+            .await
+            .unwrap();
+        if !response.ok() {
+            log::warn!("session {path} failed: {}", response.status());
+            return;
+        }
+        session.set(Some(response.json().await.unwrap()));
+    });
+}
+
+/// Show `event` if it is about session `id`, sounding the alarm as a stage falls due.
+fn follow(
+    event: ServerEvent,
+    id: &str,
+    session: &UseStateHandle<Option<SessionState>>,
+    audio: Option<&AudioContext>,
+    settings: AlarmSettings,
+) {
+    match event {
+        ServerEvent::SessionUpdated { session: changed } if changed.id() == id => {
+            session.set(Some(changed))
+        }
+        ServerEvent::StageStarted {
+            session: changed,
+            stage,
+        } if changed.id() == id => {
+            alarm::notify(&format!("Start: {stage}"), changed.course_name());
+            session.set(Some(changed));
+        }
+        ServerEvent::StageDue {
+            session: changed,
+            stage,
+        } if changed.id() == id => {
+            if let Some(audio) = audio {
+                let _ = alarm::play(audio, settings);
+            }
+            alarm::notify(&format!("Time's up: {stage}"), changed.course_name());
+            session.set(Some(changed));
+        }
+        _ => {}
+    }
+}
+
+/// A full-screen countdown through a course, with an alarm as each stage falls due.
+#[function_component(CookMode)]
+pub fn cook_mode(
+    CookModeProps {
+        course_details,
+        on_close,
+        local,
+    }: &CookModeProps,
+) -> Html {
+    let session = use_state(|| None::<SessionState>);
+    // Only in local mode; otherwise the session lives on the server.
+    let local_session = use_mut_ref(|| None::<Session>);
+    let now = use_state(Utc::now);
+    let settings = use_state(AlarmSettings::load);
+    // Read by the event stream callback, which outlives any one render.
+    let alarm_settings = use_mut_ref(AlarmSettings::load);
+    // Created while handling the click which opened cook mode, so browsers let it play.
+    let audio = use_mut_ref(|| AudioContext::new().ok());
+    let wake_lock = use_mut_ref(|| None::<JsValue>);
+
+    {
+        let session = session.clone();
+        let wake_lock = wake_lock.clone();
+        let local = local.clone();
+        let local_session = local_session.clone();
+        use_effect_with_deps(
+            move |id: &String| {
+                alarm::request_notifications();
+                {
+                    let wake_lock = wake_lock.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        *wake_lock.borrow_mut() = alarm::lock_screen().await;
+                    });
+                }
+                if let Some(course) = local {
+                    match Session::starting("local", &course, Utc::now()) {
+                        Ok(started) => {
+                            session.set(Some(started.state(Utc::now())));
+                            *local_session.borrow_mut() = Some(started);
+                        }
+                        Err(e) => log::warn!("cook {}: {}", course.details().name(), e),
+                    }
+                } else {
+                    let id = id.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        let response = crate::api(&format!("{SERVER}/course/{id}/session"))
+                            .method(Method::POST)
+                            .send()
+                            .await
+                            .unwrap();
+                        if response.ok() {
+                            session.set(Some(response.json().await.unwrap()));
+                        }
+                    });
+                }
+                move || {
+                    if let Some(lock) = wake_lock.borrow_mut().take() {
+                        alarm::unlock_screen(&lock);
+                    }
+                }
+            },
+            course_details.id().to_owned(),
+        );
+    }
+    {
+        let now = now.clone();
+        let session = session.clone();
+        let local_session = local_session.clone();
+        let alarm_settings = alarm_settings.clone();
+        let audio = audio.clone();
+        use_effect_with_deps(
+            move |_| {
+                let mut last_tick = Utc::now();
+                // The state last ticked, so a skip or extension between ticks is measured
+                // from the schedule before it.
+                let mut seen = None::<SessionState>;
+                let interval = Interval::new(1000, move || {
+                    let tick = Utc::now();
+                    // A local session has no server to announce its stages, so do it here.
+                    if let Some(local) = local_session.borrow().as_ref() {
+                        let state = local.state(tick);
+                        let before = seen.take().unwrap_or_else(|| local.state(last_tick));
+                        for event in ServerEvent::stage_changes(&before, &state) {
+                            let audio = audio.borrow();
+                            follow(
+                                event,
+                                local.id(),
+                                &session,
+                                audio.as_ref(),
+                                *alarm_settings.borrow(),
+                            );
+                        }
+                        session.set(Some(state.clone()));
+                        seen = Some(state);
+                    }
+                    last_tick = tick;
+                    now.set(tick);
+                });
+                move || drop(interval)
+            },
+            (),
+        );
+    }
+    {
+        // A local session's changes are followed as it ticks, not through the server.
+        let session_id = session
+            .as_ref()
+            .filter(|_| local.is_none())
+            .map(|session| session.id().to_owned());
+        let session = session.clone();
+        let alarm_settings = alarm_settings.clone();
+        let audio = audio.clone();
+        use_effect_with_deps(
+            move |id: &Option<String>| {
+                let subscription = id.clone().map(|id| {
+                    crate::components::server_events::subscribe(Callback::from(
+                        move |event: ServerEvent| {
+                            let audio = audio.borrow();
+                            follow(
+                                event,
+                                &id,
+                                &session,
+                                audio.as_ref(),
+                                *alarm_settings.borrow(),
+                            )
+                        },
+                    ))
+                });
+                move || drop(subscription)
+            },
+            session_id,
+        );
+    }
+
+    let change_settings = {
+        let settings = settings.clone();
+        let alarm_settings = alarm_settings.clone();
+        move |changed: AlarmSettings| {
+            changed.save();
+            *alarm_settings.borrow_mut() = changed;
+            settings.set(changed);
+        }
+    };
+    let choose_tone = {
+        let change_settings = change_settings.clone();
+        let current = *settings;
+        move |e: web_sys::Event| {
+            let tone = Tone::from_name(&e.target_unchecked_into::<HtmlSelectElement>().value());
+            change_settings(AlarmSettings { tone, ..current });
+        }
+    };
+    let choose_volume = {
+        let current = *settings;
+        move |e: web_sys::Event| {
+            let volume = e
+                .target_unchecked_into::<HtmlInputElement>()
+                .value()
+                .parse()
+                .unwrap_or(current.volume);
+            change_settings(AlarmSettings { volume, ..current });
+        }
+    };
+    let test_alarm = {
+        let audio = audio.clone();
+        let current = *settings;
+        move |_| {
+            if let Some(audio) = audio.borrow().as_ref() {
+                let _ = alarm::play(audio, current);
+            }
+        }
+    };
+
+    let exit = {
+        let session = session.clone();
+        let on_close = on_close.clone();
+        let is_local = local.is_some();
+        move |_| {
+            if let Some(id) = session
+                .as_ref()
+                .filter(|_| !is_local)
+                .map(|session| session.id().to_owned())
+            {
+                wasm_bindgen_futures::spawn_local(async move {
+                    let _ = crate::api(&format!("{SERVER}/session/{id}"))
+                        .method(Method::DELETE)
+                        .send()
+                        .await;
+                });
+            }
+            on_close.emit(());
+        }
+    };
+
+    let body = match session.as_ref() {
+        None => html! { <p>{ "Starting…" }</p> },
+        Some(state) => {
+            let id = state.id().to_owned();
+            let action = |path: &'static str,
+                          apply: fn(&mut Session, DateTime<Utc>) -> Result<(), SessionError>| {
+                let session = session.clone();
+                let local_session = local_session.clone();
+                let id = id.clone();
+                move |_| match local_session.borrow_mut().as_mut() {
+                    Some(local) => {
+                        let now = Utc::now();
+                        if let Err(e) = apply(local, now) {
+                            log::warn!("session {path} failed: {e}");
+                        }
+                        session.set(Some(local.state(now)));
+                    }
+                    None => control(session.clone(), format!("{id}/{path}")),
+                }
+            };
+            let headline = match (state.status(), state.current()) {
+                (SessionStatus::Finished, _) => "Serve!".to_owned(),
+                (_, Some(stage)) => stage.name().to_owned(),
+                (_, None) => format!(
+                    "First stage at {}",
+                    state
+                        .stages()
+                        .first()
+                        .map(|stage| stage.start().format("%H:%M").to_string())
+                        .unwrap_or_default()
+                ),
+            };
+            let paused = state.status() == SessionStatus::Paused;
+            let finished = state.status() == SessionStatus::Finished;
+            let current = state.current_index();
+            let stages = state.stages().iter().enumerate().map(|(index, stage)| {
+                let done = finished || current.is_some_and(|current| index < current);
+                let style = if Some(index) == current {
+                    "font-weight: bold; font-size: 1.5rem;"
+                } else if done {
+                    "text-decoration: line-through; opacity: 0.6;"
+                } else {
+                    ""
+                };
+                html! {
+                    <li style={style}>
+                        if Some(index) == current {
+                            <button onclick={action("skip", Session::skip)} title="Done">{ "\u{2714}" }</button>{ " " }
+                        } else if done {
+                            { "\u{2714} " }
+                        }
+                        { stage.start().format("%H:%M").to_string() }{ " — " }{ stage.name() }
+                    </li>
+                }
+            });
+            html! {
+                <>
+                <p style="font-size: 2.5rem; margin: 0;">{ headline }</p>
+                <p style="font-size: 6rem; margin: 0; font-variant-numeric: tabular-nums;">
+                    { countdown(state.remaining_at(*now)) }
+                </p>
+                if paused {
+                    <p>{ "Paused" }</p>
+                }
+                <div style="display: flex; gap: 1rem; font-size: 1.5rem;">
+                    if !finished {
+                        <button onclick={action("skip", Session::skip)}>{ "Done" }</button>
+                        if paused {
+                            <button onclick={action("resume", Session::resume)}>{ "Resume" }</button>
+                        } else {
+                            <button onclick={action("pause", Session::pause)}>{ "Pause" }</button>
+                        }
+                        <button onclick={action("extend?by=1m", |session, now| session.extend(now, EXTEND_BY))}>{ "+1 min" }</button>
+                    }
+                </div>
+                <ol>{ for stages }</ol>
+                </>
+            }
+        }
+    };
+
+    html! {
+        <div class={"cook-mode"} style="position: fixed; inset: 0; z-index: 10; overflow: auto; padding: 2rem; background: #222; color: #eee;">
+            <div style="display: flex; justify-content: space-between; align-items: center;">
+                <h2>{ course_details.name() }</h2>
+                <button onclick={exit} style="font-size: 1.5rem;">{ "Exit" }</button>
+            </div>
+            { body }
+            <div class={"alarm-settings"} style="margin-top: 2rem;">
+                { "Alarm " }
+                <select onchange={choose_tone}>
+                    { for Tone::ALL.iter().map(|tone| html! {
+                        <option value={tone.name()} selected={*tone == settings.tone}>{ tone.name() }</option>
+                    }) }
+                </select>
+                <input type="range" min="0" max="1" step="0.1" value={settings.volume.to_string()} onchange={choose_volume} />
+                <button onclick={test_alarm}>{ "Test" }</button>
+            </div>
+        </div>
+    }
+}
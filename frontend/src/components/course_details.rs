@@ -1,8 +1,9 @@
 use crate::components::safe_html::SafeHtml;
-use common::{CourseDetails, Stage};
-use reqwasm::http::Method;
+use common::{ApiError, CourseDetails, Metadata, NewStage, Planned, Stage, StageUpdate};
+use reqwasm::http::{Method, Request};
 use yew::{
     classes, function_component, html, use_effect_with_deps, use_state, Callback, Properties,
+    UseStateHandle,
 };
 
 use crate::request;
@@ -11,16 +12,73 @@ use crate::SERVER;
 #[derive(Clone, Properties, PartialEq)]
 pub struct CourseDetailsProps {
     pub course_details: CourseDetails,
+    /// The course after it was renamed, or `None` once it is deleted.
+    pub on_change: Callback<Option<CourseDetails>>,
+    /// Bumped whenever the server reports a change to this course.
+    #[prop_or_default]
+    pub revision: u64,
+}
+
+/// Send a change to the course's stages, showing the stages as they now are.
+fn change_stages(
+    request: Request,
+    course: UseStateHandle<Vec<Stage>>,
+    stage_error: UseStateHandle<Option<String>>,
+    editing: UseStateHandle<Option<u32>>,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let response = request.send().await.unwrap();
+        if response.status() == 422 {
+            let error: ApiError = response.json().await.unwrap();
+            stage_error.set(Some(error.message().to_owned()));
+            return;
+        }
+        if !response.ok() {
+            log::warn!("stage change failed: {}", response.status());
+            return;
+        }
+        let fetched_stages: Vec<Stage> = response.json().await.unwrap();
+        log::debug!("changed stages: {fetched_stages:?}");
+        stage_error.set(None);
+        editing.set(None);
+        course.set(fetched_stages);
+    });
 }
 
 #[function_component(CourseDetailsDisplay)]
-pub fn course_details(CourseDetailsProps { course_details }: &CourseDetailsProps) -> Html {
+pub fn course_details(
+    CourseDetailsProps {
+        course_details,
+        on_change,
+        revision,
+    }: &CourseDetailsProps,
+) -> Html {
     let stage_classes = ["stage"];
     let new_stage_visible = use_state(|| false);
+    let stage_error = use_state(|| None);
+    let editing = use_state(|| None);
+    let cooking = use_state(|| false);
+    let serve_at = use_state(|| None);
 
     log::debug!("course_details {course_details:?}");
     let id = course_details.id().to_owned();
     let course = use_state(std::vec::Vec::new);
+    let metadata = use_state(Metadata::default);
+    {
+        let metadata = metadata.clone();
+        let id = id.clone();
+        use_effect_with_deps(
+            move |_| {
+                wasm_bindgen_futures::spawn_local(async move {
+                    let fetched: Metadata =
+                        request!(&format!("{SERVER}/course/{id}/metadata"), Method::GET);
+                    metadata.set(fetched);
+                });
+                || ()
+            },
+            (course_details.clone(), *revision),
+        );
+    }
     {
         let course = course.clone();
         let new_stage_visible = new_stage_visible.clone();
@@ -39,58 +97,204 @@ pub fn course_details(CourseDetailsProps { course_details }: &CourseDetailsProps
                 });
                 || ()
             },
-            course_details.clone(),
+            (course_details.clone(), *revision),
         );
     }
 
     let update_stages = {
         let course = course.clone();
         let new_stage_visible = new_stage_visible.clone();
+        let stage_error = stage_error.clone();
         let id = id.clone();
         Callback::from(move |stage| {
             let id = id.clone();
             let new_stage_visible = new_stage_visible.clone();
+            let stage_error = stage_error.clone();
             let course = course.clone();
-            log::debug!("New stage for {id}: {stage}");
+            log::debug!("New stage for {id}: {stage:?}");
             wasm_bindgen_futures::spawn_local(async move {
-                let fetched_stages: Vec<Stage> = request!(
-                    &format!("{SERVER}/course/{id}"),
-                    Method::POST,
-                    serde_json::to_string(&stage).unwrap()
-                );
+                let response = crate::api(&format!("{SERVER}/course/{id}"))
+                    .method(Method::POST)
+                    .body(serde_json::to_string(&stage).unwrap())
+                    .send()
+                    .await
+                    .unwrap();
+                if response.status() == 422 {
+                    let error: ApiError = response.json().await.unwrap();
+                    stage_error.set(Some(error.message().to_owned()));
+                    return;
+                }
+                let fetched_stages: Vec<Stage> = response.json().await.unwrap();
                 log::debug!("fetched stage: {fetched_stages:?}");
+                stage_error.set(None);
                 new_stage_visible.set(fetched_stages.is_empty());
                 course.set(fetched_stages);
             });
         })
     };
 
-    let stages = course.iter().enumerate().map(|(id, stage)| {
+    let stage_url = |stage_id: u32| format!("{SERVER}/course/{id}/stage/{stage_id}");
+    let patch = {
+        let course = course.clone();
+        let stage_error = stage_error.clone();
+        let editing = editing.clone();
+        move |url: String, update: StageUpdate| {
+            let request = crate::api(&url)
+                .method(Method::PATCH)
+                .body(serde_json::to_string(&update).unwrap());
+            change_stages(
+                request,
+                course.clone(),
+                stage_error.clone(),
+                editing.clone(),
+            );
+        }
+    };
+
+    let stages = course.iter().enumerate().map(|(position, stage)| {
+        let url = stage_url(stage.id());
+        let edit = {
+            let editing = editing.clone();
+            let stage_error = stage_error.clone();
+            let stage_id = stage.id();
+            move |_| {
+                stage_error.set(None);
+                editing.set(Some(stage_id));
+            }
+        };
+        let save = {
+            let patch = patch.clone();
+            let url = url.clone();
+            Callback::from(move |stage: NewStage| patch(url.clone(), stage.into()))
+        };
+        let move_to = |position: usize| {
+            let patch = patch.clone();
+            let url = url.clone();
+            move |_| patch(url.clone(), StageUpdate::default().with_position(position))
+        };
+        let delete = {
+            let course = course.clone();
+            let stage_error = stage_error.clone();
+            let editing = editing.clone();
+            let url = url.clone();
+            move |_| {
+                let request = crate::api(&url).method(Method::DELETE);
+                change_stages(request, course.clone(), stage_error.clone(), editing.clone());
+            }
+        };
         html! {
-            <div class={classes!(stage_classes.as_ref())} style={if id%2==0 {"background: #bbb;"}else{"background: #ccc;"}}>
-                <p class={"name"} style={"padding-left: 2.5rem;"}>{ stage.name() }</p>
-                <p class={"duration"}><crate::components::icon::Alarm width=32 height=32 />{ stage.duration() }</p>
+            <div class={classes!(stage_classes.as_ref())} style={if position%2==0 {"background: #bbb;"}else{"background: #ccc;"}}>
+                if *editing == Some(stage.id()) {
+                    <crate::components::stage_editor::StageEditor on_change={save} error={(*stage_error).clone()}
+                        name={stage.name().to_owned()} duration={stage.duration().to_string()} kind={stage.kind()} />
+                } else {
+                    <p class={"name"} style={"padding-left: 2.5rem;"}>{ stage.name() }</p>
+                    <p class={"duration"}><crate::components::icon::Alarm width=32 height=32 />{ stage.duration() }</p>
+                    <p class={"controls"} style={"cursor: pointer;"}>
+                        if position > 0 {
+                            <span title="Move earlier" onclick={move_to(position - 1)}>{ "\u{25B2} " }</span>
+                        }
+                        if position + 1 < course.len() {
+                            <span title="Move later" onclick={move_to(position + 1)}>{ "\u{25BC} " }</span>
+                        }
+                        <span title="Edit" onclick={edit}>{ "\u{270E} " }</span>
+                        <span title="Delete" onclick={delete}>{ "\u{2716}" }</span>
+                    </p>
+                }
             </div>
         }
     });
 
+    let rename = {
+        let course_details = course_details.clone();
+        let on_change = on_change.clone();
+        move |_| {
+            let name = gloo_utils::window()
+                .prompt_with_message_and_default("Rename course", course_details.name());
+            let name = match name {
+                Ok(Some(name)) if !name.trim().is_empty() => name,
+                _ => return,
+            };
+            let id = course_details.id().to_owned();
+            let on_change = on_change.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let renamed: CourseDetails = request!(
+                    &format!("{SERVER}/course/{id}"),
+                    Method::PATCH,
+                    serde_json::to_string(&CourseDetails::new(&id, name.trim())).unwrap()
+                );
+                on_change.emit(Some(renamed));
+            });
+        }
+    };
+    let delete = {
+        let course_details = course_details.clone();
+        let on_change = on_change.clone();
+        move |_| {
+            let confirmed = gloo_utils::window()
+                .confirm_with_message(&format!("Delete {}?", course_details.name()))
+                .unwrap_or(false);
+            if !confirmed {
+                return;
+            }
+            let id = course_details.id().to_owned();
+            let on_change = on_change.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let response = crate::api(&format!("{SERVER}/course/{id}"))
+                    .method(Method::DELETE)
+                    .send()
+                    .await
+                    .unwrap();
+                if response.ok() {
+                    on_change.emit(None);
+                }
+            });
+        }
+    };
+
     let onclick = {
         let new_stage_visible = new_stage_visible.clone();
         move |_| {
             new_stage_visible.set(!*new_stage_visible);
         }
     };
+    let cook = {
+        let cooking = cooking.clone();
+        move |_| cooking.set(true)
+    };
+    let stop_cooking = {
+        let cooking = cooking.clone();
+        Callback::from(move |_| cooking.set(false))
+    };
+
     html! {
         <div>
-            <h2>{ course_details.name() }</h2>
+            if *cooking {
+                <crate::components::cook_mode::CookMode course_details={course_details.clone()} on_close={stop_cooking} />
+            }
+            <h2>{ course_details.name() }
+                <span style="cursor: pointer; padding-left: 1em;" title="Cook" onclick={cook}>{ "\u{25B6}" }</span>
+                <span style="cursor: pointer; padding-left: 1em;" title="Rename" onclick={rename}>{ "\u{270E}" }</span>
+                <span style="cursor: pointer; padding-left: 0.5em;" title="Delete" onclick={delete}>{ "\u{2716}" }</span>
+            </h2>
             <h3><span style="cursor: pointer;" onclick={onclick}><crate::components::icon::Plus width=32 height=32 /></span><span style={"padding-left: 2.5rem; vertical-align: 8px;"}>{ "Stages" }</span></h3>
             if *(new_stage_visible.clone()) {
                 <div>{ "Add to course " }{ course_details.id() }</div>
-                <crate::components::stage_editor::StageEditor on_change={update_stages.clone()} />
+                <crate::components::stage_editor::StageEditor on_change={update_stages.clone()} error={(*stage_error).clone()} />
 
                 <SafeHtml style="font-size: 3em;" wrapper="div" html="&#x1F418; &#x1F427; &#x1F43C; &#x2665; &#x2605; &#x2139; &#x1F480; &#x1F44C; &#x1F37D; &#x1F384; &#x23F2;" />
             }
             { for stages }
+            if !metadata.ingredients().is_empty() {
+                <h3><span style={"padding-left: 2.5rem;"}>{ "Ingredients" }</span></h3>
+                <ul style={"padding-left: 4rem;"}>
+                    { for metadata.ingredients().iter().map(|ingredient| html! { <li>{ ingredient.to_string() }</li> }) }
+                </ul>
+            }
+            <crate::components::gantt::Gantt bars={crate::components::gantt::course_bars(&course)} serve_at={*serve_at} />
+            <crate::components::schedule::ScheduleDisplay course_details={course_details.clone()} stages={(*course).clone()}
+                on_serve_at={Callback::from(move |at| serve_at.set(at))} />
+            <crate::components::share_links::ShareLinks shared={Planned::Course(course_details.id().to_owned())} />
         </div>
     }
 }
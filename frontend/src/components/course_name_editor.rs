@@ -12,7 +12,7 @@ pub struct CourseNameEditorProps {
     pub on_select: Callback<CourseDetails>,
 }
 
-fn fetch_courses(on_change: Box<dyn FnOnce(Vec<CourseDetails>)>) {
+pub fn fetch_courses(on_change: Box<dyn FnOnce(Vec<CourseDetails>)>) {
     wasm_bindgen_futures::spawn_local(async move {
         let mut fetched_courses: Vec<CourseDetails> =
             request!(&format!("{SERVER}/courses/"), Method::GET);
@@ -61,7 +61,7 @@ pub fn course_name_editor(
         use_effect_with_deps(
             move |a| {
                 if let Some(input) = a.cast::<HtmlInputElement>() {
-                    input.focus();
+                    let _ = input.focus();
                 };
                 || ()
             },
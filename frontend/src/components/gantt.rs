@@ -0,0 +1,219 @@
+use chrono::{DateTime, Local, Utc};
+use common::{Stage, TimelineEntry};
+use gloo_timers::callback::Interval;
+use std::time::Duration;
+use yew::{function_component, html, use_effect_with_deps, use_state, Callback, Properties};
+
+/// One stage drawn on the chart, from `start` until `end` before serving.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bar {
+    /// Bars with the same row are drawn on the same line.
+    pub row: String,
+    pub name: String,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+/// A bar per stage, each on its own row, from `Course::stages()`, i.e. how long each lasts.
+pub fn course_bars(stages: &[Stage]) -> Vec<Bar> {
+    let mut end = Duration::ZERO;
+    let mut bars = stages
+        .iter()
+        .rev()
+        .map(|stage| {
+            let start = end + *stage.duration().get_ref();
+            let bar = Bar {
+                row: stage.name().to_owned(),
+                name: stage.name().to_owned(),
+                start,
+                end,
+            };
+            end = start;
+            bar
+        })
+        .collect::<Vec<_>>();
+    bars.reverse();
+    bars
+}
+
+/// A row per course from a meal timeline, each stage lasting until the course's next one.
+pub fn meal_bars(timeline: &[TimelineEntry]) -> Vec<Bar> {
+    let mut bars: Vec<Bar> = Vec::new();
+    for entry in timeline {
+        let start = *entry.before_serving().get_ref();
+        // The timeline is earliest first, so this entry ends the course's previous stage.
+        if let Some(previous) = bars.iter_mut().rev().find(|bar| bar.row == entry.course()) {
+            previous.end = start;
+        }
+        bars.push(Bar {
+            row: entry.course().to_owned(),
+            name: entry.stage().to_owned(),
+            start,
+            end: Duration::ZERO,
+        });
+    }
+    bars.retain(|bar| bar.start > bar.end);
+    bars
+}
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct GanttProps {
+    pub bars: Vec<Bar>,
+    /// When the food is served; labels the axis with clock times and adds a "now" marker.
+    #[prop_or_default]
+    pub serve_at: Option<DateTime<Utc>>,
+}
+
+const LABEL_WIDTH: f64 = 160.0;
+const ROW_HEIGHT: f64 = 28.0;
+const AXIS_HEIGHT: f64 = 24.0;
+/// Zoom levels, in pixels per minute.
+const SCALES: [f64; 7] = [0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0];
+/// Candidate gaps between axis ticks, in minutes.
+const TICKS: [u64; 10] = [1, 2, 5, 10, 15, 30, 60, 120, 240, 720];
+
+fn minutes(duration: Duration) -> f64 {
+    duration.as_secs_f64() / 60.0
+}
+
+/// A time before serving as a label: a clock time if serving is scheduled, else `T-…`.
+fn label(before_serving: Duration, serve_at: Option<DateTime<Utc>>) -> String {
+    match serve_at {
+        Some(serve_at) => (serve_at - chrono::Duration::from_std(before_serving).unwrap())
+            .with_timezone(&Local)
+            .format("%H:%M")
+            .to_string(),
+        None => format!("T-{}", humantime::format_duration(before_serving)),
+    }
+}
+
+/// Stages as bars on a shared time axis, earliest on the left and serving on the right.
+#[function_component(Gantt)]
+pub fn gantt(GanttProps { bars, serve_at }: &GanttProps) -> Html {
+    let zoom = use_state(|| 3usize);
+    let hovered = use_state(|| None::<usize>);
+    let now = use_state(Utc::now);
+    {
+        let now = now.clone();
+        use_effect_with_deps(
+            move |_| {
+                let interval = Interval::new(30_000, move || now.set(Utc::now()));
+                move || drop(interval)
+            },
+            (),
+        );
+    }
+
+    let scale = SCALES[*zoom];
+    let total = bars
+        .iter()
+        .map(|bar| bar.start)
+        .max()
+        .unwrap_or(Duration::ZERO);
+    let x = |before_serving: Duration| LABEL_WIDTH + minutes(total - before_serving) * scale;
+    let mut rows: Vec<&str> = Vec::new();
+    for bar in bars {
+        if !rows.contains(&bar.row.as_str()) {
+            rows.push(&bar.row);
+        }
+    }
+    let width = x(Duration::ZERO) + 40.0;
+    let height = AXIS_HEIGHT + rows.len() as f64 * ROW_HEIGHT;
+
+    let tick = TICKS
+        .into_iter()
+        .find(|tick| *tick as f64 * scale >= 60.0)
+        .unwrap_or(720);
+    let ticks = (0..=total.as_secs() / 60 / tick).map(|i| {
+        let before_serving = Duration::from_secs(i * tick * 60);
+        let at = x(before_serving);
+        html! {
+            <g>
+                <line x1={at.to_string()} x2={at.to_string()} y1={AXIS_HEIGHT.to_string()} y2={height.to_string()} stroke="#ccc" />
+                <text x={at.to_string()} y="16" font-size="11" text-anchor="middle">{ label(before_serving, *serve_at) }</text>
+            </g>
+        }
+    });
+
+    let labels = rows.iter().enumerate().map(|(row, name)| {
+        let y = AXIS_HEIGHT + row as f64 * ROW_HEIGHT;
+        html! {
+            <text x="4" y={(y + ROW_HEIGHT * 0.65).to_string()} font-size="13">{ *name }</text>
+        }
+    });
+
+    let rects = bars.iter().enumerate().map(|(index, bar)| {
+        let row = rows.iter().position(|row| *row == bar.row).unwrap_or(0);
+        let y = AXIS_HEIGHT + row as f64 * ROW_HEIGHT + 4.0;
+        let (left, right) = (x(bar.start), x(bar.end));
+        let fill = if *hovered == Some(index) { "#c84" } else { "#e96" };
+        let enter = {
+            let hovered = hovered.clone();
+            move |_| hovered.set(Some(index))
+        };
+        let leave = {
+            let hovered = hovered.clone();
+            move |_| hovered.set(None)
+        };
+        html! {
+            <g onmouseenter={enter} onmouseleave={leave}>
+                <rect x={left.to_string()} y={y.to_string()} width={(right - left).max(1.0).to_string()}
+                    height={(ROW_HEIGHT - 8.0).to_string()} rx="3" fill={fill}>
+                    <title>{ &bar.name }</title>
+                </rect>
+                if right - left > 7.0 * bar.name.len() as f64 {
+                    <text x={(left + 4.0).to_string()} y={(y + ROW_HEIGHT * 0.5).to_string()} font-size="11" pointer-events="none">{ &bar.name }</text>
+                }
+            </g>
+        }
+    });
+
+    // Only drawn while now falls within the plan.
+    let now = serve_at
+        .and_then(|serve_at| (serve_at - *now).to_std().ok())
+        .filter(|before_serving| *before_serving <= total)
+        .map(|before_serving| {
+            let at = x(before_serving);
+            html! {
+                <line x1={at.to_string()} x2={at.to_string()} y1="0" y2={height.to_string()} stroke="#d00" stroke-width="2">
+                    <title>{ "Now" }</title>
+                </line>
+            }
+        });
+
+    let details = (*hovered).and_then(|index| bars.get(index)).map(|bar| {
+        format!(
+            "{}: {} to {} ({})",
+            bar.name,
+            label(bar.start, *serve_at),
+            label(bar.end, *serve_at),
+            humantime::format_duration(bar.start - bar.end)
+        )
+    });
+
+    let zoom_by = |step: isize| {
+        let zoom = zoom.clone();
+        Callback::from(move |_| {
+            let level = (*zoom as isize + step).clamp(0, SCALES.len() as isize - 1);
+            zoom.set(level as usize);
+        })
+    };
+
+    html! {
+        <div class={"gantt"}>
+            <div>
+                <button onclick={zoom_by(-1)} disabled={*zoom == 0} title="Zoom out">{ "\u{2212}" }</button>
+                <button onclick={zoom_by(1)} disabled={*zoom == SCALES.len() - 1} title="Zoom in">{ "+" }</button>
+                <span style="padding-left: 1em;">{ details.unwrap_or_default() }</span>
+            </div>
+            <div style="overflow-x: auto;">
+                <svg width={width.to_string()} height={height.to_string()} xmlns="http://www.w3.org/2000/svg">
+                    { for ticks }
+                    { for labels }
+                    { for rects }
+                    { for now }
+                </svg>
+            </div>
+        </div>
+    }
+}
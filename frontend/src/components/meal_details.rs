@@ -0,0 +1,185 @@
+use chrono::Local;
+use common::{AttentionPlan, CourseDetails, MealDetails, Planned, TimelineEntry};
+use reqwasm::http::Method;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::{
+    classes, function_component, html, use_effect_with_deps, use_node_ref, use_state, Callback,
+    Properties,
+};
+
+use crate::request;
+use crate::SERVER;
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct MealDetailsProps {
+    pub meal_details: MealDetails,
+    pub courses: Vec<CourseDetails>,
+}
+
+/// What an entry says to do: `course: stage`, or just the stage for serving the meal.
+pub fn entry_name(entry: &TimelineEntry) -> String {
+    match entry.course() {
+        "" => entry.stage().to_owned(),
+        course => format!("{}: {}", course, entry.stage()),
+    }
+}
+
+#[function_component(MealDetailsDisplay)]
+pub fn meal_details(
+    MealDetailsProps {
+        meal_details,
+        courses,
+    }: &MealDetailsProps,
+) -> Html {
+    let stage_classes = ["stage"];
+    let course_select_ref = use_node_ref();
+    let serve_at_ref = use_node_ref();
+
+    log::debug!("meal_details {meal_details:?}");
+    let id = meal_details.id().to_owned();
+    let timeline = use_state(std::vec::Vec::new);
+    let attention = use_state(|| None);
+    {
+        let timeline = timeline.clone();
+        let id = id.clone();
+        use_effect_with_deps(
+            move |_| {
+                let timeline = timeline.clone();
+                let id = id.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let fetched: Vec<TimelineEntry> =
+                        request!(&format!("{SERVER}/meal/{id}"), Method::GET);
+                    log::debug!("fetched meal: {fetched:?}");
+                    timeline.set(fetched);
+                });
+                || ()
+            },
+            meal_details.clone(),
+        );
+    }
+    {
+        let attention = attention.clone();
+        let id = id.clone();
+        use_effect_with_deps(
+            move |_| {
+                let attention = attention.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let fetched: AttentionPlan =
+                        request!(&format!("{SERVER}/meal/{id}/attention"), Method::GET);
+                    log::debug!("fetched attention: {fetched:?}");
+                    attention.set(Some(fetched));
+                });
+                || ()
+            },
+            (*timeline).clone(),
+        );
+    }
+
+    let add_course = {
+        let timeline = timeline.clone();
+        let course_select_ref = course_select_ref.clone();
+        let id = id.clone();
+        Callback::from(move |_| {
+            let course_id = match course_select_ref.cast::<HtmlSelectElement>() {
+                Some(select) => select.value(),
+                None => return,
+            };
+            let id = id.clone();
+            let timeline = timeline.clone();
+            log::debug!("Add course {course_id} to meal {id}");
+            wasm_bindgen_futures::spawn_local(async move {
+                let fetched: Vec<TimelineEntry> = request!(
+                    &format!("{SERVER}/meal/{id}"),
+                    Method::POST,
+                    serde_json::to_string(&course_id).unwrap()
+                );
+                timeline.set(fetched);
+            });
+        })
+    };
+
+    let set_serve_at = {
+        let timeline = timeline.clone();
+        let serve_at_ref = serve_at_ref.clone();
+        let id = id.clone();
+        Callback::from(move |_| {
+            let serve_at = match serve_at_ref.cast::<HtmlInputElement>() {
+                Some(input) => input.value(),
+                None => return,
+            };
+            // Clearing the input clears the serving time.
+            let query = match serve_at.is_empty() {
+                true => String::new(),
+                false => format!(
+                    "?{}",
+                    crate::components::schedule::schedule_query(&serve_at)
+                ),
+            };
+            let id = id.clone();
+            let timeline = timeline.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let fetched: Vec<TimelineEntry> =
+                    request!(&format!("{SERVER}/meal/{id}/serve_at{query}"), Method::PUT);
+                timeline.set(fetched);
+            });
+        })
+    };
+    // The last entry is serving, which is timed once the meal has a serving time.
+    let serve_at = timeline.last().and_then(|entry| entry.at()).copied();
+
+    let entries = timeline.iter().enumerate().map(|(id, entry)| {
+        let when = match entry.at() {
+            Some(at) => at.with_timezone(&Local).format("%H:%M").to_string(),
+            None => format!("T-{}", entry.before_serving()),
+        };
+        html! {
+            <div class={classes!(stage_classes.as_ref())} style={if id%2==0 {"background: #bbb;"}else{"background: #ccc;"}}>
+                <p class={"duration"}><crate::components::icon::Alarm width=32 height=32 />{ when }</p>
+                <p class={"name"} style={"padding-left: 2.5rem;"}>{ entry_name(entry) }</p>
+            </div>
+        }
+    });
+
+    let cook = (*attention).as_ref().map(|attention| {
+        let clashes = attention.clashes().iter().map(|window| {
+            html! { <p class={"stage"} style={"background: #e99;"}>{ "Clash " }{ window.to_string() }</p> }
+        });
+        let idle = attention.idle().iter().map(|window| {
+            html! { <p class={"stage"}>{ "Free " }{ window.to_string() }</p> }
+        });
+        html! {
+            <>
+                <h3>{ "Cook" }</h3>
+                { for clashes }
+                { for idle }
+            </>
+        }
+    });
+
+    let options = courses.iter().map(|course| {
+        html! {
+            <option value={course.id().to_owned()}>{ course.name() }</option>
+        }
+    });
+
+    html! {
+        <div>
+            <h2>{ meal_details.name() }</h2>
+            <div style="display: flex; flex-flow: row nowrap;">
+                <select ref={course_select_ref} style="flex: 4 0px;">
+                    { for options }
+                </select>
+                <button onclick={add_course} style="flex: 0">{ "Add course" }</button>
+            </div>
+            <div style="display: flex; flex-flow: row nowrap;">
+                <input type="datetime-local" ref={serve_at_ref} style="flex: 4 0px;" />
+                <button onclick={set_serve_at} style="flex: 0">{ "Serve at" }</button>
+            </div>
+            <h3>{ "Timeline" }</h3>
+            <crate::components::gantt::Gantt bars={crate::components::gantt::meal_bars(&timeline)} serve_at={serve_at} />
+            { for entries }
+            { for cook }
+            <crate::components::share_links::ShareLinks shared={Planned::Meal(id)} />
+        </div>
+    }
+}
@@ -0,0 +1,33 @@
+use common::MealDetails;
+use yew::{classes, function_component, html, Callback, Properties};
+
+#[derive(Properties, PartialEq)]
+pub struct MealListProps {
+    pub meal_details: Vec<MealDetails>,
+    pub on_click: Callback<MealDetails>,
+}
+
+#[function_component(MealsList)]
+pub fn meals_list(
+    MealListProps {
+        meal_details,
+        on_click,
+    }: &MealListProps,
+) -> Html {
+    let meal_detail_classes = ["course"];
+    let on_click = on_click.clone();
+    meal_details
+        .iter()
+        .enumerate()
+        .map(|(id, meal_detail)| {
+            let on_meal_select = {
+                let on_click = on_click.clone();
+                let meal_detail = meal_detail.clone();
+                Callback::from(move |_| on_click.emit(meal_detail.clone()))
+            };
+            html! {
+                <p class={classes!(meal_detail_classes.as_ref())} style={if id%2==0 {"background: #bbb;"}else{"background: #ccc;"}} onclick={on_meal_select}>{meal_detail.name().to_string()}</p>
+            }
+        })
+        .collect()
+}
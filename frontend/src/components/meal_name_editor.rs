@@ -0,0 +1,100 @@
+use common::MealDetails;
+use reqwasm::http::Method;
+use web_sys::{HtmlInputElement, KeyboardEvent};
+use yew::use_effect_with_deps;
+use yew::{function_component, html, use_node_ref, Callback, Properties};
+
+use crate::{request, SERVER};
+
+#[derive(Properties, PartialEq)]
+pub struct MealNameEditorProps {
+    pub on_change: Callback<Vec<MealDetails>>,
+    pub on_select: Callback<MealDetails>,
+}
+
+pub fn fetch_meals(on_change: Box<dyn FnOnce(Vec<MealDetails>)>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let mut fetched_meals: Vec<MealDetails> = request!(&format!("{SERVER}/meals"), Method::GET);
+        log::debug!("fetched: {fetched_meals:?}");
+        fetched_meals.sort_by(|a, b| a.name().cmp(b.name()));
+        on_change(fetched_meals);
+    });
+}
+
+fn add_new_meal(
+    input: HtmlInputElement,
+    on_change: &Callback<Vec<MealDetails>>,
+    on_select: &Callback<MealDetails>,
+) {
+    let on_change = on_change.clone();
+    let on_select = on_select.clone();
+    let name = input.value();
+    let meal_details = MealDetails::new("", &name);
+    log::info!("Update: {:?}", meal_details);
+
+    wasm_bindgen_futures::spawn_local(async move {
+        let meal_details: MealDetails = request!(
+            &format!("{SERVER}/meal"),
+            Method::PUT,
+            serde_json::to_string(&meal_details).unwrap()
+        );
+        let on_fetch = move |meals| {
+            on_change.emit(meals);
+            on_select.emit(meal_details);
+        };
+        fetch_meals(Box::new(on_fetch));
+    });
+}
+
+#[function_component(MealNameEditor)]
+pub fn meal_name_editor(
+    MealNameEditorProps {
+        on_change,
+        on_select,
+    }: &MealNameEditorProps,
+) -> Html {
+    let meal_name_ref = use_node_ref();
+
+    {
+        let meal_name_ref = meal_name_ref.clone();
+        use_effect_with_deps(
+            move |a| {
+                if let Some(input) = a.cast::<HtmlInputElement>() {
+                    let _ = input.focus();
+                };
+                || ()
+            },
+            meal_name_ref,
+        );
+    }
+    let onclick = {
+        let meal_name_ref = meal_name_ref.clone();
+        let on_change = on_change.clone();
+        let on_select = on_select.clone();
+        move |_| {
+            if let Some(input) = meal_name_ref.cast::<HtmlInputElement>() {
+                add_new_meal(input, &on_change, &on_select)
+            }
+        }
+    };
+    let onkeyup = {
+        let meal_name_ref = meal_name_ref.clone();
+        let on_change = on_change.clone();
+        let on_select = on_select.clone();
+        move |e: KeyboardEvent| {
+            if e.key_code() == 13 {
+                if let Some(input) = meal_name_ref.cast::<HtmlInputElement>() {
+                    add_new_meal(input, &on_change, &on_select)
+                }
+            }
+        }
+    };
+    html! {
+        <div style="display: flex; flex-flow: row nowrap;">
+            <input type="text" ref={meal_name_ref} onkeyup={onkeyup}
+                name="meal_name_editor" placeholder="Meal name …"
+                autocapitalize="none" spellcheck="false" autocomplete="off"  style="flex: 4 0px; padding-right: 1em"/>
+            <button onclick={onclick} style="flex: 0">{ "Ok" }</button>
+        </div>
+    }
+}
@@ -1,6 +1,20 @@
+pub mod alarm;
+pub mod api_keys;
+pub mod calendar_feed;
+pub mod cook_mode;
 pub mod course_details;
 pub mod course_list;
 pub mod course_name_editor;
+pub mod gantt;
 pub mod icon;
+pub mod meal_details;
+pub mod meal_list;
+pub mod meal_name_editor;
+pub mod recipe_import;
 pub mod safe_html;
+pub mod schedule;
+pub mod server_events;
+pub mod share_links;
+pub mod shared_plan;
+pub mod sign_in;
 pub mod stage_editor;
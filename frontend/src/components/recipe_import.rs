@@ -0,0 +1,217 @@
+use common::{ApiError, Course, CourseDetails, StageUpdate};
+use reqwasm::http::Method;
+use web_sys::{HtmlInputElement, HtmlTextAreaElement};
+use yew::{
+    function_component, html, use_node_ref, use_state, Callback, Properties, TargetCast,
+    UseStateHandle,
+};
+
+use crate::components::course_name_editor::fetch_courses;
+use crate::SERVER;
+
+/// A change to the proposed course, or why it cannot be made.
+type Change = Box<dyn Fn(&mut Course) -> Result<(), String>>;
+
+#[derive(Properties, PartialEq)]
+pub struct RecipeImportProps {
+    pub on_change: Callback<Vec<CourseDetails>>,
+    pub on_select: Callback<CourseDetails>,
+}
+
+/// Ask the backend to propose a course from a recipe page or its JSON-LD.
+fn propose(
+    page: String,
+    proposal: UseStateHandle<Option<Course>>,
+    error: UseStateHandle<Option<String>>,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let response = crate::api(&format!("{SERVER}/course/schema_org"))
+            .method(Method::POST)
+            .body(page)
+            .send()
+            .await
+            .unwrap();
+        if response.ok() {
+            error.set(None);
+            proposal.set(Some(response.json().await.unwrap()));
+        } else {
+            let e: ApiError = response.json().await.unwrap();
+            error.set(Some(e.message().to_owned()));
+        }
+    });
+}
+
+/// Import a recipe from a saved web page, reviewing the proposed stages before saving.
+#[function_component(RecipeImport)]
+pub fn recipe_import(
+    RecipeImportProps {
+        on_change,
+        on_select,
+    }: &RecipeImportProps,
+) -> Html {
+    let page_ref = use_node_ref();
+    let proposal = use_state(|| None::<Course>);
+    let error = use_state(|| None::<String>);
+
+    let propose_pasted = {
+        let page_ref = page_ref.clone();
+        let proposal = proposal.clone();
+        let error = error.clone();
+        Callback::from(move |_| {
+            if let Some(page) = page_ref.cast::<HtmlTextAreaElement>() {
+                propose(page.value(), proposal.clone(), error.clone());
+            }
+        })
+    };
+    let propose_file = {
+        let proposal = proposal.clone();
+        let error = error.clone();
+        Callback::from(move |e: web_sys::Event| {
+            let file = e
+                .target_unchecked_into::<HtmlInputElement>()
+                .files()
+                .and_then(|files| files.get(0));
+            let file = match file {
+                Some(file) => file,
+                None => return,
+            };
+            let proposal = proposal.clone();
+            let error = error.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let text = wasm_bindgen_futures::JsFuture::from(file.text()).await;
+                match text.ok().and_then(|text| text.as_string()) {
+                    Some(page) => propose(page, proposal, error),
+                    None => error.set(Some("cannot read file".to_owned())),
+                }
+            });
+        })
+    };
+
+    // Apply a change to the proposal, showing why if it was refused.
+    let change = {
+        let proposal = proposal.clone();
+        let error = error.clone();
+        move |change: Change| {
+            let mut changed = match proposal.as_ref() {
+                Some(course) => course.clone(),
+                None => return,
+            };
+            match change(&mut changed) {
+                Ok(()) => {
+                    error.set(None);
+                    proposal.set(Some(changed));
+                }
+                Err(e) => error.set(Some(e)),
+            }
+        }
+    };
+
+    let save = {
+        let proposal = proposal.clone();
+        let on_change = on_change.clone();
+        let on_select = on_select.clone();
+        Callback::from(move |_| {
+            let course = match proposal.as_ref() {
+                Some(course) => course.clone(),
+                None => return,
+            };
+            let proposal = proposal.clone();
+            let on_change = on_change.clone();
+            let on_select = on_select.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let details: CourseDetails = crate::request!(
+                    &format!("{SERVER}/course/import"),
+                    Method::PUT,
+                    serde_json::to_string(&course).unwrap()
+                );
+                proposal.set(None);
+                fetch_courses(Box::new(move |courses| {
+                    on_change.emit(courses);
+                    on_select.emit(details);
+                }));
+            });
+        })
+    };
+    let discard = {
+        let proposal = proposal.clone();
+        Callback::from(move |_| proposal.set(None))
+    };
+
+    let review = proposal.as_ref().map(|course| {
+        let rename = {
+            let change = change.clone();
+            move |e: web_sys::Event| {
+                let name = e.target_unchecked_into::<HtmlInputElement>().value();
+                change(Box::new(move |course| {
+                    course.rename(&name);
+                    Ok(())
+                }))
+            }
+        };
+        let stages = course.stages().map(|stage| {
+            let id = stage.id();
+            let update = |field: fn(StageUpdate, &str) -> StageUpdate| {
+                let change = change.clone();
+                move |e: web_sys::Event| {
+                    let update = field(
+                        StageUpdate::default(),
+                        &e.target_unchecked_into::<HtmlInputElement>().value(),
+                    );
+                    change(Box::new(move |course| {
+                        course
+                            .update_stage(id, &update)
+                            .map(|_| ())
+                            .map_err(|e| e.to_string())
+                    }))
+                }
+            };
+            let remove = {
+                let change = change.clone();
+                move |_| {
+                    change(Box::new(move |course| {
+                        course.remove_stage(id);
+                        Ok(())
+                    }))
+                }
+            };
+            html! {
+                <li>
+                    <input type="text" value={stage.name().to_owned()} onchange={update(StageUpdate::with_name)} style="width: 60%;" />
+                    <input type="text" value={stage.duration().to_string()} onchange={update(StageUpdate::with_duration)} size="8" title="How long it takes" />
+                    <span style="cursor: pointer; padding-left: 0.5em;" onclick={remove} title="Remove">{ "\u{2716}" }</span>
+                </li>
+            }
+        });
+        html! {
+            <div>
+                <input type="text" value={course.details().name().to_owned()} onchange={rename} />
+                <ol>{ for stages }</ol>
+                if !course.metadata().ingredients().is_empty() {
+                    <ul>
+                        { for course.metadata().ingredients().iter().map(|ingredient| html! { <li>{ ingredient.to_string() }</li> }) }
+                    </ul>
+                }
+                if let Some(instructions) = course.metadata().field("instructions") {
+                    <pre style="white-space: pre-wrap;">{ instructions }</pre>
+                }
+                <button onclick={save}>{ "Save" }</button>
+                <button onclick={discard}>{ "Discard" }</button>
+            </div>
+        }
+    });
+
+    html! {
+        <div class={"recipe-import"}>
+            if let Some(review) = review {
+                { review }
+            } else {
+                <textarea ref={page_ref} rows="4" style="width: 100%;" placeholder="Paste a recipe page's HTML or JSON-LD" />
+                <button onclick={propose_pasted}>{ "Propose" }</button>
+                <input type="file" accept=".html,.htm,.json,.jsonld" onchange={propose_file} />
+            }
+            if let Some(error) = error.as_ref() {
+                <p style="color: #c00;">{ error }</p>
+            }
+        </div>
+    }
+}
@@ -18,7 +18,7 @@ pub fn safe_html(
     }: &Props,
 ) -> Html {
     let element = gloo_utils::document().create_element(wrapper).unwrap();
-    element.set_attribute("style", style);
+    let _ = element.set_attribute("style", style);
     element.set_inner_html(&html.clone());
 
     Html::VRef(element.into())
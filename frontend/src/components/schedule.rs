@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use common::{CourseDetails, ScheduledStage, Stage};
+use reqwasm::http::Method;
+use web_sys::HtmlInputElement;
+use yew::{
+    classes, function_component, html, use_effect_with_deps, use_node_ref, use_state, Callback,
+    Properties,
+};
+
+use crate::request;
+use crate::SERVER;
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct ScheduleProps {
+    pub course_details: CourseDetails,
+    /// Current stages of the course; the schedule is refetched whenever they change.
+    pub stages: Vec<Stage>,
+    /// Told when the course is served, once a serve time has been chosen.
+    #[prop_or_default]
+    pub on_serve_at: Callback<Option<DateTime<Utc>>>,
+}
+
+/// IANA name of the browser's time zone, e.g. `Europe/London`.
+pub fn browser_time_zone() -> Option<String> {
+    let options = js_sys::Intl::DateTimeFormat::new(&js_sys::Array::new(), &js_sys::Object::new())
+        .resolved_options();
+    js_sys::Reflect::get(&options, &"timeZone".into())
+        .ok()
+        .and_then(|tz| tz.as_string())
+}
+
+pub fn schedule_query(serve_at: &str) -> String {
+    let mut query = format!(
+        "serve_at={}",
+        js_sys::encode_uri_component(serve_at).as_string().unwrap()
+    );
+    if let Some(tz) = browser_time_zone() {
+        query.push_str(&format!(
+            "&tz={}",
+            js_sys::encode_uri_component(&tz).as_string().unwrap()
+        ));
+    }
+    query
+}
+
+#[function_component(ScheduleDisplay)]
+pub fn schedule(
+    ScheduleProps {
+        course_details,
+        stages,
+        on_serve_at,
+    }: &ScheduleProps,
+) -> Html {
+    let stage_classes = ["stage"];
+    let serve_at_ref = use_node_ref();
+    let serve_at = use_state(String::new);
+    let schedule = use_state(std::vec::Vec::new);
+
+    let id = course_details.id().to_owned();
+    {
+        let schedule = schedule.clone();
+        let on_serve_at = on_serve_at.clone();
+        use_effect_with_deps(
+            move |(serve_at, _, _)| {
+                let schedule = schedule.clone();
+                let serve_at = serve_at.clone();
+                let on_serve_at = on_serve_at.clone();
+                if !serve_at.is_empty() {
+                    wasm_bindgen_futures::spawn_local(async move {
+                        let query = schedule_query(&serve_at);
+                        let fetched: Vec<ScheduledStage> = request!(
+                            &format!("{SERVER}/course/{id}/schedule?{query}"),
+                            Method::GET
+                        );
+                        log::debug!("fetched schedule: {fetched:?}");
+                        on_serve_at
+                            .emit(fetched.last().map(|stage| stage.end().with_timezone(&Utc)));
+                        schedule.set(fetched);
+                    });
+                }
+                || ()
+            },
+            ((*serve_at).clone(), course_details.clone(), stages.clone()),
+        );
+    }
+
+    let onchange = {
+        let serve_at = serve_at.clone();
+        let serve_at_ref = serve_at_ref.clone();
+        Callback::from(move |_| {
+            if let Some(input) = serve_at_ref.cast::<HtmlInputElement>() {
+                serve_at.set(input.value());
+            }
+        })
+    };
+
+    let entries = schedule.iter().enumerate().map(|(id, stage)| {
+        html! {
+            <div class={classes!(stage_classes.as_ref())} style={if id%2==0 {"background: #bbb;"}else{"background: #ccc;"}}>
+                <p class={"name"} style={"padding-left: 2.5rem;"}>{ stage.to_string() }</p>
+            </div>
+        }
+    });
+
+    html! {
+        <div>
+            <h3><span style={"padding-left: 2.5rem;"}>{ "Serve at " }</span>
+                <input type="datetime-local" ref={serve_at_ref} onchange={onchange} name="serve_at" />
+            </h3>
+            if !serve_at.is_empty() {
+                { for entries }
+                <p style={"padding-left: 2.5rem;"}>
+                    <a href={format!("{SERVER}/course/{}/schedule.ics?{}", course_details.id(), schedule_query(&serve_at))}
+                        download={format!("{}.ics", course_details.name())}>{ "Add to calendar" }</a>
+                </p>
+            }
+        </div>
+    }
+}
@@ -0,0 +1,78 @@
+use common::ServerEvent;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{EventSource, EventSourceInit, MessageEvent};
+use yew::{Callback, Reducible};
+
+use crate::SERVER;
+
+/// An open connection to the server's event stream, closed when dropped.
+pub struct Subscription {
+    source: EventSource,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
+
+/// Call `on_event` with every change the server publishes.
+///
+/// The browser reconnects by itself if the stream drops.
+pub fn subscribe(on_event: Callback<ServerEvent>) -> Subscription {
+    // The stream only carries the signed in user's changes, so it needs their cookie.
+    let init = EventSourceInit::new();
+    init.set_with_credentials(true);
+    let source =
+        EventSource::new_with_event_source_init_dict(&format!("{SERVER}/events"), &init).unwrap();
+    let on_message = Closure::wrap(Box::new(move |message: MessageEvent| {
+        let data = message.data().as_string().unwrap_or_default();
+        match serde_json::from_str(&data) {
+            Ok(event) => on_event.emit(event),
+            Err(e) => log::warn!("unreadable server event {data}: {e}"),
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    Subscription {
+        source,
+        _on_message: on_message,
+    }
+}
+
+/// Counts of server changes, so components can refetch when what they show has changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    courses: u64,
+    course: HashMap<String, u64>,
+}
+
+impl Changes {
+    /// Changes to the list of courses, including renames.
+    pub fn courses(&self) -> u64 {
+        self.courses
+    }
+
+    /// Changes to one course.
+    pub fn course(&self, id: &str) -> u64 {
+        self.course.get(id).copied().unwrap_or(0)
+    }
+}
+
+impl Reducible for Changes {
+    type Action = ServerEvent;
+
+    fn reduce(self: Rc<Self>, event: ServerEvent) -> Rc<Self> {
+        let id = match event.course_id() {
+            Some(id) => id,
+            None => return self,
+        };
+        let mut changes = (*self).clone();
+        changes.courses += 1;
+        *changes.course.entry(id.to_owned()).or_default() += 1;
+        Rc::new(changes)
+    }
+}
@@ -0,0 +1,133 @@
+use chrono::Local;
+use common::{Planned, Share};
+use reqwasm::http::Method;
+use web_sys::HtmlSelectElement;
+use yew::{
+    function_component, html, use_effect_with_deps, use_node_ref, use_state, Callback, Properties,
+    UseStateHandle,
+};
+
+use crate::{request, SERVER};
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct ShareLinksProps {
+    pub shared: Planned,
+}
+
+/// How long a new link may last, as offered, with the `expires_in` each sends.
+const EXPIRY: [(&str, Option<&str>); 4] = [
+    ("Never expires", None),
+    ("For a day", Some("1d")),
+    ("For a week", Some("7d")),
+    ("For a month", Some("30d")),
+];
+
+/// The page a guest opens for a share, i.e. this app with the token in its query.
+pub fn share_url(token: &str) -> String {
+    let location = gloo_utils::window().location();
+    let origin = location.origin().unwrap_or_default();
+    let path = location.pathname().unwrap_or_default();
+    format!("{origin}{path}?share={token}")
+}
+
+fn fetch_shares(shared: Planned, shares: UseStateHandle<Vec<Share>>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let fetched: Vec<Share> = request!(&format!("{SERVER}/shares"), Method::GET);
+        shares.set(
+            fetched
+                .into_iter()
+                .filter(|share| *share.shared() == shared)
+                .collect(),
+        );
+    });
+}
+
+/// Read-only links to a course or meal for guests, which can be revoked at any time.
+#[function_component(ShareLinks)]
+pub fn share_links(ShareLinksProps { shared }: &ShareLinksProps) -> Html {
+    let shares = use_state(Vec::<Share>::new);
+    let expiry_ref = use_node_ref();
+    {
+        let shares = shares.clone();
+        use_effect_with_deps(
+            move |shared| {
+                fetch_shares(shared.clone(), shares);
+                || ()
+            },
+            shared.clone(),
+        );
+    }
+
+    let add_share = {
+        let shared = shared.clone();
+        let shares = shares.clone();
+        let expiry_ref = expiry_ref.clone();
+        Callback::from(move |_| {
+            let expires_in = expiry_ref
+                .cast::<HtmlSelectElement>()
+                .and_then(|select| EXPIRY.get(select.selected_index() as usize))
+                .and_then(|(_, expires_in)| *expires_in);
+            let query = expires_in
+                .map(|expires_in| format!("?expires_in={expires_in}"))
+                .unwrap_or_default();
+            let path = match &shared {
+                Planned::Course(id) => format!("course/{id}"),
+                Planned::Meal(id) => format!("meal/{id}"),
+            };
+            let shared = shared.clone();
+            let shares = shares.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let _: Share = request!(&format!("{SERVER}/{path}/share{query}"), Method::POST);
+                fetch_shares(shared, shares);
+            });
+        })
+    };
+
+    let links = shares.iter().map(|share| {
+        let revoke = {
+            let shared = shared.clone();
+            let shares = shares.clone();
+            let token = share.token().to_owned();
+            move |_| {
+                let shared = shared.clone();
+                let shares = shares.clone();
+                let token = token.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let response = crate::api(&format!("{SERVER}/share/{token}"))
+                        .method(Method::DELETE)
+                        .send()
+                        .await
+                        .unwrap();
+                    if response.ok() {
+                        fetch_shares(shared, shares);
+                    }
+                });
+            }
+        };
+        let expiry = match share.expires_at() {
+            Some(expires_at) => format!(
+                "until {}",
+                expires_at.with_timezone(&Local).format("%a %e %b %H:%M")
+            ),
+            None => "no expiry".to_owned(),
+        };
+        html! {
+            <li>
+                <input type="text" readonly=true value={share_url(share.token())} style="width: 60%;" />
+                { " " }{ expiry }
+                <span style="cursor: pointer; padding-left: 0.5em;" onclick={revoke} title="Revoke">{ "\u{2716}" }</span>
+            </li>
+        }
+    });
+
+    html! {
+        <div class={"share-links"}>
+            <h3>{ "Share" }</h3>
+            <select ref={expiry_ref}>
+                { for EXPIRY.iter().map(|(label, _)| html! { <option>{ *label }</option> }) }
+            </select>
+            <button onclick={add_share}>{ "Make a link" }</button>
+            <ul>{ for links }</ul>
+        </div>
+    }
+}
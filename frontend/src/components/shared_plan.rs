@@ -0,0 +1,105 @@
+use chrono::Local;
+use common::{ApiError, Course, SharedView};
+use reqwasm::http::Method;
+use yew::{function_component, html, use_effect_with_deps, use_state, Callback, Properties};
+
+use crate::SERVER;
+
+#[derive(Clone, Properties, PartialEq)]
+pub struct SharedPlanProps {
+    pub token: String,
+}
+
+/// The token of the share link this page was opened from, if any.
+pub fn share_token() -> Option<String> {
+    let search = gloo_utils::window().location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search)
+        .ok()?
+        .get("share")
+        .filter(|token| !token.is_empty())
+}
+
+/// A course or meal shared by its owner: its timeline, and cook mode for each course,
+/// without an account or any way to change them.
+#[function_component(SharedPlan)]
+pub fn shared_plan(SharedPlanProps { token }: &SharedPlanProps) -> Html {
+    let view = use_state(|| None::<Result<SharedView, String>>);
+    let cooking = use_state(|| None::<Course>);
+    {
+        let view = view.clone();
+        use_effect_with_deps(
+            move |token: &String| {
+                let token = token.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let response = crate::api(&format!("{SERVER}/shared/{token}"))
+                        .method(Method::GET)
+                        .send()
+                        .await
+                        .unwrap();
+                    if response.ok() {
+                        view.set(Some(Ok(response.json().await.unwrap())));
+                    } else {
+                        let e: ApiError = response.json().await.unwrap();
+                        log::warn!("share {token}: {}", e.message());
+                        view.set(Some(Err(
+                            "This link has expired or been revoked.".to_owned()
+                        )));
+                    }
+                });
+                || ()
+            },
+            token.clone(),
+        );
+    }
+
+    let view = match view.as_ref() {
+        None => return html! { <p>{ "Loading…" }</p> },
+        Some(Err(message)) => return html! { <p style="color: #c00;">{ message }</p> },
+        Some(Ok(view)) => view,
+    };
+
+    let stop_cooking = {
+        let cooking = cooking.clone();
+        Callback::from(move |_| cooking.set(None))
+    };
+    let courses = view.courses().iter().map(|course| {
+        let cook = {
+            let cooking = cooking.clone();
+            let course = course.clone();
+            move |_| cooking.set(Some(course.clone()))
+        };
+        html! {
+            <li>
+                { course.details().name() }
+                <span style="cursor: pointer; padding-left: 1em;" title="Cook" onclick={cook}>{ "\u{25B6}" }</span>
+            </li>
+        }
+    });
+    let entries = view.timeline().iter().enumerate().map(|(i, entry)| {
+        let when = match entry.at() {
+            Some(at) => at.with_timezone(&Local).format("%a %H:%M").to_string(),
+            None => format!("T-{}", entry.before_serving()),
+        };
+        html! {
+            <div class={"stage"} style={if i % 2 == 0 {"background: #bbb;"} else {"background: #ccc;"}}>
+                <p class={"duration"}><crate::components::icon::Alarm width=32 height=32 />{ when }</p>
+                <p class={"name"} style={"padding-left: 2.5rem;"}>{ crate::components::meal_details::entry_name(entry) }</p>
+            </div>
+        }
+    });
+    let serve_at = view.timeline().last().and_then(|entry| entry.at()).copied();
+
+    html! {
+        <div>
+            if let Some(course) = cooking.as_ref() {
+                <crate::components::cook_mode::CookMode course_details={course.details().clone()}
+                    on_close={stop_cooking} local={Some(course.clone())} />
+            }
+            <h2>{ view.name() }</h2>
+            <ul>{ for courses }</ul>
+            <h3>{ "Timeline" }</h3>
+            <crate::components::gantt::Gantt bars={crate::components::gantt::meal_bars(view.timeline())} serve_at={serve_at} />
+            { for entries }
+        </div>
+    }
+}
@@ -0,0 +1,113 @@
+use common::{ApiError, Credentials, UserDetails};
+use reqwasm::http::Method;
+use web_sys::HtmlInputElement;
+use yew::{
+    function_component, html, use_node_ref, use_state, Callback, NodeRef, Properties,
+    UseStateHandle,
+};
+
+use crate::SERVER;
+
+#[derive(Properties, PartialEq)]
+pub struct SignInProps {
+    pub on_sign_in: Callback<UserDetails>,
+}
+
+/// The signed in user, or `None` if nobody is.
+pub fn fetch_user(on_fetch: Box<dyn FnOnce(Option<UserDetails>)>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let response = crate::api(&format!("{SERVER}/user"))
+            .method(Method::GET)
+            .send()
+            .await
+            .unwrap();
+        on_fetch(if response.ok() {
+            Some(response.json().await.unwrap())
+        } else {
+            None
+        });
+    });
+}
+
+pub fn sign_out(on_sign_out: Callback<()>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        crate::api(&format!("{SERVER}/logout"))
+            .method(Method::POST)
+            .send()
+            .await
+            .unwrap();
+        on_sign_out.emit(());
+    });
+}
+
+/// Send the name and password to `path`, either `user` to register or `login`.
+fn send(
+    path: &'static str,
+    name_ref: &NodeRef,
+    password_ref: &NodeRef,
+    on_sign_in: &Callback<UserDetails>,
+    error: &UseStateHandle<Option<String>>,
+) {
+    let (name, password) = match (
+        name_ref.cast::<HtmlInputElement>(),
+        password_ref.cast::<HtmlInputElement>(),
+    ) {
+        (Some(name), Some(password)) => (name.value(), password.value()),
+        _ => return,
+    };
+    let credentials = Credentials::new(&name, &password);
+    let on_sign_in = on_sign_in.clone();
+    let error = error.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let response = crate::api(&format!("{SERVER}/{path}"))
+            .method(Method::POST)
+            .body(serde_json::to_string(&credentials).unwrap())
+            .send()
+            .await
+            .unwrap();
+        if response.ok() {
+            error.set(None);
+            on_sign_in.emit(response.json().await.unwrap());
+        } else {
+            let e: ApiError = response.json().await.unwrap();
+            error.set(Some(e.message().to_owned()));
+        }
+    });
+}
+
+/// Sign in to an account, or make a new one.
+#[function_component(SignIn)]
+pub fn sign_in(SignInProps { on_sign_in }: &SignInProps) -> Html {
+    let name_ref = use_node_ref();
+    let password_ref = use_node_ref();
+    let error = use_state(|| None::<String>);
+
+    let button = |label: &str, path: &'static str| {
+        let name_ref = name_ref.clone();
+        let password_ref = password_ref.clone();
+        let on_sign_in = on_sign_in.clone();
+        let error = error.clone();
+        let onclick =
+            Callback::from(move |_| send(path, &name_ref, &password_ref, &on_sign_in, &error));
+        html! { <button onclick={onclick} style="flex: 0">{ label }</button> }
+    };
+
+    html! {
+        <div class={"content"}>
+            <div>
+                <h2>{ "Sign in" }</h2>
+                <div style="display: flex; flex-flow: row nowrap;">
+                    <input type="text" ref={name_ref.clone()} placeholder="Name …"
+                        autocapitalize="none" spellcheck="false" autocomplete="username" style="flex: 2 0px"/>
+                    <input type="password" ref={password_ref.clone()} placeholder="Password …"
+                        autocomplete="current-password" style="flex: 2 0px"/>
+                    { button("Sign in", "login") }
+                    { button("Register", "user") }
+                </div>
+                if let Some(error) = error.as_ref() {
+                    <p style="color: #c00;">{ error }</p>
+                }
+            </div>
+        </div>
+    }
+}
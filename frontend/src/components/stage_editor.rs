@@ -1,49 +1,45 @@
-use common::{CourseDetails, Stage};
-use reqwasm::http::Method;
-use web_sys::{HtmlInputElement, KeyboardEvent};
-use yew::{function_component, html, use_effect_with_deps, use_node_ref, Callback, Properties};
-
-use crate::{request, SERVER};
+use common::{NewStage, Stage, StageKind};
+use web_sys::{HtmlInputElement, HtmlSelectElement, KeyboardEvent};
+use yew::{
+    function_component, html, use_effect_with_deps, use_node_ref, use_state, Callback, Properties,
+};
 
 #[derive(Clone, Properties, PartialEq)]
 pub struct StageEditorProps {
-    pub on_change: Callback<Stage>,
-}
-
-fn add_new_stage(
-    course: CourseDetails,
-    input: HtmlInputElement,
-    duration: HtmlInputElement,
-    on_change: &Callback<Vec<Stage>>,
-) {
-    let on_change = on_change.clone();
-    let name = input.value();
-    let duration = duration.value();
-    let stage = Stage::new(&name, &duration);
-    let id = course.id().to_owned();
-    log::info!("Add stage: {:?}", stage);
-
-    wasm_bindgen_futures::spawn_local(async move {
-        let stages: Vec<Stage> = request!(
-            &format!("{SERVER}/course/{id}"),
-            Method::POST,
-            serde_json::to_string(&stage).unwrap()
-        );
-        on_change.emit(stages);
-    });
+    pub on_change: Callback<NewStage>,
+    /// Why the server rejected the last stage, if it did.
+    #[prop_or_default]
+    pub error: Option<String>,
+    /// The stage being edited, if any, to start from.
+    #[prop_or_default]
+    pub name: String,
+    #[prop_or_default]
+    pub duration: String,
+    #[prop_or_default]
+    pub kind: StageKind,
 }
 
 #[function_component(StageEditor)]
-pub fn stage_editor(StageEditorProps { on_change }: &StageEditorProps) -> Html {
+pub fn stage_editor(
+    StageEditorProps {
+        on_change,
+        error,
+        name,
+        duration,
+        kind,
+    }: &StageEditorProps,
+) -> Html {
+    let invalid = use_state(|| None);
     let stage_name_ref = use_node_ref();
     let stage_duration_ref = use_node_ref();
+    let stage_kind_ref = use_node_ref();
 
     {
         let stage_name_ref = stage_name_ref.clone();
         use_effect_with_deps(
             move |a| {
                 if let Some(input) = a.cast::<HtmlInputElement>() {
-                    input.focus();
+                    let _ = input.focus();
                 };
                 || ()
             },
@@ -54,13 +50,27 @@ pub fn stage_editor(StageEditorProps { on_change }: &StageEditorProps) -> Html {
         let on_change = on_change.clone();
         let stage_name_ref = stage_name_ref.clone();
         let stage_duration_ref = stage_duration_ref.clone();
+        let stage_kind_ref = stage_kind_ref.clone();
+        let invalid = invalid.clone();
         move || {
             if let Some(name) = stage_name_ref.cast::<HtmlInputElement>() {
                 if let Some(duration) = stage_duration_ref.cast::<HtmlInputElement>() {
                     let name = name.value();
                     let duration = duration.value();
+                    let kind = match stage_kind_ref.cast::<HtmlSelectElement>() {
+                        Some(kind) if kind.value() == "passive" => StageKind::Passive,
+                        Some(kind) if kind.value() == "resting" => StageKind::Resting,
+                        _ => StageKind::Active,
+                    };
 
-                    on_change.emit(Stage::new(&name, &duration))
+                    let stage = NewStage::new(&name, &duration).with_kind(kind);
+                    match Stage::try_from(stage.clone()) {
+                        Ok(_) => {
+                            invalid.set(None);
+                            on_change.emit(stage)
+                        }
+                        Err(e) => invalid.set(Some(e.to_string())),
+                    }
                 }
             }
         }
@@ -80,13 +90,25 @@ pub fn stage_editor(StageEditorProps { on_change }: &StageEditorProps) -> Html {
         }
     };
 
+    let message = (*invalid).clone().or_else(|| error.clone());
+
     html! {
+        <>
         <div style="display: flex; flex-flow: row nowrap;">
-            <input type="text" ref={stage_name_ref} onkeyup={onkeyup.clone()}
+            <input type="text" ref={stage_name_ref} onkeyup={onkeyup.clone()} value={name.clone()}
                 name="stage_name_editor" placeholder="Stage name …" style="flex: 4 0px; padding-right: 1em"/>
-            <input type="text" ref={stage_duration_ref} onkeyup={onkeyup.clone()}
+            <input type="text" ref={stage_duration_ref} onkeyup={onkeyup.clone()} value={duration.clone()}
                 name="stage_len_editor" placeholder="duration" style="flex: 1 0px; padding-right: 1em"/>
+            <select ref={stage_kind_ref} name="stage_kind_editor" style="flex: 0">
+                <option value="active" selected={*kind == StageKind::Active}>{ "Hands on" }</option>
+                <option value="passive" selected={*kind == StageKind::Passive}>{ "Unattended" }</option>
+                <option value="resting" selected={*kind == StageKind::Resting}>{ "Resting" }</option>
+            </select>
             <button onclick={onclick} style="flex: 0">{ "Ok" }</button>
         </div>
+        if let Some(message) = message {
+            <p class={"error"} style={"color: darkred;"}>{ message }</p>
+        }
+        </>
     }
 }
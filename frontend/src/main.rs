@@ -1,6 +1,10 @@
+// The `html!` macro in yew 0.19 expands component props into statements that
+// clippy flags as no-ops, and components without props into bindings of `()`.
+#![allow(clippy::unnecessary_operation, clippy::let_unit_value)]
+
 use yew::prelude::*;
 
-use common::CourseDetails;
+use common::{CourseDetails, MealDetails, UserDetails};
 use reqwasm::http::Method;
 mod components;
 use wasm_bindgen_futures::spawn_local;
@@ -11,13 +15,19 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 const SERVER: &str = "http://localhost:1111";
 
+/// A request to the server, carrying the sign-in cookie even though the server is
+/// another origin.
+pub fn api(url: &str) -> reqwasm::http::Request {
+    reqwasm::http::Request::new(url).credentials(reqwasm::http::RequestCredentials::Include)
+}
+
 /**
  * Documentation?
  */
 #[macro_export]
 macro_rules! request {
     ($u:expr, $m:expr) => {
-        reqwasm::http::Request::new($u)
+        $crate::api($u)
             .method($m)
             .send()
             .await
@@ -27,7 +37,7 @@ macro_rules! request {
             .unwrap()
     };
     ($u:expr, $m:expr, $b:expr) => {
-        reqwasm::http::Request::new($u)
+        $crate::api($u)
             .method($m)
             .body($b)
             .send()
@@ -39,15 +49,80 @@ macro_rules! request {
     };
 }
 
-#[function_component(App)]
-fn app() -> Html {
+#[derive(Properties, PartialEq)]
+struct PlannerProps {
+    user: UserDetails,
+    on_sign_out: Callback<()>,
+}
+
+/// Everything a signed in user can see and do.
+#[function_component(Planner)]
+fn planner(PlannerProps { user, on_sign_out }: &PlannerProps) -> Html {
     let courses = use_state(std::vec::Vec::new);
-    let selected_course = use_state(|| None);
+    let selected_course = use_state(|| None::<CourseDetails>);
     let new_course_visible = use_state(|| false);
+    let meals = use_state(std::vec::Vec::new);
+    let selected_meal = use_state(|| None);
+    let new_meal_visible = use_state(|| false);
+    let changes = use_reducer(components::server_events::Changes::default);
+
+    {
+        let changes = changes.clone();
+        use_effect_with_deps(
+            move |_| {
+                let subscription =
+                    components::server_events::subscribe(Callback::from(move |event| {
+                        changes.dispatch(event)
+                    }));
+                move || drop(subscription)
+            },
+            (),
+        );
+    }
+    {
+        // Another device added, renamed or deleted a course.
+        let courses = courses.clone();
+        let selected_course = selected_course.clone();
+        use_effect_with_deps(
+            move |revision| {
+                if *revision > 0 {
+                    spawn_local(async move {
+                        let mut fetched_courses: Vec<CourseDetails> =
+                            request!(&format!("{SERVER}/courses/"), Method::GET);
+                        fetched_courses.sort_by(|a, b| a.name().cmp(b.name()));
+                        if let Some(selected) = selected_course.as_ref() {
+                            let current = fetched_courses
+                                .iter()
+                                .find(|course| course.id() == selected.id());
+                            if current != Some(selected) {
+                                selected_course.set(current.cloned());
+                            }
+                        }
+                        courses.set(fetched_courses);
+                    });
+                }
+                || ()
+            },
+            changes.courses(),
+        );
+    }
 
     let on_course_select = {
         let selected_course = selected_course.clone();
-        Callback::from(move |course: CourseDetails| selected_course.set(Some(course)))
+        let selected_meal = selected_meal.clone();
+        Callback::from(move |course: CourseDetails| {
+            selected_meal.set(None);
+            selected_course.set(Some(course))
+        })
+    };
+
+    let on_meal_select = {
+        let selected_course = selected_course.clone();
+        let selected_meal = selected_meal.clone();
+        Callback::from(move |meal: MealDetails| {
+            selected_course.set(None);
+            selected_meal.set(Some(meal))
+        })
     };
 
     {
@@ -64,7 +139,7 @@ fn app() -> Html {
                     if fetched_courses.is_empty() {
                         new_course_visible.set(true);
                     } else {
-                        on_course_select.emit(fetched_courses.get(0).unwrap().clone());
+                        on_course_select.emit(fetched_courses.first().unwrap().clone());
                         new_course_visible.set(false);
                     }
                     courses.set(fetched_courses);
@@ -74,15 +149,57 @@ fn app() -> Html {
             (),
         );
     }
+    {
+        let meals = meals.clone();
+        use_effect_with_deps(
+            move |_| {
+                components::meal_name_editor::fetch_meals(Box::new(move |fetched_meals| {
+                    meals.set(fetched_meals)
+                }));
+                || ()
+            },
+            (),
+        );
+    }
     let update_courses = {
         let courses = courses.clone();
         log::debug!("update_courses");
         Callback::from(move |course_details| courses.set(course_details))
     };
 
+    let update_meals = {
+        let meals = meals.clone();
+        Callback::from(move |meal_details| meals.set(meal_details))
+    };
+
+    let change_course = {
+        let courses = courses.clone();
+        let selected_course = selected_course.clone();
+        Callback::from(move |changed: Option<CourseDetails>| {
+            let selected = match selected_course.as_ref() {
+                Some(selected) => selected.id().to_owned(),
+                None => return,
+            };
+            let mut changed_courses: Vec<CourseDetails> = (*courses)
+                .iter()
+                .filter(|course| course.id() != selected)
+                .cloned()
+                .collect();
+            changed_courses.extend(changed.clone());
+            changed_courses.sort_by(|a, b| a.name().cmp(b.name()));
+            courses.set(changed_courses);
+            selected_course.set(changed);
+        })
+    };
+
     let details = selected_course.as_ref().map(|course_details| {
         html! {
-            <components::course_details::CourseDetailsDisplay course_details={course_details.clone()} />
+            <components::course_details::CourseDetailsDisplay course_details={course_details.clone()} on_change={change_course.clone()} revision={changes.course(course_details.id())} />
+        }
+    });
+    let meal_details = selected_meal.as_ref().map(|meal_details| {
+        html! {
+            <components::meal_details::MealDetailsDisplay meal_details={meal_details.clone()} courses={(*courses).clone()} />
         }
     });
 
@@ -93,24 +210,50 @@ fn app() -> Html {
         }
     };
 
+    let toggle_new_meal = {
+        let new_meal_visible = new_meal_visible.clone();
+        move |_| {
+            new_meal_visible.set(!*new_meal_visible);
+        }
+    };
+
+    let sign_out = {
+        let on_sign_out = on_sign_out.clone();
+        move |_| components::sign_in::sign_out(on_sign_out.clone())
+    };
+
     html! {
         <>
         <div class={"header"}>
         <h1>{ "Course Planner" }</h1>
+        <span>{ user.name() }<button onclick={sign_out} style="margin-left: 1em">{ "Sign out" }</button></span>
     </div>
     <div class={"wrapper"}>
             <div class={"content"}>
             <div>
                 <h2>{"Known Courses"}<span style="cursor: pointer; padding-left: 1em;" onclick={toggle_new_course}><crate::components::icon::Plus width=32 height=32 /></span></h2>
                 if *(new_course_visible.clone()) {
-                    <components::course_name_editor::CourseNameEditor on_change={update_courses} on_select={on_course_select.clone()} />
+                    <components::course_name_editor::CourseNameEditor on_change={update_courses.clone()} on_select={on_course_select.clone()} />
+                    <components::recipe_import::RecipeImport on_change={update_courses} on_select={on_course_select.clone()} />
                 }
                 <div class={"courses"}>
                     <components::course_list::CoursesList course_details={(*courses).clone()} on_click={on_course_select.clone()} />
                 </div>
+                <h2>{"Meals"}<span style="cursor: pointer; padding-left: 1em;" onclick={toggle_new_meal}><crate::components::icon::Plus width=32 height=32 /></span></h2>
+                if *(new_meal_visible.clone()) {
+                    <components::meal_name_editor::MealNameEditor on_change={update_meals} on_select={on_meal_select.clone()} />
+                }
+                <div class={"courses"}>
+                    <components::meal_list::MealsList meal_details={(*meals).clone()} on_click={on_meal_select.clone()} />
+                </div>
+                <h2>{"Planned"}</h2>
+                <components::calendar_feed::CalendarFeed courses={(*courses).clone()} meals={(*meals).clone()} />
+                <h2>{"API keys"}</h2>
+                <components::api_keys::ApiKeys />
             </div>
             <div style={"flex: 2 0px"}>
                 { for details }
+                { for meal_details }
             </div>
             </div>
         </div>
@@ -118,6 +261,64 @@ fn app() -> Html {
     }
 }
 
+/// Who is signed in: unknown until the server has been asked, then someone or nobody.
+#[function_component(App)]
+fn app() -> Html {
+    let share = use_state(components::shared_plan::share_token);
+    let user = use_state(|| None::<Option<UserDetails>>);
+    {
+        let user = user.clone();
+        use_effect_with_deps(
+            move |_| {
+                components::sign_in::fetch_user(Box::new(move |fetched| user.set(Some(fetched))));
+                || ()
+            },
+            (),
+        );
+    }
+    let on_sign_in = {
+        let user = user.clone();
+        Callback::from(move |signed_in| user.set(Some(Some(signed_in))))
+    };
+    let on_sign_out = {
+        let user = user.clone();
+        Callback::from(move |_| user.set(Some(None)))
+    };
+
+    // Guests on a share link see only what was shared, signed in or not.
+    if let Some(token) = share.as_ref() {
+        return html! {
+            <>
+            <div class={"header"}>
+                <h1>{ "Course Planner" }</h1>
+            </div>
+            <div class={"wrapper"}>
+                <div class={"content"}>
+                    <components::shared_plan::SharedPlan token={token.clone()} />
+                </div>
+            </div>
+            </>
+        };
+    }
+
+    match user.as_ref() {
+        None => html! {},
+        Some(Some(user)) => html! {
+            <Planner user={user.clone()} on_sign_out={on_sign_out} />
+        },
+        Some(None) => html! {
+            <>
+            <div class={"header"}>
+                <h1>{ "Course Planner" }</h1>
+            </div>
+            <div class={"wrapper"}>
+                <components::sign_in::SignIn on_sign_in={on_sign_in} />
+            </div>
+            </>
+        },
+    }
+}
+
 fn main() {
     wasm_logger::init(wasm_logger::Config::default());
     yew::start_app::<App>();